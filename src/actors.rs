@@ -1,15 +1,22 @@
+#![allow(dead_code)]
 
 // Engine
 use crate::math::{Vector2, normalize_degrees, radians};
-use crate::map::Thing;
+use crate::map::{Thing, ThingFlags};
 use crate::doom::Doom;
 use crate::configure;
+use crate::ticcmd::TicCmd;
 use crate::types::ThingType;
-use crate::collision::CollisionClass;
+use crate::collision::{CollisionClass, get_default_height, get_default_radius};
+use crate::projectiles::{Projectile, ProjectileType};
+use crate::trace::check_sight;
+use crate::inventory::{AmmoType, Inventory, KeyType, PowerType, WeaponType};
+use crate::weapons::PlayerWeapon;
+use crate::specials::{cross_special_lines, use_lines};
+use crate::time::{tics, TIC_RATE};
 // Utils
 use std::boxed::Box;
-use winit::keyboard::KeyCode;
-use winit_input_helper::WinitInputHelper;
+use winit_input_helper::{TextChar, WinitInputHelper};
 
 #[derive(Debug, Clone)]
 pub struct Transform {
@@ -62,7 +69,10 @@ impl Transform {
 }
 
 pub trait Actor {
-    fn control(&mut self, input: &WinitInputHelper, last_frame_time: f64, blending_factor: f64);
+    // Command of the tic, only the player takes them
+    fn command(&mut self, _cmd: &TicCmd) {
+    }
+
     fn update<'wad>(&mut self, engine: &Doom<'wad>, last_frame_time: f64, blending_factor: f64); 
 
     fn type_id(&self) -> u16;
@@ -80,6 +90,75 @@ pub trait Actor {
     fn get_last_transform(&self) -> &Transform;
     fn get_transform(&self) -> &Transform;
     fn set_transform(&mut self, transform: &Transform);
+
+    // Body
+    fn floor_z(&self) -> f32 {
+        self.height()
+    }
+
+    fn body_height(&self) -> f32 {
+        get_default_height(&self.thing_type()) as f32
+    }
+
+    // Health
+    fn health(&self) -> i32 {
+        0
+    }
+
+    fn damage<'wad>(&mut self, _engine: &Doom<'wad>, _amount: i32) {
+    }
+
+    // Health of a saved game
+    fn set_health(&mut self, _health: i32) {
+    }
+
+    // Removed actors are dropped by the engine at the end of the update
+    fn is_removed(&self) -> bool {
+        false
+    }
+
+    // Index of the map thing spawning the actor, None for the player and the spawned ones
+    fn thing_id(&self) -> Option<usize> {
+        None
+    }
+
+    // Downcast
+    fn as_player(&self) -> Option<&Player> {
+        None
+    }
+
+    fn as_player_mut(&mut self) -> Option<&mut Player> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArmorType {
+    None,
+    Green,
+    Blue
+}
+
+impl ArmorType {
+    // Divisor of the damage absorbed by the armor
+    pub fn absorption(&self) -> i32 {
+        match self {
+            ArmorType::None => 0,
+            ArmorType::Green => 3,
+            ArmorType::Blue => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerState {
+    Alive,
+    Dead
+}
+
+// Stable identity of an actor while it lives inside the engine
+pub fn actor_id(actor: &dyn Actor) -> usize {
+    actor as *const dyn Actor as *const () as usize
 }
 
 pub struct Player {
@@ -91,15 +170,59 @@ pub struct Player {
     transform: Transform,
     last_transform: Transform,
     configure: configure::Player,
-    // Control
-    control_direction: Vector2<f32>, 
-    control_angle: f32,
-    control_angle_update: f32,
+    // Control, the command of the tic
+    command: TicCmd,
+    // Vertical look in degrees, up is positive
+    pitch: f32,
+    // Units per tic, as momx and momy
+    momentum: Vector2<f32>,
+    // Position and move of the last tic, to find how much of it the walls let through
+    move_start: Vector2<f32>,
+    move_delta: Vector2<f32>,
     player_jump: f32,
     player_jump_lock: bool,
+    cheat: String,
+    // Attack
+    weapon: PlayerWeapon,
+    // Status
+    state: PlayerState,
+    health: i32,
+    armor_points: i32,
+    armor_type: ArmorType,
+    god_mode: bool,
+    pain_time: f32,
+    damage_count: f32,
+    bonus_count: f32,
+    view_height: f32,
+    inventory: Inventory,
 }
 
 impl Player {
+    const PAIN_TICS: u32 = 8;
+    const MAX_HEALTH: i32 = 100;
+    const MAX_DAMAGE_COUNT: f32 = 100.0;
+    // Bonus health and armor cap
+    const MAX_BONUS: i32 = 200;
+    const BONUS_ADD: f32 = 6.0;
+    // Height of the view once dead, reached dropping one unit per tic
+    const DEAD_VIEW_HEIGHT: f32 = 6.0;
+    // Only telefrags get through god mode
+    const GOD_MODE_LIMIT: i32 = 1000;
+    const CHEAT_GOD_MODE: &'static str = "iddqd";
+    // Vertical look limit, in degrees
+    const MAX_PITCH: f32 = 32.0;
+    // Vanilla movement in units per tic: 2048 of thrust for each unit of move, friction of 0xE800
+    const THRUST: f32 = 2048.0 / 65536.0;
+    const FRICTION: f32 = 0.90625;
+    const STOP_SPEED: f32 = 1.0 / 16.0;
+    const MAX_MOVE: f32 = 30.0;
+    // PLAYPAL layout
+    const START_RED_PALETTES: usize = 1;
+    const RED_PALETTES: usize = 8;
+    const START_BONUS_PALETTES: usize = 9;
+    const BONUS_PALETTES: usize = 4;
+    const RADIATION_PALETTE: usize = 13;
+
     pub fn new<'wad>(thing: &'wad Thing, configure: &configure::Configure) -> Box<dyn Actor> {
         let transform = {
             Transform::set({
@@ -119,43 +242,456 @@ impl Player {
             transform: transform.clone(),
             configure: configure.player.clone(),
             // Control
-            control_direction: Vector2::zeros(),
-            control_angle: 0.0,
-            control_angle_update: 0.0,
+            command: TicCmd::default(),
+            pitch: 0.0,
+            momentum: Vector2::zeros(),
+            move_start: transform.position,
+            move_delta: Vector2::zeros(),
             player_jump: 0.0,
-            player_jump_lock: false
+            player_jump_lock: false,
+            cheat: String::new(),
+            // Attack
+            weapon: PlayerWeapon::new(WeaponType::Pistol),
+            // Status
+            state: PlayerState::Alive,
+            health: Player::MAX_HEALTH,
+            armor_points: 0,
+            armor_type: ArmorType::None,
+            god_mode: false,
+            pain_time: 0.0,
+            damage_count: 0.0,
+            bonus_count: 0.0,
+            view_height: configure.player.height as f32,
+            inventory: Inventory::new(),
         })
     }
+
+    // Vertical look, the software renderer shears the view by it
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn weapon(&self) -> &PlayerWeapon {
+        &self.weapon
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    pub fn inventory_mut(&mut self) -> &mut Inventory {
+        &mut self.inventory
+    }
+
+    // Decays by one each tic, drives the gold palette flash
+    pub fn bonus_count(&self) -> i32 {
+        self.bonus_count.ceil() as i32
+    }
+
+    // As P_GiveBody
+    fn give_body(&mut self, amount: i32) -> bool {
+        if self.health >= Player::MAX_HEALTH {
+            return false;
+        }
+        self.health = (self.health + amount).min(Player::MAX_HEALTH);
+        return true;
+    }
+
+    // As P_GiveArmor, never replace a better armor
+    fn give_armor(&mut self, armor_type: ArmorType) -> bool {
+        let points = match armor_type {
+            ArmorType::Green => 100,
+            ArmorType::Blue => 200,
+            ArmorType::None => 0
+        };
+        if self.armor_points >= points {
+            return false;
+        }
+        self.armor_type = armor_type;
+        self.armor_points = points;
+        return true;
+    }
+
+    // As P_TouchSpecialThing, returns the pickup message if the item has been taken
+    pub fn touch_special(&mut self, item: ThingType, dropped: bool) -> Option<&'static str> {
+        if self.state == PlayerState::Dead {
+            return None;
+        }
+        let message = match item {
+            // Armor
+            ThingType::GreenArmor => {
+                if !self.give_armor(ArmorType::Green) { return None; }
+                "Picked up the armor."
+            }
+            ThingType::BlueArmor => {
+                if !self.give_armor(ArmorType::Blue) { return None; }
+                "Picked up the MegaArmor!"
+            }
+            // Bonus
+            ThingType::HealthBonus => {
+                self.health = (self.health + 1).min(Player::MAX_BONUS);
+                "Picked up a health bonus."
+            }
+            ThingType::ArmorBonus => {
+                self.armor_points = (self.armor_points + 1).min(Player::MAX_BONUS);
+                if self.armor_type == ArmorType::None {
+                    self.armor_type = ArmorType::Green;
+                }
+                "Picked up an armor bonus."
+            }
+            ThingType::Soulsphere => {
+                self.health = (self.health + 100).min(Player::MAX_BONUS);
+                "Supercharge!"
+            }
+            ThingType::Megasphere => {
+                self.health = Player::MAX_BONUS;
+                self.give_armor(ArmorType::Blue);
+                "MegaSphere!"
+            }
+            // Health
+            ThingType::Stimpack => {
+                if !self.give_body(10) { return None; }
+                "Picked up a stimpack."
+            }
+            ThingType::Medikit => {
+                if !self.give_body(25) { return None; }
+                // Vanilla tests the health after healing, so the first message never shows
+                if self.health < 25 { "Picked up a medikit that you REALLY need!" } else { "Picked up a medikit." }
+            }
+            // Powerups
+            ThingType::Invulnerability => {
+                if !self.inventory.give_power(PowerType::Invulnerability) { return None; }
+                "Invulnerability!"
+            }
+            ThingType::Berserk => {
+                if !self.inventory.give_power(PowerType::Strength) { return None; }
+                self.health = self.health.max(Player::MAX_HEALTH);
+                self.inventory.set_pending_weapon(Some(WeaponType::Fist));
+                "Berserk!"
+            }
+            ThingType::PartialInvisibility => {
+                if !self.inventory.give_power(PowerType::Invisibility) { return None; }
+                "Partial Invisibility"
+            }
+            ThingType::RadSuit => {
+                if !self.inventory.give_power(PowerType::IronFeet) { return None; }
+                "Radiation Shielding Suit"
+            }
+            ThingType::ComputerMap => {
+                if !self.inventory.give_power(PowerType::AllMap) { return None; }
+                "Computer Area Map"
+            }
+            ThingType::LightAmp => {
+                if !self.inventory.give_power(PowerType::Infrared) { return None; }
+                "Light Amplification Visor"
+            }
+            // Ammo
+            ThingType::Clip => {
+                if !self.inventory.give_ammo(AmmoType::Clip, if dropped { 0 } else { 1 }) { return None; }
+                "Picked up a clip."
+            }
+            ThingType::BulletBox => {
+                if !self.inventory.give_ammo(AmmoType::Clip, 5) { return None; }
+                "Picked up a box of bullets."
+            }
+            ThingType::Rocket => {
+                if !self.inventory.give_ammo(AmmoType::Misl, 1) { return None; }
+                "Picked up a rocket."
+            }
+            ThingType::RocketBox => {
+                if !self.inventory.give_ammo(AmmoType::Misl, 5) { return None; }
+                "Picked up a box of rockets."
+            }
+            ThingType::EnergyCell => {
+                if !self.inventory.give_ammo(AmmoType::Cell, 1) { return None; }
+                "Picked up an energy cell."
+            }
+            ThingType::EnergyPack => {
+                if !self.inventory.give_ammo(AmmoType::Cell, 5) { return None; }
+                "Picked up an energy cell pack."
+            }
+            ThingType::Shell4 => {
+                if !self.inventory.give_ammo(AmmoType::Shell, 1) { return None; }
+                "Picked up 4 shotgun shells."
+            }
+            ThingType::ShellBox => {
+                if !self.inventory.give_ammo(AmmoType::Shell, 5) { return None; }
+                "Picked up a box of shotgun shells."
+            }
+            // Keys
+            ThingType::BlueCard => {
+                if !self.inventory.give_key(KeyType::BlueCard) { return None; }
+                "Picked up a blue keycard."
+            }
+            ThingType::YellowCard => {
+                if !self.inventory.give_key(KeyType::YellowCard) { return None; }
+                "Picked up a yellow keycard."
+            }
+            ThingType::RedCard => {
+                if !self.inventory.give_key(KeyType::RedCard) { return None; }
+                "Picked up a red keycard."
+            }
+            ThingType::BlueSkull => {
+                if !self.inventory.give_key(KeyType::BlueSkull) { return None; }
+                "Picked up a blue skull key."
+            }
+            ThingType::YellowSkull => {
+                if !self.inventory.give_key(KeyType::YellowSkull) { return None; }
+                "Picked up a yellow skull key."
+            }
+            ThingType::RedSkull => {
+                if !self.inventory.give_key(KeyType::RedSkull) { return None; }
+                "Picked up a red skull key."
+            }
+            ThingType::Backpack => {
+                self.inventory.give_backpack();
+                "Picked up a backpack full of ammo!"
+            }
+            // Weapons
+            ThingType::BFG9000 => {
+                if !self.inventory.give_weapon(WeaponType::BFG, dropped) { return None; }
+                "You got the BFG9000!  Oh, yes."
+            }
+            ThingType::Chaingun => {
+                if !self.inventory.give_weapon(WeaponType::Chaingun, dropped) { return None; }
+                "You got the chaingun!"
+            }
+            ThingType::Chainsaw => {
+                if !self.inventory.give_weapon(WeaponType::Chainsaw, dropped) { return None; }
+                "A chainsaw!  Find some meat!"
+            }
+            ThingType::RocketLauncher => {
+                if !self.inventory.give_weapon(WeaponType::RocketLauncher, dropped) { return None; }
+                "You got the rocket launcher!"
+            }
+            ThingType::PlasmaRifle => {
+                if !self.inventory.give_weapon(WeaponType::PlasmaRifle, dropped) { return None; }
+                "You got the plasma gun!"
+            }
+            ThingType::Shotgun => {
+                if !self.inventory.give_weapon(WeaponType::Shotgun, dropped) { return None; }
+                "You got the shotgun!"
+            }
+            ThingType::SuperShotgun => {
+                if !self.inventory.give_weapon(WeaponType::SuperShotgun, dropped) { return None; }
+                "You got the super shotgun!"
+            }
+            _ => return None
+        };
+        self.bonus_count += Player::BONUS_ADD;
+        return Some(message);
+    }
+
+    pub fn state(&self) -> PlayerState {
+        self.state
+    }
+
+    pub fn armor(&self) -> i32 {
+        self.armor_points
+    }
+
+    pub fn armor_type(&self) -> ArmorType {
+        self.armor_type
+    }
+
+    // Restored by the save games
+    pub fn set_status(&mut self, health: i32, armor_points: i32, armor_type: ArmorType, weapon: WeaponType) {
+        self.health = health;
+        self.armor_points = armor_points;
+        self.armor_type = armor_type;
+        self.weapon = PlayerWeapon::new(weapon);
+    }
+
+    // Status and inventory of the player of the last map, a dead one is reborn.
+    // Keys and powers are left behind, as G_PlayerFinishLevel.
+    pub fn carry_over(&mut self, previous: &Player) {
+        if previous.state == PlayerState::Dead {
+            return;
+        }
+        self.health = previous.health;
+        self.armor_points = previous.armor_points;
+        self.armor_type = previous.armor_type;
+        self.god_mode = previous.god_mode;
+        self.inventory = previous.inventory.clone();
+        self.inventory.finish_level();
+        self.weapon = PlayerWeapon::new(previous.weapon.weapon());
+    }
+
+    pub fn god_mode(&self) -> bool {
+        self.god_mode
+    }
+
+    pub fn set_god_mode(&mut self, enable: bool) {
+        self.god_mode = enable;
+    }
+
+    pub fn in_pain(&self) -> bool {
+        self.pain_time > 0.0
+    }
+
+    // Decays by one each tic, drives the red palette flash
+    pub fn damage_count(&self) -> i32 {
+        self.damage_count.ceil() as i32
+    }
+
+    // PLAYPAL palette of the view, as ST_doPaletteStuff
+    pub fn palette(&self) -> usize {
+        let mut count = self.damage_count();
+        if self.inventory.has_power(PowerType::Strength) {
+            // The berserk red fades out in the first seconds
+            let strength = ((self.inventory.power(PowerType::Strength) - 1.0) * TIC_RATE) as i32;
+            count = count.max(12 - (strength >> 6));
+        }
+        let iron_feet = (self.inventory.power(PowerType::IronFeet) * TIC_RATE) as i32;
+        if count > 0 {
+            return Player::START_RED_PALETTES + (((count + 7) >> 3) as usize).min(Player::RED_PALETTES - 1);
+        } else if self.bonus_count() > 0 {
+            return Player::START_BONUS_PALETTES + (((self.bonus_count() + 7) >> 3) as usize).min(Player::BONUS_PALETTES - 1);
+        } else if iron_feet > 4 * 32 || iron_feet & 8 != 0 {
+            return Player::RADIATION_PALETTE;
+        }
+        return 0;
+    }
+
+    fn die(&mut self) {
+        self.state = PlayerState::Dead;
+        self.health = self.health.min(0);
+        self.player_jump = 0.0;
+        self.player_jump_lock = false;
+    }
+
+    pub fn momentum(&self) -> &Vector2<f32> {
+        &self.momentum
+    }
+
+    // As P_SlideMove, only the momentum along the blocking walls is kept.
+    // A longer move than the one tried is a teleport, which stops the player.
+    fn clip_momentum(&mut self) {
+        let moved = self.transform.position - self.move_start;
+        let tried = self.move_delta.magnitude();
+        if (moved - self.move_delta).magnitude() < f32::EPSILON {
+            return;
+        }
+        // The walls may push out the player by a bit
+        if moved.magnitude() > tried + 1.0 || moved.magnitude() < f32::EPSILON {
+            self.momentum = Vector2::zeros();
+            return;
+        }
+        let direction = moved.normalize();
+        self.momentum = direction * self.momentum.dot(&direction).max(0.0);
+    }
+
+    // As P_MovePlayer and P_XYMovement, the command thrusts and the friction slows down
+    fn move_player(&mut self, cmd: &TicCmd, last_frame_time: f32) {
+        let tics = TIC_RATE * last_frame_time;
+        let angle = radians(self.transform.angle);
+        let forward = Vector2::new(angle.cos(), angle.sin());
+        let right = Vector2::new(angle.sin(), -angle.cos());
+        let thrust = forward * cmd.forward_move as f32 + right * cmd.side_move as f32;
+        self.momentum += thrust * Player::THRUST * tics;
+        self.momentum = Vector2::new(
+            self.momentum.x.clamp(-Player::MAX_MOVE, Player::MAX_MOVE),
+            self.momentum.y.clamp(-Player::MAX_MOVE, Player::MAX_MOVE),
+        );
+        self.move_start = self.transform.position;
+        self.move_delta = self.momentum * tics;
+        self.transform.position += self.move_delta;
+        // Stop once slow and without a move
+        if cmd.forward_move == 0 && cmd.side_move == 0
+        && self.momentum.x.abs() < Player::STOP_SPEED
+        && self.momentum.y.abs() < Player::STOP_SPEED {
+            self.momentum = Vector2::zeros();
+        } else {
+            self.momentum = self.momentum * Player::FRICTION.powf(tics);
+        }
+    }
+
+    // As P_DeathThink: drop the view, restart the level on use
+    fn death_think<'wad>(&mut self, engine: &Doom<'wad>, last_frame_time: f32, use_line: bool) {
+        self.view_height = (self.view_height - TIC_RATE * last_frame_time).max(Player::DEAD_VIEW_HEIGHT);
+        self.transform.height = engine.bsp.floor_height(&self.transform.position_as_int()) as f32 + self.view_height;
+        if use_line {
+            engine.restart_level();
+        }
+    }
+
+    // The weapon is moved out while it runs, as it needs the whole player
+    fn update_weapon<'wad>(&mut self, engine: &Doom<'wad>, last_frame_time: f32, cmd: &TicCmd) {
+        let mut weapon = std::mem::take(&mut self.weapon);
+        weapon.set_fire(cmd.fire);
+        if let Some(slot) = cmd.weapon {
+            weapon.select_slot(slot);
+        }
+        weapon.update(engine, self, last_frame_time);
+        self.weapon = weapon;
+    }
+
+    // Typed text, as ST_Responder
+    pub fn check_cheats(&mut self, input: &WinitInputHelper) {
+        for text in input.text() {
+            // Cheats are ASCII, other chars break the sequence
+            match text {
+                TextChar::Char(c) if c.is_ascii() => self.cheat.push(c.to_ascii_lowercase()),
+                TextChar::Char(_) => self.cheat.clear(),
+                _ => ()
+            }
+        }
+        if self.cheat.ends_with(Player::CHEAT_GOD_MODE) {
+            self.god_mode = !self.god_mode;
+            self.cheat.clear();
+        }
+        // Keep only the tail
+        if self.cheat.len() > Player::CHEAT_GOD_MODE.len() {
+            let start = self.cheat.len() - Player::CHEAT_GOD_MODE.len();
+            self.cheat = self.cheat[start..].to_string();
+        }
+    }
 }
 
 #[allow(unused_variables)]
 impl Actor for Player {
     fn update<'wad>(&mut self, engine: &Doom<'wad>, last_frame_time: f64, blending_factor: f64) {
         let last_frame_time = last_frame_time as f32;
+        self.clip_momentum();
+        // Triggers and secrets reached by the last move, once the collisions are solved
+        if self.state != PlayerState::Dead {
+            cross_special_lines(engine, &self.last_transform.position, &self.transform.position);
+            if let Some(sector_id) = engine.bsp.sector_id(&self.transform.position_as_int()) {
+                engine.stats.borrow_mut().enter_sector(&engine.map, sector_id);
+            }
+        }
         self.last_transform = self.transform.clone();
-        // Angle
-        if self.control_angle != 0.0 {
-            self.control_angle /= self.control_angle_update;
-            self.transform.angle = normalize_degrees(self.transform.angle + (self.control_angle * self.configure.angle_speed * last_frame_time));
-            self.control_angle = 0.0;
-            self.control_angle_update = 0.0;
-        }
-        // Get move direction
-        if self.control_direction.x != 0.0 || self.control_direction.y != 0.0 {
-            let direction = self.control_direction.normalize();
-            self.control_direction = Vector2::zeros();
-            // Move rotation
-            let psin = radians(self.transform.angle - 90.0).sin();
-            let pcos = radians(self.transform.angle - 90.0).cos();
-            // Delta
-            let velocity = Vector2::new(
-                direction.x * pcos - direction.y * psin,
-                direction.x * psin + direction.y * pcos,
-            ) * self.configure.speed;
-            // New position
-            self.transform.position += velocity * last_frame_time;
+        // Status
+        self.pain_time = (self.pain_time - last_frame_time).max(0.0);
+        self.damage_count = (self.damage_count - TIC_RATE * last_frame_time).max(0.0);
+        self.bonus_count = (self.bonus_count - TIC_RATE * last_frame_time).max(0.0);
+        self.inventory.update(last_frame_time);
+        let cmd = std::mem::take(&mut self.command);
+        if self.state == PlayerState::Dead {
+            self.move_player(&TicCmd::default(), last_frame_time);
+            self.death_think(engine, last_frame_time, cmd.use_line);
+            self.update_weapon(engine, last_frame_time, &TicCmd::default());
+            return;
+        }
+        // Use
+        if cmd.use_line {
+            use_lines(engine, self);
+        }
+        // Turn and look
+        if cmd.angle_turn != 0.0 {
+            self.transform.angle = normalize_degrees(self.transform.angle + cmd.angle_turn);
         }
+        self.pitch = (self.pitch + cmd.look).clamp(-Player::MAX_PITCH, Player::MAX_PITCH);
+        // Move
+        self.move_player(&cmd, last_frame_time);
         // Height
+        if cmd.jump
+        && self.player_jump < self.configure.jump
+        && !self.player_jump_lock {
+            self.player_jump += self.configure.jump_speed * last_frame_time;
+        } else if self.player_jump != 0.0 {
+            self.player_jump_lock = true;
+        }
         if self.player_jump_lock {
             self.player_jump -= self.configure.jump_speed * last_frame_time;
             if self.player_jump <= 0.0 {
@@ -164,44 +700,12 @@ impl Actor for Player {
             }
         }
         self.transform.height = engine.bsp.floor_height(&self.transform.position_as_int()) as f32 + self.configure.height as f32 + self.player_jump;
-
+        // Attack
+        self.update_weapon(engine, last_frame_time, &cmd);
     }
 
-    fn control(&mut self, input: &WinitInputHelper, last_frame_time: f64, blending_factor: f64) {
-        let last_frame_time = last_frame_time as f32;
-        if  input.key_held(KeyCode::KeyW) 
-        && !input.key_held(KeyCode::KeyS) {
-            self.control_direction += Vector2::new(0.0, 1.0);
-        }
-        if !input.key_held(KeyCode::KeyW) 
-        &&  input.key_held(KeyCode::KeyS) {
-            self.control_direction -= Vector2::new(0.0, 1.0);
-        }   
-        if  input.key_held(KeyCode::KeyA) 
-        && !input.key_held(KeyCode::KeyD) {
-            self.control_direction -= Vector2::new(1.0, 0.0);
-        }
-        if !input.key_held(KeyCode::KeyA) 
-        &&  input.key_held(KeyCode::KeyD) {
-            self.control_direction += Vector2::new(1.0, 0.0);
-        }      
-        if  input.key_held(KeyCode::ArrowLeft) 
-        && !input.key_held(KeyCode::ArrowRight) {
-            self.control_angle += 1.0;
-            self.control_angle_update += 1.0;
-        }
-        if !input.key_held(KeyCode::ArrowLeft) 
-        &&  input.key_held(KeyCode::ArrowRight) {
-            self.control_angle -= 1.0;
-            self.control_angle_update += 1.0;
-        }
-        if input.key_held(KeyCode::KeyE) 
-        && self.player_jump < self.configure.jump
-        && !self.player_jump_lock {
-            self.player_jump += self.configure.jump_speed * last_frame_time;
-        } else if self.player_jump != 0.0 {
-            self.player_jump_lock = true;
-        }
+    fn command(&mut self, cmd: &TicCmd) {
+        self.command = *cmd;
     }
 
     fn type_id(&self) -> u16 {
@@ -247,4 +751,580 @@ impl Actor for Player {
     fn set_transform(&mut self, transform: &Transform) {
         self.transform = transform.clone();
     }
-}
\ No newline at end of file
+
+    fn floor_z(&self) -> f32 {
+        self.transform.height() - self.view_height
+    }
+
+    fn health(&self) -> i32 {
+        self.health
+    }
+
+    // As P_DamageMobj for players, armor takes its share first
+    fn damage<'wad>(&mut self, engine: &Doom<'wad>, amount: i32) {
+        if self.state == PlayerState::Dead {
+            return;
+        }
+        if (self.god_mode || self.inventory.has_power(PowerType::Invulnerability))
+        && amount < Player::GOD_MODE_LIMIT {
+            return;
+        }
+        let mut amount = amount;
+        if self.armor_type != ArmorType::None {
+            let mut saved = amount / self.armor_type.absorption();
+            if self.armor_points <= saved {
+                saved = self.armor_points;
+                self.armor_type = ArmorType::None;
+            }
+            self.armor_points -= saved;
+            amount -= saved;
+        }
+        self.health -= amount;
+        self.damage_count = (self.damage_count + amount as f32).min(Player::MAX_DAMAGE_COUNT);
+        if self.health <= 0 {
+            // As A_PlayerScream
+            engine.start_sound(if self.health < -50 { "pdiehi" } else { "pldeth" }, Some(self));
+            self.die();
+        } else {
+            self.pain_time = tics(Player::PAIN_TICS);
+            engine.start_sound("plpain", Some(self));
+        }
+    }
+
+    fn as_player(&self) -> Option<&Player> {
+        Some(self)
+    }
+
+    fn as_player_mut(&mut self) -> Option<&mut Player> {
+        Some(self)
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MonsterState {
+    Idle,
+    See,
+    Attack,
+    Pain,
+    Dying,
+    Dead
+}
+
+pub struct Monster {
+    type_id: u16,
+    thing_type: ThingType,
+    collision_class: CollisionClass,
+    flags: u16,
+    // Transformation
+    transform: Transform,
+    last_transform: Transform,
+    // Status
+    health: i32,
+    state: MonsterState,
+    state_time: f32,
+    // Thinking, once per tic
+    think_time: f32,
+    reaction_time: u32,
+    thing_id: usize,
+}
+
+impl Monster {
+    const PAIN_TICS: u32 = 6;
+    const DEATH_TICS: u32 = 25;
+    const ATTACK_TICS: u32 = 16;
+    const REACTION_TICS: u32 = 8;
+    const SIGHT_RANGE: f32 = 2048.0;
+    const MELEE_RANGE: f32 = 64.0;
+
+    pub fn new<'wad>(thing_id: usize, thing: &'wad Thing) -> Box<dyn Actor> {
+        let thing_type = ThingType::try_from(thing.type_id).unwrap_or(ThingType::Unknown);
+        let transform = Transform::set({
+                let position_i16 = thing.position;
+                Vector2::<f32>::from(&position_i16)
+            },
+            thing.angle as f32,
+            0.0
+        );
+        Box::new(Monster {
+            type_id: thing.type_id,
+            thing_type: thing_type,
+            collision_class: CollisionClass::new(thing_type),
+            flags: thing.flags,
+            last_transform: transform.clone(),
+            transform: transform,
+            health: thing_type.spawn_health(),
+            state: MonsterState::Idle,
+            state_time: 0.0,
+            think_time: 0.0,
+            reaction_time: Monster::REACTION_TICS,
+            thing_id: thing_id,
+        })
+    }
+
+    // Only monsters with a missile attack do fight back for now
+    fn missile(&self) -> Option<ProjectileType> {
+        match self.thing_type {
+            ThingType::Imp => Some(ProjectileType::ImpFireball),
+            _ => None
+        }
+    }
+
+    // Wake up when the player is in front and visible, as P_LookForPlayers
+    fn look_for_player<'wad>(&self, engine: &Doom<'wad>, target: &dyn Actor) -> bool {
+        if target.health() <= 0 {
+            return false;
+        }
+        let delta = *target.position() - self.transform.position;
+        let facing = Vector2::new(radians(self.transform.angle).cos(), radians(self.transform.angle).sin());
+        if delta.dot(&facing) < 0.0 && delta.magnitude() > Monster::MELEE_RANGE {
+            return false;
+        }
+        return self.can_see(engine, target);
+    }
+
+    // The sector heard the player, as A_Look ambush monsters wake only seeing it
+    fn hear_player<'wad>(&self, engine: &Doom<'wad>, target: &dyn Actor) -> bool {
+        if target.health() <= 0 {
+            return false;
+        }
+        let heard = engine.bsp.sector_id(&self.transform.position_as_int())
+            .and_then(|sector_id| engine.sound_targets.borrow().target(sector_id));
+        if heard != Some(actor_id(target)) {
+            return false;
+        }
+        return self.flags & ThingFlags::Ambush.value() == 0 || self.can_see(engine, target);
+    }
+
+    fn can_see<'wad>(&self, engine: &Doom<'wad>, target: &dyn Actor) -> bool {
+        if self.transform.position.distance(target.position()) > Monster::SIGHT_RANGE {
+            return false;
+        }
+        let eye = self.transform.height + self.body_height() * 0.75;
+        let target_z = target.floor_z() + target.body_height() / 2.0;
+        return check_sight(&engine.map, &self.transform.position, eye, target.position(), target_z);
+    }
+
+    // Chance to fire grows when closer, as P_CheckMissileRange
+    fn check_missile_range<'wad>(&self, engine: &Doom<'wad>, target: &dyn Actor) -> bool {
+        if self.reaction_time > 0 {
+            return false;
+        }
+        let distance = (self.transform.position.distance(target.position()) - 64.0).min(200.0);
+        return (engine.random.borrow_mut().next() as f32) >= distance;
+    }
+
+    fn think<'wad>(&mut self, engine: &Doom<'wad>) {
+        let target = match engine.player() {
+            Some(target) => target,
+            None => return
+        };
+        let target = target.borrow();
+        match self.state {
+            MonsterState::Idle => {
+                if self.hear_player(engine, target.as_ref()) || self.look_for_player(engine, target.as_ref()) {
+                    self.set_state(MonsterState::See);
+                    self.play_sound(engine, self.thing_type.sight_sounds());
+                }
+            }
+            MonsterState::See => {
+                self.reaction_time = self.reaction_time.saturating_sub(1);
+                if target.health() <= 0 {
+                    self.set_state(MonsterState::Idle);
+                    return;
+                }
+                let delta = *target.position() - self.transform.position;
+                self.transform.angle = delta.y.atan2(delta.x).to_degrees();
+                if let Some(missile) = self.missile() {
+                    if self.can_see(engine, target.as_ref()) && self.check_missile_range(engine, target.as_ref()) {
+                        Projectile::spawn_missile(engine, missile, self, target.as_ref());
+                        self.set_state(MonsterState::Attack);
+                    }
+                }
+            }
+            _ => ()
+        }
+    }
+
+    pub fn state(&self) -> MonsterState {
+        self.state
+    }
+
+    // One of the sounds at random
+    fn play_sound<'wad>(&self, engine: &Doom<'wad>, sounds: &[&str]) {
+        if !sounds.is_empty() {
+            let choice = engine.random.borrow_mut().next() as usize % sounds.len();
+            engine.start_sound(sounds[choice], Some(self));
+        }
+    }
+
+    fn set_state(&mut self, state: MonsterState) {
+        self.state = state;
+        self.state_time = match state {
+            MonsterState::Attack => tics(Monster::ATTACK_TICS),
+            MonsterState::Pain => tics(Monster::PAIN_TICS),
+            MonsterState::Dying => tics(Monster::DEATH_TICS),
+            _ => 0.0
+        };
+    }
+}
+
+#[allow(unused_variables)]
+impl Actor for Monster {
+    fn update<'wad>(&mut self, engine: &Doom<'wad>, last_frame_time: f64, blending_factor: f64) {
+        self.last_transform = self.transform.clone();
+        self.transform.height = engine.bsp.floor_height(&self.transform.position_as_int()) as f32;
+        // States with a duration
+        if self.state_time > 0.0 {
+            self.state_time -= last_frame_time as f32;
+            if self.state_time <= 0.0 {
+                match self.state {
+                    MonsterState::Attack | MonsterState::Pain => self.set_state(MonsterState::See),
+                    MonsterState::Dying => self.set_state(MonsterState::Dead),
+                    _ => ()
+                }
+            }
+        }
+        // AI
+        if self.health > 0 {
+            self.think_time += last_frame_time as f32;
+            while self.think_time >= tics(1) {
+                self.think_time -= tics(1);
+                self.think(engine);
+            }
+        }
+    }
+
+    fn type_id(&self) -> u16 {
+        self.type_id
+    }
+
+    fn thing_type(&self) -> ThingType {
+        self.thing_type
+    }
+
+    fn collision_class(&self) -> CollisionClass {
+        self.collision_class
+    }
+
+    fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    fn size(&self) -> u16 {
+        get_default_radius(&self.thing_type) as u16
+    }
+
+    fn position(&self) -> &Vector2<f32> {
+        &self.transform.position
+    }
+
+    fn angle(&self) -> f32 {
+        self.transform.angle()
+    }
+
+    fn height(&self) -> f32 {
+        self.transform.height()
+    }
+
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn get_last_transform(&self) -> &Transform {
+        &self.last_transform
+    }
+
+    fn set_transform(&mut self, transform: &Transform) {
+        self.transform = transform.clone();
+    }
+
+    fn health(&self) -> i32 {
+        self.health
+    }
+
+    // Dead monsters of a saved game are corpses already
+    fn set_health(&mut self, health: i32) {
+        self.health = health;
+        self.set_state(if health > 0 { MonsterState::Idle } else { MonsterState::Dead });
+    }
+
+    fn thing_id(&self) -> Option<usize> {
+        Some(self.thing_id)
+    }
+
+    fn damage<'wad>(&mut self, engine: &Doom<'wad>, amount: i32) {
+        if self.health <= 0 {
+            return;
+        }
+        self.health -= amount;
+        if self.health <= 0 {
+            self.set_state(MonsterState::Dying);
+            self.play_sound(engine, self.thing_type.death_sounds());
+            if self.thing_type.counts_kill() {
+                engine.stats.borrow_mut().kills += 1;
+            }
+            if let Some(item) = self.thing_type.dropped_item() {
+                engine.spawn(Pickup::dropped(item, &self.transform.position, self.transform.height));
+            }
+            return;
+        }
+        // Being hurt wakes the monster up
+        self.reaction_time = 0;
+        if self.state == MonsterState::Idle {
+            self.set_state(MonsterState::See);
+        }
+        if engine.random.borrow_mut().next() < self.thing_type.pain_chance() {
+            self.set_state(MonsterState::Pain);
+            if let Some(sound) = self.thing_type.pain_sound() {
+                engine.start_sound(sound, Some(self));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EffectType {
+    Puff,
+    Blood
+}
+
+// Short lived visual things: bullet puffs, blood splats
+pub struct Effect {
+    effect_type: EffectType,
+    transform: Transform,
+    last_transform: Transform,
+    time_left: f32,
+}
+
+impl Effect {
+    const PUFF_TICS: u32 = 16;
+    const BLOOD_TICS: u32 = 24;
+    // Puffs float up by one unit per tic
+    const PUFF_RISE: f32 = 1.0;
+
+    pub fn new(effect_type: EffectType, position: &Vector2<f32>, z: f32) -> Box<dyn Actor> {
+        let transform = Transform::set(*position, 0.0, z);
+        Box::new(Effect {
+            effect_type: effect_type,
+            last_transform: transform.clone(),
+            transform: transform,
+            time_left: match effect_type {
+                EffectType::Puff => tics(Effect::PUFF_TICS),
+                EffectType::Blood => tics(Effect::BLOOD_TICS),
+            }
+        })
+    }
+
+    pub fn effect_type(&self) -> EffectType {
+        self.effect_type
+    }
+}
+
+#[allow(unused_variables)]
+impl Actor for Effect {
+    fn update<'wad>(&mut self, engine: &Doom<'wad>, last_frame_time: f64, blending_factor: f64) {
+        let last_frame_time = last_frame_time as f32;
+        self.last_transform = self.transform.clone();
+        if self.effect_type == EffectType::Puff {
+            self.transform.height += Effect::PUFF_RISE * TIC_RATE * last_frame_time;
+        }
+        self.time_left -= last_frame_time;
+    }
+
+    fn type_id(&self) -> u16 {
+        ThingType::Unknown.id()
+    }
+
+    fn thing_type(&self) -> ThingType {
+        ThingType::Unknown
+    }
+
+    fn collision_class(&self) -> CollisionClass {
+        CollisionClass::Static
+    }
+
+    fn flags(&self) -> u16 {
+        0
+    }
+
+    fn size(&self) -> u16 {
+        0
+    }
+
+    fn position(&self) -> &Vector2<f32> {
+        &self.transform.position
+    }
+
+    fn angle(&self) -> f32 {
+        self.transform.angle()
+    }
+
+    fn height(&self) -> f32 {
+        self.transform.height()
+    }
+
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn get_last_transform(&self) -> &Transform {
+        &self.last_transform
+    }
+
+    fn set_transform(&mut self, transform: &Transform) {
+        self.transform = transform.clone();
+    }
+
+    fn body_height(&self) -> f32 {
+        0.0
+    }
+
+    fn is_removed(&self) -> bool {
+        self.time_left <= 0.0
+    }
+}
+
+pub struct Pickup {
+    type_id: u16,
+    thing_type: ThingType,
+    flags: u16,
+    // Transformation
+    transform: Transform,
+    last_transform: Transform,
+    // Dropped by a monster
+    dropped: bool,
+    removed: bool,
+    thing_id: Option<usize>,
+}
+
+impl Pickup {
+    const RADIUS: u16 = 20;
+    const HEIGHT: f32 = 16.0;
+
+    pub fn new<'wad>(thing_id: usize, thing: &'wad Thing) -> Box<dyn Actor> {
+        let thing_type = ThingType::try_from(thing.type_id).unwrap_or(ThingType::Unknown);
+        let transform = Transform::set({
+                let position_i16 = thing.position;
+                Vector2::<f32>::from(&position_i16)
+            },
+            thing.angle as f32,
+            0.0
+        );
+        Box::new(Pickup {
+            type_id: thing.type_id,
+            thing_type: thing_type,
+            flags: thing.flags,
+            last_transform: transform.clone(),
+            transform: transform,
+            dropped: false,
+            removed: false,
+            thing_id: Some(thing_id),
+        })
+    }
+
+    pub fn dropped(thing_type: ThingType, position: &Vector2<f32>, z: f32) -> Box<dyn Actor> {
+        let transform = Transform::set(*position, 0.0, z);
+        Box::new(Pickup {
+            type_id: thing_type.id(),
+            thing_type: thing_type,
+            flags: 0,
+            last_transform: transform.clone(),
+            transform: transform,
+            dropped: true,
+            removed: false,
+            thing_id: None,
+        })
+    }
+
+    // Bounding boxes overlap, as in P_CheckThing/PIT_CheckThing
+    fn touches(&self, actor: &dyn Actor) -> bool {
+        let block_distance = (Pickup::RADIUS + actor.size()) as f32;
+        let delta = *actor.position() - self.transform.position;
+        if delta.x.abs() >= block_distance || delta.y.abs() >= block_distance {
+            return false;
+        }
+        // Out of reach
+        let z = actor.floor_z();
+        return z <= self.transform.height + Pickup::HEIGHT && z + actor.body_height() >= self.transform.height - 8.0;
+    }
+}
+
+#[allow(unused_variables)]
+impl Actor for Pickup {
+    fn update<'wad>(&mut self, engine: &Doom<'wad>, last_frame_time: f64, blending_factor: f64) {
+        self.last_transform = self.transform.clone();
+        self.transform.height = engine.bsp.floor_height(&self.transform.position_as_int()) as f32;
+        // Touch
+        if let Some(rc_player) = engine.player() {
+            let mut player = rc_player.borrow_mut();
+            if player.health() <= 0 || !self.touches(player.as_ref()) {
+                return;
+            }
+            if let Some(player) = player.as_player_mut() {
+                if let Some(message) = player.touch_special(self.thing_type, self.dropped) {
+                    engine.message(message);
+                    engine.start_sound(self.thing_type.pickup_sound(), None);
+                    if self.thing_type.counts_item() && !self.dropped {
+                        engine.stats.borrow_mut().items += 1;
+                    }
+                    self.removed = true;
+                }
+            }
+        }
+    }
+
+    fn type_id(&self) -> u16 {
+        self.type_id
+    }
+
+    fn thing_type(&self) -> ThingType {
+        self.thing_type
+    }
+
+    fn collision_class(&self) -> CollisionClass {
+        CollisionClass::Pickup
+    }
+
+    fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    fn size(&self) -> u16 {
+        Pickup::RADIUS
+    }
+
+    fn position(&self) -> &Vector2<f32> {
+        &self.transform.position
+    }
+
+    fn angle(&self) -> f32 {
+        self.transform.angle()
+    }
+
+    fn height(&self) -> f32 {
+        self.transform.height()
+    }
+
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn get_last_transform(&self) -> &Transform {
+        &self.last_transform
+    }
+
+    fn set_transform(&mut self, transform: &Transform) {
+        self.transform = transform.clone();
+    }
+
+    fn body_height(&self) -> f32 {
+        Pickup::HEIGHT
+    }
+
+    fn is_removed(&self) -> bool {
+        self.removed
+    }
+
+    fn thing_id(&self) -> Option<usize> {
+        self.thing_id
+    }
+}
@@ -0,0 +1,228 @@
+#![allow(dead_code)]
+use std::cell::RefCell;
+use std::rc::Rc;
+// Engine
+use crate::actors::{Actor, Effect, EffectType};
+use crate::data_textures::is_sky_texture;
+use crate::doom::Doom;
+use crate::math::{Vector2, radians};
+use crate::trace::{line_opening, path_traverse, InterceptKind, Trace};
+
+mod consts {
+    pub const MISSILE_RANGE: f32 = 32.0 * 64.0;
+    // 1 << 26 in BAM
+    pub const AUTOAIM_ANGLE: f32 = 5.625;
+    // Vertical field of the autoaim
+    pub const TOP_SLOPE: f32 = 100.0 / 160.0;
+    pub const BOTTOM_SLOPE: f32 = -100.0 / 160.0;
+    // 1 << 18 in BAM, per unit of random spread
+    pub const SPREAD: f32 = 360.0 / 16384.0;
+    pub const SHOTGUN_PELLETS: u32 = 7;
+    pub const SUPER_SHOTGUN_PELLETS: u32 = 20;
+    // 1 << 5 in fixed point, per unit of random vertical spread
+    pub const SLOPE_SPREAD: f32 = 32.0 / 65536.0;
+    // Step back from the hit point where puffs and blood spawn
+    pub const PUFF_BACKOFF: f32 = 4.0;
+    pub const BLOOD_BACKOFF: f32 = 10.0;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hitscan {
+    Pistol,
+    Shotgun,
+    SuperShotgun,
+    Chaingun
+}
+
+pub struct Aim {
+    pub slope: f32,
+    pub target: Option<Rc<RefCell<Box<dyn Actor>>>>
+}
+
+// Height where the shots of an actor start
+pub fn shoot_z(shooter: &dyn Actor) -> f32 {
+    shooter.floor_z() + shooter.body_height() / 2.0 + 8.0
+}
+
+fn direction(angle: f32) -> Vector2<f32> {
+    let angle = radians(angle);
+    Vector2::new(angle.cos(), angle.sin())
+}
+
+// Find the slope to the first shootable actor along the angle
+pub fn aim_line_attack<'wad>(engine: &Doom<'wad>, shooter: &dyn Actor, angle: f32, range: f32) -> Aim {
+    let origin = *shooter.position();
+    let trace = Trace::new(&origin, &(origin + direction(angle) * range));
+    let z = shoot_z(shooter);
+    let mut top_slope = consts::TOP_SLOPE;
+    let mut bottom_slope = consts::BOTTOM_SLOPE;
+    for intercept in path_traverse(&engine.map, &engine.actors, &trace, true, true) {
+        let distance = intercept.fraction * range;
+        match intercept.kind {
+            InterceptKind::Line(line) => {
+                // Stop at walls and closed doors
+                let opening = match line_opening(&engine.map, line) {
+                    Some(opening) if opening.range > 0.0 => opening,
+                    _ => break
+                };
+                if distance <= 0.0 {
+                    continue;
+                }
+                let (front, back) = (line.front_sector(&engine.map).unwrap(), line.back_sector(&engine.map).unwrap());
+                if front.floor_height != back.floor_height {
+                    bottom_slope = bottom_slope.max((opening.bottom - z) / distance);
+                }
+                if front.ceiling_height != back.ceiling_height {
+                    top_slope = top_slope.min((opening.top - z) / distance);
+                }
+                if top_slope <= bottom_slope {
+                    break;
+                }
+            }
+            InterceptKind::Thing(rc_actor) => {
+                let actor = rc_actor.borrow();
+                if !actor.collision_class().is_shootable() || actor.health() <= 0 || distance <= 0.0 {
+                    continue;
+                }
+                // Check angles to see if the thing can be aimed at
+                let thing_top_slope = (actor.floor_z() + actor.body_height() - z) / distance;
+                let thing_bottom_slope = (actor.floor_z() - z) / distance;
+                if thing_top_slope < bottom_slope || thing_bottom_slope > top_slope {
+                    continue;
+                }
+                let slope = (thing_top_slope.min(top_slope) + thing_bottom_slope.max(bottom_slope)) / 2.0;
+                drop(actor);
+                return Aim { slope, target: Some(rc_actor) };
+            }
+        }
+    }
+    return Aim { slope: 0.0, target: None };
+}
+
+// Fire a ray, stopping at walls (puff) or at the first shootable actor hit (blood)
+pub fn line_attack<'wad>(engine: &Doom<'wad>, shooter: &dyn Actor, angle: f32, range: f32, slope: f32, damage: i32) {
+    let origin = *shooter.position();
+    let trace = Trace::new(&origin, &(origin + direction(angle) * range));
+    let z = shoot_z(shooter);
+    for intercept in path_traverse(&engine.map, &engine.actors, &trace, true, true) {
+        let distance = intercept.fraction * range;
+        match intercept.kind {
+            InterceptKind::Line(line) => {
+                if let Some(opening) = line_opening(&engine.map, line) {
+                    let (front, back) = (line.front_sector(&engine.map).unwrap(), line.back_sector(&engine.map).unwrap());
+                    let mut blocked = opening.range <= 0.0;
+                    if !blocked && distance > 0.0 {
+                        if front.floor_height != back.floor_height && (opening.bottom - z) / distance > slope {
+                            blocked = true;
+                        }
+                        if front.ceiling_height != back.ceiling_height && (opening.top - z) / distance < slope {
+                            blocked = true;
+                        }
+                    }
+                    if !blocked {
+                        continue;
+                    }
+                }
+                // Hit the wall
+                let fraction = intercept.fraction - consts::PUFF_BACKOFF / range;
+                let hit_z = z + slope * fraction * range;
+                if let Some(front) = line.front_sector(&engine.map) {
+                    // Don't shoot the sky
+                    if is_sky_texture(&front.ceiling_texture) && hit_z > front.ceiling_height as f32 {
+                        return;
+                    }
+                    if let Some(back) = line.back_sector(&engine.map) {
+                        if is_sky_texture(&back.ceiling_texture) && hit_z > back.ceiling_height as f32 {
+                            return;
+                        }
+                    }
+                }
+                spawn_puff(engine, &trace.point(fraction), hit_z);
+                return;
+            }
+            InterceptKind::Thing(rc_actor) => {
+                {
+                    let actor = rc_actor.borrow();
+                    if !actor.collision_class().is_shootable() || actor.health() <= 0 || distance <= 0.0 {
+                        continue;
+                    }
+                    // Shot over or under the thing
+                    let thing_top_slope = (actor.floor_z() + actor.body_height() - z) / distance;
+                    let thing_bottom_slope = (actor.floor_z() - z) / distance;
+                    if thing_top_slope < slope || thing_bottom_slope > slope {
+                        continue;
+                    }
+                }
+                let fraction = intercept.fraction - consts::BLOOD_BACKOFF / range;
+                let hit_z = z + slope * fraction * range;
+                spawn_blood(engine, &trace.point(fraction), hit_z);
+                rc_actor.borrow_mut().damage(engine, damage);
+                return;
+            }
+        }
+    }
+}
+
+// Autoaim as P_BulletSlope: straight ahead, then a bit to the sides
+pub fn bullet_slope<'wad>(engine: &Doom<'wad>, shooter: &dyn Actor) -> f32 {
+    let angle = shooter.angle();
+    for offset in [0.0, consts::AUTOAIM_ANGLE, -consts::AUTOAIM_ANGLE] {
+        let aim = aim_line_attack(engine, shooter, angle + offset, consts::MISSILE_RANGE);
+        if aim.target.is_some() {
+            return aim.slope;
+        }
+    }
+    return 0.0;
+}
+
+pub fn gun_shot<'wad>(engine: &Doom<'wad>, shooter: &dyn Actor, slope: f32, accurate: bool) {
+    let damage = 5 * (engine.random.borrow_mut().next() % 3 + 1);
+    let mut angle = shooter.angle();
+    if !accurate {
+        angle += engine.random.borrow_mut().next_sub() as f32 * consts::SPREAD;
+    }
+    line_attack(engine, shooter, angle, consts::MISSILE_RANGE, slope, damage);
+}
+
+pub fn spawn_puff<'wad>(engine: &Doom<'wad>, position: &Vector2<f32>, z: f32) {
+    let z = z + engine.random.borrow_mut().next_sub() as f32 / 64.0;
+    engine.spawn(Effect::new(EffectType::Puff, position, z));
+}
+
+pub fn spawn_blood<'wad>(engine: &Doom<'wad>, position: &Vector2<f32>, z: f32) {
+    let z = z + engine.random.borrow_mut().next_sub() as f32 / 64.0;
+    engine.spawn(Effect::new(EffectType::Blood, position, z));
+}
+
+impl Hitscan {
+    // The first shot of a burst is accurate, refires spread
+    pub fn fire<'wad>(&self, engine: &Doom<'wad>, shooter: &dyn Actor, refire: bool) {
+        let slope = bullet_slope(engine, shooter);
+        match self {
+            Hitscan::Pistol | Hitscan::Chaingun => {
+                gun_shot(engine, shooter, slope, !refire);
+            }
+            Hitscan::Shotgun => {
+                for _ in 0..consts::SHOTGUN_PELLETS {
+                    gun_shot(engine, shooter, slope, false);
+                }
+            }
+            // As A_FireShotgun2, wider and with vertical spread
+            Hitscan::SuperShotgun => {
+                for _ in 0..consts::SUPER_SHOTGUN_PELLETS {
+                    let damage = 5 * (engine.random.borrow_mut().next() % 3 + 1);
+                    let angle = shooter.angle() + engine.random.borrow_mut().next_sub() as f32 * consts::SPREAD * 2.0;
+                    let slope = slope + engine.random.borrow_mut().next_sub() as f32 * consts::SLOPE_SPREAD;
+                    line_attack(engine, shooter, angle, consts::MISSILE_RANGE, slope, damage);
+                }
+            }
+        }
+    }
+}
+
+// Short range attack, as A_Punch and A_Saw
+pub fn melee_attack<'wad>(engine: &Doom<'wad>, shooter: &dyn Actor, damage: i32, range: f32) {
+    let angle = shooter.angle() + engine.random.borrow_mut().next_sub() as f32 * consts::SPREAD;
+    let aim = aim_line_attack(engine, shooter, angle, range);
+    line_attack(engine, shooter, angle, range, aim.slope, damage);
+}
@@ -0,0 +1,346 @@
+#![allow(dead_code)]
+use std::rc::Rc;
+// Engine
+use crate::data_textures::DataTextures;
+use crate::doom::Doom;
+use crate::inventory::{KeyColor, PowerType};
+use crate::map::{LineDef, LineDefFlags, Map};
+use crate::math::{radians, Vector2};
+use crate::render::Render;
+use crate::shape::Size;
+use crate::specials::line_lock;
+use crate::time::tics;
+use crate::controls::{Action, Controls};
+use crate::window::DoomSurface;
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+mod consts {
+    use winit::keyboard::KeyCode;
+    // As am_map.c, scales in pixels per map unit of a 320 wide screen
+    pub const INIT_SCALE: f32 = 0.2;
+    pub const MAX_SCALE: f32 = 200.0 / (2.0 * 16.0);
+    pub const ZOOM_IN: f32 = 1.02;
+    pub const ZOOM_OUT: f32 = 1.0 / 1.02;
+    pub const PAN_SPEED: f32 = 4.0;
+    // As AM_PANLEFTKEY and the others, left, right, up and down
+    pub const PAN_KEYS: [KeyCode; 4] = [KeyCode::ArrowLeft, KeyCode::ArrowRight, KeyCode::ArrowUp, KeyCode::ArrowDown];
+    // Palette indices
+    pub const BACKGROUND: usize = 0;
+    pub const WALL: usize = 176;
+    pub const FLOOR_CHANGE: usize = 64;
+    pub const CEILING_CHANGE: usize = 231;
+    pub const TWO_SIDED: usize = 96;
+    pub const COMPUTER_MAP: usize = 99;
+    pub const PLAYER: usize = 209;
+    pub const BLUE_DOOR: usize = 200;
+    pub const YELLOW_DOOR: usize = 231;
+    pub const RED_DOOR: usize = 176;
+    // Player arrow in units of its radius, as player_arrow
+    pub const ARROW_RADIUS: f32 = 8.0 * 16.0 / 7.0;
+    pub const ARROW: [((f32, f32), (f32, f32)); 7] = [
+        ((-1.0 + 1.0 / 8.0, 0.0), (1.0, 0.0)),
+        ((1.0, 0.0), (1.0 - 1.0 / 2.0, 1.0 / 4.0)),
+        ((1.0, 0.0), (1.0 - 1.0 / 2.0, -1.0 / 4.0)),
+        ((-1.0 + 1.0 / 8.0, 0.0), (-1.0 - 1.0 / 8.0, 1.0 / 4.0)),
+        ((-1.0 + 1.0 / 8.0, 0.0), (-1.0 - 1.0 / 8.0, -1.0 / 4.0)),
+        ((-1.0 + 3.0 / 8.0, 0.0), (-1.0 + 1.0 / 8.0, 1.0 / 4.0)),
+        ((-1.0 + 3.0 / 8.0, 0.0), (-1.0 + 1.0 / 8.0, -1.0 / 4.0)),
+    ];
+}
+
+// Automap state, the lines seen by the player and the view over the map
+pub struct Automap {
+    active: bool,
+    follow: bool,
+    seen_lines: Vec<bool>,
+    bounds: [Vector2<f32>; 2],
+    center: Vector2<f32>,
+    scale: f32,
+    saved_scale: f32,
+    // Per tic zoom factor and pan direction, from the held keys
+    zoom: f32,
+    pan: Vector2<f32>,
+    think_time: f32,
+}
+
+impl Automap {
+    pub fn new(map: &Map) -> Self {
+        let mut bounds = [Vector2::new(f32::MAX, f32::MAX), Vector2::new(f32::MIN, f32::MIN)];
+        for vertex in &map.vertices {
+            bounds[0].x = bounds[0].x.min(vertex.x as f32);
+            bounds[0].y = bounds[0].y.min(vertex.y as f32);
+            bounds[1].x = bounds[1].x.max(vertex.x as f32);
+            bounds[1].y = bounds[1].y.max(vertex.y as f32);
+        }
+        Automap {
+            active: false,
+            follow: true,
+            seen_lines: map.line_defs.iter().map(|line| line.has_flag(LineDefFlags::Mapped)).collect(),
+            bounds: bounds,
+            center: (bounds[0] + bounds[1]) * 0.5,
+            scale: consts::INIT_SCALE,
+            saved_scale: consts::INIT_SCALE,
+            zoom: 1.0,
+            pan: Vector2::new(0.0, 0.0),
+            think_time: 0.0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.follow
+    }
+
+    pub fn center(&self) -> Vector2<f32> {
+        self.center
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    // Set by the renderer for every wall it draws, as ML_MAPPED
+    pub fn mark_seen(&mut self, line_id: usize) {
+        if let Some(seen) = self.seen_lines.get_mut(line_id) {
+            *seen = true;
+        }
+    }
+
+    pub fn is_seen(&self, line_id: usize) -> bool {
+        self.seen_lines.get(line_id).copied().unwrap_or(false)
+    }
+
+    // Whole map in view
+    fn min_scale(&self) -> f32 {
+        let size = self.bounds[1] - self.bounds[0];
+        return (320.0 / size.x.max(1.0)).min(200.0 / size.y.max(1.0));
+    }
+
+    // As AM_Responder, returns the keys the map takes from the player
+    pub fn control(&mut self, input: &WinitInputHelper, controls: &Controls) -> &'static [KeyCode] {
+        if controls.pressed(input, Action::Automap) {
+            self.active = !self.active;
+        }
+        if !self.active {
+            return &[];
+        }
+        if input.key_pressed(KeyCode::KeyF) {
+            self.follow = !self.follow;
+        }
+        if input.key_pressed(KeyCode::Digit0) {
+            if self.scale == self.min_scale() {
+                self.scale = self.saved_scale;
+            } else {
+                self.saved_scale = self.scale;
+                self.scale = self.min_scale();
+            }
+        }
+        self.zoom = 1.0;
+        if input.key_held(KeyCode::Equal) || input.key_held(KeyCode::NumpadAdd) {
+            self.zoom = consts::ZOOM_IN;
+        } else if input.key_held(KeyCode::Minus) || input.key_held(KeyCode::NumpadSubtract) {
+            self.zoom = consts::ZOOM_OUT;
+        }
+        self.pan = Vector2::new(0.0, 0.0);
+        if self.follow {
+            return &[];
+        }
+        // Arrows pan the map instead of moving the player
+        let [left, right, up, down] = consts::PAN_KEYS;
+        if input.key_held(left) { self.pan.x -= 1.0; }
+        if input.key_held(right) { self.pan.x += 1.0; }
+        if input.key_held(up) { self.pan.y += 1.0; }
+        if input.key_held(down) { self.pan.y -= 1.0; }
+        return &consts::PAN_KEYS;
+    }
+
+    // As AM_Ticker
+    pub fn update(&mut self, player_position: Option<Vector2<f32>>, last_frame_time: f32) {
+        if !self.active {
+            return;
+        }
+        self.think_time += last_frame_time;
+        while self.think_time >= tics(1) {
+            self.think_time -= tics(1);
+            self.scale = (self.scale * self.zoom).clamp(self.min_scale(), consts::MAX_SCALE);
+            self.center += self.pan * (consts::PAN_SPEED / self.scale);
+            self.center.x = self.center.x.clamp(self.bounds[0].x, self.bounds[1].x);
+            self.center.y = self.center.y.clamp(self.bounds[0].y, self.bounds[1].y);
+        }
+        if self.follow {
+            if let Some(position) = player_position {
+                self.center = position;
+            }
+        }
+    }
+}
+
+// Outcodes of the Cohen-Sutherland clipping, as AM_clipMline
+fn outcode(point: &Vector2<f32>, min: &Vector2<f32>, max: &Vector2<f32>) -> u8 {
+    let mut code = 0;
+    if point.x < min.x { code |= 1; } else if point.x > max.x { code |= 2; }
+    if point.y < min.y { code |= 4; } else if point.y > max.y { code |= 8; }
+    return code;
+}
+
+fn clip_line(from: Vector2<f32>, to: Vector2<f32>, min: &Vector2<f32>, max: &Vector2<f32>) -> Option<(Vector2<f32>, Vector2<f32>)> {
+    let (mut from, mut to) = (from, to);
+    let (mut code_from, mut code_to) = (outcode(&from, min, max), outcode(&to, min, max));
+    loop {
+        if code_from | code_to == 0 {
+            return Some((from, to));
+        }
+        if code_from & code_to != 0 {
+            return None;
+        }
+        let code = if code_from != 0 { code_from } else { code_to };
+        let delta = to - from;
+        let point = if code & 8 != 0 {
+            Vector2::new(from.x + delta.x * (max.y - from.y) / delta.y, max.y)
+        } else if code & 4 != 0 {
+            Vector2::new(from.x + delta.x * (min.y - from.y) / delta.y, min.y)
+        } else if code & 2 != 0 {
+            Vector2::new(max.x, from.y + delta.y * (max.x - from.x) / delta.x)
+        } else {
+            Vector2::new(min.x, from.y + delta.y * (min.x - from.x) / delta.x)
+        };
+        if code == code_from {
+            from = point;
+            code_from = outcode(&from, min, max);
+        } else {
+            to = point;
+            code_to = outcode(&to, min, max);
+        }
+    }
+}
+
+// Gameplay automap drawn over the 3D view when active
+pub struct RenderAutomap<'wad> {
+    map: Rc<Map<'wad>>,
+    data_textures: Rc<DataTextures<'wad>>,
+    size: Vector2<i32>,
+    offset: Vector2<i32>,
+}
+
+impl<'wad> RenderAutomap<'wad> {
+    pub fn new(map: &Rc<Map<'wad>>, data_textures: &Rc<DataTextures<'wad>>, size: Vector2<i32>, offset: Vector2<i32>) -> Self {
+        RenderAutomap {
+            map: map.clone(),
+            data_textures: data_textures.clone(),
+            size: size,
+            offset: offset,
+        }
+    }
+
+    fn color(&self, index: usize) -> [u8; 4] {
+        match self.data_textures.palettes.first() {
+            Some(palette) => {
+                let rgb = palette.slices()[index];
+                [rgb[0], rgb[1], rgb[2], 0xFF]
+            },
+            None => [0xFF, 0xFF, 0xFF, 0xFF]
+        }
+    }
+
+    // As AM_drawWalls, None when the line is not drawn
+    fn line_color(&self, line: &LineDef, seen: bool, computer_map: bool) -> Option<usize> {
+        if line.has_flag(LineDefFlags::DontDraw) {
+            return None;
+        }
+        if !seen {
+            return if computer_map { Some(consts::COMPUTER_MAP) } else { None };
+        }
+        if let Some(lock) = line_lock(line.line_type) {
+            return Some(match lock.color {
+                KeyColor::Blue => consts::BLUE_DOOR,
+                KeyColor::Yellow => consts::YELLOW_DOOR,
+                KeyColor::Red => consts::RED_DOOR,
+            });
+        }
+        // Secret lines look like walls
+        let (front, back) = match (line.front_sector(&self.map), line.back_sector(&self.map)) {
+            (Some(front), Some(back)) if !line.has_flag(LineDefFlags::Secret) => (front, back),
+            _ => return Some(consts::WALL)
+        };
+        if front.floor_height != back.floor_height {
+            return Some(consts::FLOOR_CHANGE);
+        }
+        if front.ceiling_height != back.ceiling_height {
+            return Some(consts::CEILING_CHANGE);
+        }
+        // Two-sided lines without height changes are only shown by the cheat
+        return None;
+    }
+
+    fn draw_line(&self, surface: &mut DoomSurface, automap: &Automap, from: Vector2<f32>, to: Vector2<f32>, color: &[u8; 4]) {
+        let scale = automap.scale() * self.size.width() as f32 / 320.0;
+        let center = automap.center();
+        let half_size = Vector2::new(self.size.width() as f32, self.size.height() as f32) * 0.5;
+        // The map y goes up, the screen y goes down
+        let to_screen = |point: Vector2<f32>| Vector2::new(
+            half_size.x + (point.x - center.x) * scale,
+            half_size.y - (point.y - center.y) * scale
+        );
+        let max = Vector2::new(self.size.width() as f32 - 1.0, self.size.height() as f32 - 1.0);
+        if let Some((from, to)) = clip_line(to_screen(from), to_screen(to), &Vector2::new(0.0, 0.0), &max) {
+            surface.draw_line_lt(
+                &Vector2::new(from.x as i32 + self.offset.x, from.y as i32 + self.offset.y),
+                &Vector2::new(to.x as i32 + self.offset.x, to.y as i32 + self.offset.y),
+                color
+            );
+        }
+    }
+
+    fn draw_player_arrow(&self, surface: &mut DoomSurface, automap: &Automap, position: Vector2<f32>, angle: f32) {
+        let color = self.color(consts::PLAYER);
+        let (sin, cos) = radians(angle).sin_cos();
+        let rotate = |(x, y): (f32, f32)| Vector2::new(
+            position.x + (x * cos - y * sin) * consts::ARROW_RADIUS,
+            position.y + (x * sin + y * cos) * consts::ARROW_RADIUS
+        );
+        for (from, to) in consts::ARROW {
+            self.draw_line(surface, automap, rotate(from), rotate(to), &color);
+        }
+    }
+}
+
+impl Render for RenderAutomap<'_> {
+    fn draw<'wad>(&mut self, doom: &mut Doom<'wad>, _last_frame_time: f64, _blending_factor: f64) {
+        if !doom.automap.is_active() {
+            return;
+        }
+        let player = doom.player();
+        let computer_map = player.as_ref()
+            .and_then(|actor| actor.borrow().as_player().map(|player| player.inventory().has_power(PowerType::AllMap)))
+            .unwrap_or(false);
+        let surface = &mut doom.surface.borrow_mut();
+        // Background
+        let background = self.color(consts::BACKGROUND);
+        for y in 0..self.size.height() {
+            for x in 0..self.size.width() {
+                surface.draw_lt(&Vector2::new((x + self.offset.x) as usize, (y + self.offset.y) as usize), &background);
+            }
+        }
+        // Walls
+        for (line_id, line) in self.map.line_defs.iter().enumerate() {
+            if let Some(color) = self.line_color(line, doom.automap.is_seen(line_id), computer_map) {
+                self.draw_line(
+                    surface,
+                    &doom.automap,
+                    Vector2::<f32>::from(line.start_vertex(&self.map)),
+                    Vector2::<f32>::from(line.end_vertex(&self.map)),
+                    &self.color(color)
+                );
+            }
+        }
+        // Player
+        if let Some(actor) = player {
+            let actor = actor.borrow();
+            self.draw_player_arrow(surface, &doom.automap, *actor.position(), actor.angle());
+        }
+    }
+}
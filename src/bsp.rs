@@ -1,6 +1,6 @@
 use std::rc::Rc;
 // Engine
-use crate::map::{Map, NodeBox};
+use crate::map::{Map, NodeBox, Sector};
 use crate::math::Vector2;
 
 // 0x8000 in binary 1000000000000000
@@ -27,6 +27,20 @@ impl<'wad> BSP<'wad> {
     }
 
     pub fn floor_height(&self, position: &Vector2<i16>) -> i16 {
+        if let Some(sector) = self.sector(&position) {
+            return sector.floor_height;
+        }
+        return 0;
+    }
+
+    pub fn sector(&self, position: &Vector2<i16>) -> Option<&Sector> {
+        if let Some(sector_id) = self.sector_id(&position) {
+            return Some(self.map.sectors[sector_id]);
+        }
+        return None;
+    }
+
+    pub fn sector_id(&self, position: &Vector2<i16>) -> Option<usize> {
         // Looking for sector id
         let mut node_id = self.root_id;
         // Search for the segment
@@ -41,11 +55,11 @@ impl<'wad> BSP<'wad> {
         if node_id & SUBSECTORIDENTIFIER > 0 { 
             let sub_sector_id = node_id & (!SUBSECTORIDENTIFIER);
             let first_seg_id = self.map.sub_sectors[sub_sector_id as usize].first_seg_id;
-            if let Some(sector) = self.map.segs[first_seg_id as usize].right_sector(&self.map) {
-                return sector.floor_height;
+            if let Some(side) = self.map.segs[first_seg_id as usize].side(&self.map) {
+                return Some(side.sector_id as usize);
             }
         }
-        return 0;
+        return None;
     }
 
     pub fn visit<'a,'b, T>(&mut self, position: &Vector2<i16>, context: &mut T, callback: impl FnMut(u16, &mut T) -> bool + 'a, test_node: impl FnMut(&NodeBox, &mut T) -> bool + 'b) {
@@ -96,7 +96,7 @@ impl CollisionClass {
     }
 }
 
-fn get_default_radius(thing_type:&ThingType) -> i32 {
+pub fn get_default_radius(thing_type:&ThingType) -> i32 {
     match thing_type {
         // Players
         ThingType::Player1Start 
@@ -116,12 +116,37 @@ fn get_default_radius(thing_type:&ThingType) -> i32 {
         ThingType::Cacodemon => 31,
         ThingType::LostSoul => 16,
         ThingType::BaronOfHell => 24,
+        ThingType::HellKnight => 24,
+        ThingType::PainElemental => 31,
+        ThingType::Mancubus => 48,
+        ThingType::Arachnotron => 64,
+        ThingType::Cyberdemon => 40,
+        ThingType::SpiderDemon => 128,
 
         // Default case
         _ => 20,
     }
 }
 
+pub fn get_default_height(thing_type:&ThingType) -> i32 {
+    match thing_type {
+        // Big monsters
+        ThingType::BaronOfHell
+        | ThingType::HellKnight
+        | ThingType::Mancubus
+        | ThingType::Arachnotron => 64,
+
+        ThingType::SpiderDemon => 100,
+        ThingType::Cyberdemon => 110,
+
+        // Default case: bodies are 56 units tall, items and decorations 16
+        _ => match CollisionClass::new(*thing_type) {
+            CollisionClass::Player | CollisionClass::Monster => 56,
+            _ => 16
+        },
+    }
+}
+
 pub struct CollisionSolver<'wad> {
     map: Rc<Map<'wad>>,
 }
@@ -2,6 +2,7 @@
 #![allow(dead_code)]
 use ini::{Ini, Properties};
 use crate::math::{Vector2, Vector4};
+use crate::controls::Controls;
 
 #[readonly::make]
 #[derive(Clone)]
@@ -16,7 +17,9 @@ pub struct Screen {
     pub window: Vector2<f64>, 
     pub surface: Vector2<u32>, 
     pub frame_rate: u32,
-    pub vsync: bool
+    pub vsync: bool,
+    // 8-bit rendering through PLAYPAL and COLORMAP
+    pub indexed: bool
 }
 
 #[readonly::make]
@@ -45,6 +48,49 @@ pub struct Map {
     pub blockmap_no_first_line: bool
 }
 
+#[readonly::make]
+#[derive(Clone)]
+pub struct Mouse {
+    pub enabled: bool,
+    // Degrees of turn and look for each count of the mouse
+    pub sensitivity: f32,
+    // Vertical look
+    pub look: bool,
+    pub invert: bool,
+}
+
+#[readonly::make]
+#[derive(Clone)]
+pub struct Demo {
+    // Vanilla .lmp files, the played demo drives the player until its end
+    pub play: Option<String>,
+    pub record: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SoundDevice {
+    Digital,
+    Speaker,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MusicDevice {
+    None,
+    Opl2,
+    Opl3,
+}
+
+#[readonly::make]
+#[derive(Clone)]
+pub struct Sound {
+    pub channels: usize,
+    pub rate: u32,
+    // Headless output, the mixed sound is written there on exit
+    pub wav: Option<String>,
+    pub sfx: SoundDevice,
+    pub music: MusicDevice,
+}
+
 #[readonly::make]
 #[derive(Clone)]
 pub struct Render {
@@ -56,6 +102,13 @@ pub struct Render {
     pub sprite_2d: Option<Vector4<i32>>, 
     pub texture_2d: Option<Vector4<i32>>, 
     pub software_3d: Option<Vector4<i32>>, 
+    pub automap: Option<Vector4<i32>>, 
+    pub status_bar: Option<Vector4<i32>>, 
+    pub hud: Option<Vector4<i32>>, 
+    pub messages: Option<Vector4<i32>>, 
+    pub menu: Option<Vector4<i32>>, 
+    pub intermission: Option<Vector4<i32>>, 
+    pub console: Option<Vector4<i32>>, 
 }
 
 #[readonly::make]
@@ -66,6 +119,10 @@ pub struct Configure {
     pub camera: Camera,
     pub player: Player,
     pub map: Map,
+    pub sound: Sound,
+    pub mouse: Mouse,
+    pub controls: Controls,
+    pub demo: Demo,
     pub render: Option<Render>,
 }
 
@@ -111,6 +168,27 @@ impl<T> Vector4<T> where T: std::str::FromStr, <T as std::str::FromStr>::Err: st
     }
 }
 
+impl SoundDevice {
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.trim() {
+            "digital" => Some(SoundDevice::Digital),
+            "speaker" => Some(SoundDevice::Speaker),
+            _ => None
+        }
+    }
+}
+
+impl MusicDevice {
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.trim() {
+            "none" => Some(MusicDevice::None),
+            "opl2" => Some(MusicDevice::Opl2),
+            "opl3" => Some(MusicDevice::Opl3),
+            _ => None
+        }
+    }
+}
+
 fn bool_from_str(value: Option<&str>) -> Option<bool> {
     match value?.trim() {
         "true" => Some(true),        
@@ -196,10 +274,64 @@ impl Screen {
             surface: Vector2::<u32>::from_str(props.get("surface")?)?,
             frame_rate: props.get("frame_rate")?.parse().ok()?,
             vsync: bool_from_str(props.get("vsync")).unwrap_or(false),
+            indexed: bool_from_str(props.get("indexed")).unwrap_or(false),
         })
     }
 }
 
+impl Sound {
+    const DEFAULT_CHANNELS: usize = 8;
+    const DEFAULT_RATE: u32 = 44100;
+
+    pub fn from(props: Option<&Properties>) -> Self {
+        Sound {
+            channels: props.and_then(|props| props.get("channels"))
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(Self::DEFAULT_CHANNELS),
+            rate: props.and_then(|props| props.get("rate"))
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(Self::DEFAULT_RATE),
+            wav: props.and_then(|props| props.get("wav")).map(String::from),
+            sfx: props.and_then(|props| props.get("sfx"))
+                .map(|v| SoundDevice::from_str(v).unwrap_or_else(|| {
+                    eprintln!("Invalid sfx device: {}, digital is used", v);
+                    SoundDevice::Digital
+                }))
+                .unwrap_or(SoundDevice::Digital),
+            music: props.and_then(|props| props.get("music"))
+                .map(|v| MusicDevice::from_str(v).unwrap_or_else(|| {
+                    eprintln!("Invalid music device: {}, opl3 is used", v);
+                    MusicDevice::Opl3
+                }))
+                .unwrap_or(MusicDevice::Opl3),
+        }
+    }
+}
+
+impl Mouse {
+    const DEFAULT_SENSITIVITY: f32 = 0.15;
+
+    pub fn from(props: Option<&Properties>) -> Self {
+        Mouse {
+            enabled: bool_from_str(props.and_then(|props| props.get("enabled"))).unwrap_or(true),
+            sensitivity: props.and_then(|props| props.get("sensitivity"))
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(Self::DEFAULT_SENSITIVITY),
+            look: bool_from_str(props.and_then(|props| props.get("look"))).unwrap_or(true),
+            invert: bool_from_str(props.and_then(|props| props.get("invert"))).unwrap_or(false),
+        }
+    }
+}
+
+impl Demo {
+    pub fn from(props: Option<&Properties>) -> Self {
+        Demo {
+            play: props.and_then(|props| props.get("play")).map(String::from),
+            record: props.and_then(|props| props.get("record")).map(String::from),
+        }
+    }
+}
+
 impl Render {
     pub fn from(props: Option<&Properties>) -> Option<Self> {
         match props {
@@ -212,6 +344,13 @@ impl Render {
                 sprite_2d: Vector4::<i32>::from_optional_str(props.get("sprite_2d")),
                 texture_2d: Vector4::<i32>::from_optional_str(props.get("texture_2d")),
                 software_3d: Vector4::<i32>::from_optional_str(props.get("software_3d")),
+                automap: Vector4::<i32>::from_optional_str(props.get("automap")),
+                status_bar: Vector4::<i32>::from_optional_str(props.get("status_bar")),
+                hud: Vector4::<i32>::from_optional_str(props.get("hud")),
+                messages: Vector4::<i32>::from_optional_str(props.get("messages")),
+                menu: Vector4::<i32>::from_optional_str(props.get("menu")),
+                intermission: Vector4::<i32>::from_optional_str(props.get("intermission")),
+                console: Vector4::<i32>::from_optional_str(props.get("console")),
             }),
             _ => None  
         }
@@ -227,9 +366,20 @@ impl Configure {
                 camera : Camera::from(ini.section(Some("Camera"))?)?,
                 player : Player::from(ini.section(Some("Player"))?)?,
                 map : Map::from(ini.section(Some("Map"))?)?,
+                sound : Sound::from(ini.section(Some("Sound"))),
+                mouse : Mouse::from(ini.section(Some("Mouse"))),
+                controls : Controls::from(ini.section(Some("Controls"))),
+                demo : Demo::from(ini.section(Some("Demo"))),
                 render : Render::from(ini.section(Some("Render"))),
             });
         }
         None
     }
+
+    // Same configuration on another map
+    pub fn with_map(&self, name: &str) -> Self {
+        let mut configure = self.clone();
+        configure.map.name = String::from(name);
+        return configure;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,154 @@
+#![allow(dead_code)]
+use std::rc::Rc;
+// Engine
+use crate::data_textures::DataTextures;
+use crate::doom::Doom;
+use crate::math::Vector2;
+use crate::render::Render;
+use crate::text::Font;
+use crate::time::tics;
+use crate::controls::{Action, Controls};
+use crate::window::DoomSurface;
+use winit::keyboard::KeyCode;
+use winit_input_helper::{TextChar, WinitInputHelper};
+
+mod consts {
+    pub const PROMPT: &str = "> ";
+    pub const CONSOLE_X: i32 = 0;
+    // Below the message lines
+    pub const CONSOLE_Y: i32 = 40;
+    pub const MAX_LENGTH: usize = 40;
+    pub const CURSOR_TICS: u32 = 8;
+}
+
+// Parsed console line
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Map(String),
+    Unknown(String)
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Option<Command> {
+        let mut words = line.split_whitespace();
+        let name = words.next()?.to_ascii_lowercase();
+        match (name.as_str(), words.next(), words.next()) {
+            ("map", Some(map), None) => Some(Command::Map(map.to_ascii_uppercase())),
+            _ => Some(Command::Unknown(line.trim().to_string()))
+        }
+    }
+}
+
+// Single command line, the backquote opens and closes it
+pub struct Console {
+    active: bool,
+    line: String,
+    history: Vec<String>,
+    history_id: usize,
+    cursor_time: f32,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            active: false,
+            line: String::new(),
+            history: vec![],
+            history_id: 0,
+            cursor_time: 0.0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    fn close(&mut self) {
+        self.active = false;
+        self.line.clear();
+    }
+
+    // Returns the command entered, the input is eaten while the console is open
+    pub fn control(&mut self, input: &WinitInputHelper, controls: &Controls) -> Option<Command> {
+        if !self.active {
+            if controls.pressed(input, Action::Console) {
+                self.active = true;
+                self.history_id = self.history.len();
+            }
+            return None;
+        }
+        if controls.pressed(input, Action::Console) || input.key_pressed(KeyCode::Escape) {
+            self.close();
+            return None;
+        }
+        if input.key_pressed(KeyCode::Enter) {
+            let line = std::mem::take(&mut self.line);
+            self.active = false;
+            if !line.trim().is_empty() {
+                self.history.push(line.clone());
+            }
+            return Command::parse(&line);
+        }
+        if input.key_pressed(KeyCode::ArrowUp) && self.history_id > 0 {
+            self.history_id -= 1;
+            self.line = self.history[self.history_id].clone();
+        } else if input.key_pressed(KeyCode::ArrowDown) && self.history_id < self.history.len() {
+            self.history_id += 1;
+            self.line = self.history.get(self.history_id).cloned().unwrap_or_default();
+        }
+        for text in input.text() {
+            match text {
+                TextChar::Char('`') | TextChar::Char('~') => {},
+                TextChar::Char(c) if !c.is_control() && self.line.len() < consts::MAX_LENGTH => self.line.push(c),
+                TextChar::Back => { self.line.pop(); },
+                _ => {}
+            }
+        }
+        return None;
+    }
+
+    pub fn update(&mut self, last_frame_time: f32) {
+        self.cursor_time = (self.cursor_time + last_frame_time) % (tics(consts::CURSOR_TICS) * 2.0);
+    }
+
+    fn cursor_visible(&self) -> bool {
+        self.cursor_time < tics(consts::CURSOR_TICS)
+    }
+}
+
+pub struct RenderConsole<'wad> {
+    font: Font<'wad>,
+    size: Vector2<i32>,
+    offset: Vector2<i32>,
+}
+
+impl<'wad> RenderConsole<'wad> {
+    pub fn new(data_textures: &Rc<DataTextures<'wad>>, size: Vector2<i32>, offset: Vector2<i32>) -> Self {
+        RenderConsole {
+            font: Font::new(data_textures),
+            size: size,
+            offset: offset,
+        }
+    }
+
+    fn draw_line(&self, surface: &mut DoomSurface, console: &Console) {
+        let cursor = if console.cursor_visible() { "_" } else { "" };
+        let text = format!("{}{}{}", consts::PROMPT, console.line(), cursor);
+        self.font.draw(surface, &text, consts::CONSOLE_X, consts::CONSOLE_Y, &self.size, &self.offset);
+    }
+}
+
+impl Render for RenderConsole<'_> {
+    fn draw<'wad>(&mut self, doom: &mut Doom<'wad>, last_frame_time: f64, _blending_factor: f64) {
+        doom.console.update(last_frame_time as f32);
+        if !doom.console.is_active() {
+            return;
+        }
+        let surface = doom.surface.clone();
+        self.draw_line(&mut surface.borrow_mut(), &doom.console);
+    }
+}
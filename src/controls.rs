@@ -0,0 +1,199 @@
+#![allow(dead_code)]
+use std::collections::HashMap;
+use ini::Properties;
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Forward,
+    Back,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    Jump,
+    Use,
+    Fire,
+    Run,
+    // The turn keys strafe while it is held
+    Strafe,
+    // Slots 1 to 7
+    Weapon(u8),
+    Automap,
+    Menu,
+    Console,
+}
+
+impl Action {
+    pub const ALL: [Action; 21] = [
+        Action::Forward, Action::Back, Action::StrafeLeft, Action::StrafeRight,
+        Action::TurnLeft, Action::TurnRight, Action::Jump, Action::Use,
+        Action::Fire, Action::Run, Action::Strafe,
+        Action::Weapon(1), Action::Weapon(2), Action::Weapon(3), Action::Weapon(4),
+        Action::Weapon(5), Action::Weapon(6), Action::Weapon(7),
+        Action::Automap, Action::Menu, Action::Console,
+    ];
+
+    // Key of the action in the [Controls] section
+    pub fn name(&self) -> String {
+        match self {
+            Action::Forward => String::from("forward"),
+            Action::Back => String::from("back"),
+            Action::StrafeLeft => String::from("strafe_left"),
+            Action::StrafeRight => String::from("strafe_right"),
+            Action::TurnLeft => String::from("turn_left"),
+            Action::TurnRight => String::from("turn_right"),
+            Action::Jump => String::from("jump"),
+            Action::Use => String::from("use"),
+            Action::Fire => String::from("fire"),
+            Action::Run => String::from("run"),
+            Action::Strafe => String::from("strafe"),
+            Action::Weapon(slot) => format!("weapon{}", slot),
+            Action::Automap => String::from("automap"),
+            Action::Menu => String::from("menu"),
+            Action::Console => String::from("console"),
+        }
+    }
+
+    fn default_bindings(&self) -> Vec<Binding> {
+        let keys = |keys: &[KeyCode]| keys.iter().map(|key| Binding::Key(*key)).collect();
+        match self {
+            Action::Forward => keys(&[KeyCode::KeyW]),
+            Action::Back => keys(&[KeyCode::KeyS]),
+            Action::StrafeLeft => keys(&[KeyCode::KeyA]),
+            Action::StrafeRight => keys(&[KeyCode::KeyD]),
+            Action::TurnLeft => keys(&[KeyCode::ArrowLeft]),
+            Action::TurnRight => keys(&[KeyCode::ArrowRight]),
+            Action::Jump => keys(&[KeyCode::KeyE]),
+            Action::Use => keys(&[KeyCode::Space]),
+            Action::Fire => vec![Binding::Key(KeyCode::ControlLeft), Binding::Key(KeyCode::ControlRight), Binding::Mouse(0)],
+            Action::Run => keys(&[KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+            Action::Strafe => keys(&[KeyCode::AltLeft, KeyCode::AltRight]),
+            Action::Weapon(slot) => {
+                let digits = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+                              KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7];
+                digits.get((*slot as usize).wrapping_sub(1)).map(|key| vec![Binding::Key(*key)]).unwrap_or_default()
+            },
+            Action::Automap => keys(&[KeyCode::Tab]),
+            Action::Menu => keys(&[KeyCode::Escape]),
+            Action::Console => keys(&[KeyCode::Backquote]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    // As WinitInputHelper: 0 left, 1 right, 2 middle
+    Mouse(usize),
+}
+
+// Keys that can be bound, by their winit names
+const KEYS: [KeyCode; 92] = [
+    KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF,
+    KeyCode::KeyG, KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL,
+    KeyCode::KeyM, KeyCode::KeyN, KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR,
+    KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU, KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX,
+    KeyCode::KeyY, KeyCode::KeyZ,
+    KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+    KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    KeyCode::ArrowUp, KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight,
+    KeyCode::Space, KeyCode::Enter, KeyCode::Escape, KeyCode::Tab, KeyCode::Backspace,
+    KeyCode::Backquote, KeyCode::Minus, KeyCode::Equal, KeyCode::BracketLeft, KeyCode::BracketRight,
+    KeyCode::Backslash, KeyCode::Semicolon, KeyCode::Quote, KeyCode::Comma, KeyCode::Period,
+    KeyCode::Slash, KeyCode::CapsLock,
+    KeyCode::ShiftLeft, KeyCode::ShiftRight, KeyCode::ControlLeft, KeyCode::ControlRight,
+    KeyCode::AltLeft, KeyCode::AltRight,
+    KeyCode::Insert, KeyCode::Delete, KeyCode::Home, KeyCode::End, KeyCode::PageUp, KeyCode::PageDown,
+    KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4,
+    KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9,
+    KeyCode::NumpadEnter,
+];
+
+impl Binding {
+    // Winit key names, as KeyW or ArrowUp, and Mouse1 to Mouse3 for the buttons
+    pub fn from_str(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if let Some(button) = value.strip_prefix("Mouse") {
+            return match button.parse::<usize>() {
+                Ok(button) if button > 0 => Some(Binding::Mouse(button - 1)),
+                _ => None
+            };
+        }
+        return KEYS.iter().find(|key| format!("{:?}", key) == value).map(|key| Binding::Key(*key));
+    }
+
+    pub fn held(&self, input: &WinitInputHelper) -> bool {
+        match self {
+            Binding::Key(key) => input.key_held(*key),
+            Binding::Mouse(button) => input.mouse_held(*button),
+        }
+    }
+
+    pub fn pressed(&self, input: &WinitInputHelper) -> bool {
+        match self {
+            Binding::Key(key) => input.key_pressed(*key),
+            Binding::Mouse(button) => input.mouse_pressed(*button),
+        }
+    }
+}
+
+// Action mapping of the [Controls] section, each action has one or more bindings
+#[derive(Clone)]
+pub struct Controls {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Controls {
+    pub fn new() -> Self {
+        Controls {
+            bindings: Action::ALL.iter().map(|action| (*action, action.default_bindings())).collect(),
+        }
+    }
+
+    // Comma separated bindings, the actions left out or without a valid one keep their default ones
+    pub fn from(props: Option<&Properties>) -> Self {
+        let mut controls = Controls::new();
+        if let Some(props) = props {
+            for action in Action::ALL.iter() {
+                if let Some(value) = props.get(&action.name()) {
+                    let mut bindings: Vec<Binding> = vec![];
+                    for name in value.split(',').filter(|name| !name.trim().is_empty()) {
+                        match Binding::from_str(name) {
+                            Some(binding) => bindings.push(binding),
+                            None => eprintln!("Invalid binding of {}: {}", action.name(), name.trim())
+                        }
+                    }
+                    if bindings.is_empty() {
+                        continue;
+                    }
+                    controls.bindings.insert(*action, bindings);
+                }
+            }
+        }
+        return controls;
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map(|bindings| bindings.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn held(&self, input: &WinitInputHelper, action: Action) -> bool {
+        self.held_without(input, action, &[])
+    }
+
+    // Keys taken by something else, as the automap pan, do not count
+    pub fn held_without(&self, input: &WinitInputHelper, action: Action, taken: &[KeyCode]) -> bool {
+        self.bindings(action).iter().any(|binding| match binding {
+            Binding::Key(key) if taken.contains(key) => false,
+            _ => binding.held(input)
+        })
+    }
+
+    pub fn pressed(&self, input: &WinitInputHelper, action: Action) -> bool {
+        self.bindings(action).iter().any(|binding| binding.pressed(input))
+    }
+}
@@ -6,7 +6,8 @@ use std::{
     mem::size_of, 
     rc::Rc, 
     ops::Index, 
-    cell::RefCell
+    cell::RefCell,
+    collections::HashMap
 };
 
 // Engine
@@ -103,11 +104,28 @@ pub struct TextureMap {
     pub patch_maps: [PatchMap; 0]
 }
 
+#[allow(dead_code)]
+#[derive(Debug)]
+#[readonly::make]
+#[repr(C, packed(4))]
+pub struct ColorMap([u8; 256]);
+
 pub struct TextureHeader (Vec<u32>);
 
 pub struct Texture<const C : usize> {
     pub size: Vector2<u16>,
-    pub colors: Vec<[u8; C]>
+    // Empty when the indexed pipeline draws the texture
+    pub colors: Vec<[u8; C]>,
+    // PLAYPAL index of each texel, empty when drawing RGBA
+    pub indices: Vec<u8>,
+    // Texels no patch covers, empty when there are none or when drawing RGBA
+    pub holes: Vec<bool>
+}
+
+// Picture with its origin, as menus and status bar patches
+pub struct Graphic {
+    pub texture: Texture<4>,
+    pub offset: Vector2<i16>
 }
 
 // Ref alias
@@ -115,8 +133,12 @@ type RefCount<T> =  Rc<RefCell<T>>;
 
 pub struct DataTextures<'a> {
         reader: Rc<wad::Reader>,
+    // Textures keep the PLAYPAL indices instead of the colors
+    indexed: bool,
     // Palette
     pub palettes: Vec<&'a Palette>,
+    // Light levels, from the brightest to the darkest, plus the invulnerability one
+    pub colormaps: Vec<&'a ColorMap>,
     // Top/Bottom textures
     pub flats_data: Vec<Option<&'a RawFlats>>,
     pub flats_names: Vec<[u8; 8]>,
@@ -129,6 +151,8 @@ pub struct DataTextures<'a> {
     pub texture_patches: Vec<Patch<'a>>,
     pub texture_maps: Vec<&'a TextureMap>,
     pub textures: Rc< RefCell< Vec< Rc< Texture<4> > > > >,
+    // Graphics (status bar, menu, fonts), loaded on demand
+    graphics: RefCell<HashMap<[u8; 8], Option<Rc<Graphic>>>>,
 }
 
 // PatchName
@@ -165,6 +189,36 @@ impl Index<usize> for Palette {
     }
 }
 
+// Nearest color of the palette, for the colors not read from the WAD
+pub fn nearest_index(palette: &[[u8; 3]; 256], color: &[u8]) -> u8 {
+    let distance = |other: &[u8; 3]| -> i32 {
+        (0..3).map(|c| (other[c] as i32 - color[c] as i32).pow(2)).sum()
+    };
+    let mut nearest = 0;
+    for (index, other) in palette.iter().enumerate() {
+        if distance(other) < distance(&palette[nearest]) {
+            nearest = index;
+        }
+    }
+    return nearest as u8;
+}
+
+// ColorMap
+impl ColorMap {
+    // Slices method
+    pub fn slices(&self) -> &[u8; 256] {
+        &self.0
+    }
+}
+
+impl Index<usize> for ColorMap {
+    type Output = u8;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        &self.0[idx]
+    }
+}
+
 // Pnames
 impl PNames {
     pub fn iter(&self) -> std::slice::Iter<'_,[u8; 8]> {
@@ -280,6 +334,15 @@ impl<const C: usize> Texture<C> {
     pub fn get(&self, x: u16, y: u16) -> &[u8; C] {
         &self.colors[(self.size.x * y + x) as usize]
     }
+
+    pub fn index(&self, x: u16, y: u16) -> u8 {
+        self.indices[(self.size.x * y + x) as usize]
+    }
+
+    // Transparency of the indexed texels, the colors keep it in the alpha
+    pub fn is_opaque(&self, x: u16, y: u16) -> bool {
+        self.holes.is_empty() || !self.holes[(self.size.x * y + x) as usize]
+    }
 }
 
 // Test if the sprite or texture is a sky
@@ -314,10 +377,12 @@ pub fn remap_sky_texture(pname: &[u8; 8]) -> [u8; 8] {
 
 // Implement DataTextures
 impl<'a> DataTextures<'a> {
-    pub fn new(reader: &Rc<wad::Reader>) -> Option<Self> {
+    pub fn new(reader: &Rc<wad::Reader>, indexed: bool) -> Option<Self> {
         let mut data_textures: DataTextures<'a> = DataTextures {
             reader: reader.clone(),
+            indexed: indexed,
             palettes: vec![], 
+            colormaps: vec![],
             // Flats (bottom, top textures)
             flats_data: vec![], 
             flats_names: vec![],
@@ -330,11 +395,13 @@ impl<'a> DataTextures<'a> {
             texture_patches: vec![],            
             texture_maps: vec![],
             textures: Rc::new(RefCell::new(vec![])),
+            graphics: RefCell::new(HashMap::new()),
         };
         if let Some(directories) = data_textures.reader.directories() {
             if let Some(palettes_id) = directories.index_of(&String::from("PLAYPAL")) {
                 // Palettes
                 data_textures.palettes = data_textures.extract_vec::<Palette>(&directories[palettes_id]);
+                data_textures.colormaps = data_textures.extract_vec_from_name::<ColorMap>(&directories, String::from("COLORMAP"));
                 // Flats
                 data_textures.flats_names = data_textures.extract_a_directories_names_set(&directories, String::from("F_START"), String::from("F_END"));
                 data_textures.flats_data = data_textures.extract_a_flats(&directories, String::from("F_START"), String::from("F_END"));
@@ -450,37 +517,63 @@ impl<'a> DataTextures<'a> {
                                 .collect()
     }
 
+    fn to_indices<const C: usize>(&self, colors: &[[u8; C]]) -> Vec<u8> {
+        match self.palettes.first() {
+            Some(palette) => colors.iter().map(|color| nearest_index(palette.slices(), color)).collect(),
+            None => vec![0; colors.len()]
+        }
+    }
+
+    // Keeps the texels the pipeline draws from, the indices or the colors
+    fn keep_texels<const C: usize>(&self, mut texture: Texture<C>) -> Texture<C> {
+        if self.indexed {
+            if C == 4 && texture.colors.iter().any(|color| color[3] == 0) {
+                texture.holes = texture.colors.iter().map(|color| color[3] == 0).collect();
+            }
+            texture.colors = vec![];
+        } else {
+            texture.indices = vec![];
+        }
+        return texture;
+    }
+
     // Flats
     fn build_flats(&mut self, palette: &Palette) {
         self.flats.as_ref().borrow_mut().clear();
         self.flats.as_ref().borrow_mut().reserve(self.flats_data.len());
         for raw_flat in &self.flats_data {
             if let Some(ref ptexture) = raw_flat {
-                self.flats.as_ref().borrow_mut().push(Rc::new(
+                self.flats.as_ref().borrow_mut().push(Rc::new(self.keep_texels(
                     Texture {
                         size: Vector2::new(64, 64),
                         colors: {
                             ptexture.slices().iter().map(|id| (*palette)[(*id) as usize]).collect()
-                        }
+                        },
+                        indices: ptexture.slices().to_vec(),
+                        holes: vec![]
                     }
-                ))
+                )))
             } else {
-                self.flats.as_ref().borrow_mut().push(Rc::new(
+                self.flats.as_ref().borrow_mut().push(Rc::new(self.keep_texels(
                     Texture {
                         size: Vector2::new(64, 64),
-                        colors: self.unknown_flat()
+                        colors: self.unknown_flat(),
+                        indices: self.to_indices(&self.unknown_flat()),
+                        holes: vec![]
                     }
-                ))
+                )))
             }
         }
         // Put unknown flat at the end
         self.flats_names.push([0x01,b'U',b'N',b'K',b'N',b'O',b'W',b'N']); // Start with ASCII header start
-        self.flats.as_ref().borrow_mut().push(Rc::new(
+        self.flats.as_ref().borrow_mut().push(Rc::new(self.keep_texels(
             Texture { 
                 size: Vector2::new(64, 64),
-                colors: self.unknown_flat()
+                colors: self.unknown_flat(),
+                indices: self.to_indices(&self.unknown_flat()),
+                holes: vec![]
             }
-        ));
+        )));
     }
 
     pub fn flat(&self, name: &[u8; 8]) -> Option<Rc<Texture<3>>> {
@@ -525,11 +618,12 @@ impl<'a> DataTextures<'a> {
     }
 
     fn build_patch_as_texture(&self, patch: &Patch, palette: &Palette) -> Texture<4> {
+        let width = patch.header.size[0] as usize;
+        let height = patch.header.size[1] as usize;
+        let mut texture_indices: Vec<u8> = vec![0; width * height];
         let texture = Texture {
             size: Vector2::new(patch.header.size[0], patch.header.size[1]),
             colors: {
-                let width = patch.header.size[0] as usize;
-                let height = patch.header.size[1] as usize;
                 let size = width * height;
                 let mut texture_data: Vec<[u8; 4]> = vec![[0,0,0,0]; size];
                 for x in 0..width {
@@ -542,15 +636,18 @@ impl<'a> DataTextures<'a> {
                                 let palette_id = *pidx as usize;
                                 texture_data[y * width + x][0..3].copy_from_slice(&(*palette)[palette_id]);
                                 texture_data[y * width + x][3] = 0xFF;
+                                texture_indices[y * width + x] = *pidx;
                                 y += 1;
                             }
                         }
                     }
                 }
                 texture_data
-            }
+            },
+            indices: texture_indices,
+            holes: vec![]
         };
-        texture
+        self.keep_texels(texture)
     }
 
     fn build_sprites(&mut self, palette: &Palette) {
@@ -567,6 +664,55 @@ impl<'a> DataTextures<'a> {
         None
     }
 
+    // Left/top offset of the sprite origin
+    pub fn sprite_offset(&self, name: &[u8; 8]) -> Option<Vector2<i16>> {
+        if let Some(patch) = self.sprite_patches.iter().find(|patch| patch.name == *name) {
+            let offset = patch.header.offset;
+            return Some(Vector2::new(offset[0] as i16, offset[1] as i16));
+        }
+        None
+    }
+
+    // Graphics
+    pub fn graphic(&self, name: &str) -> Option<Rc<Graphic>> {
+        let mut lump_name = [0u8; 8];
+        for (i, c) in name.bytes().take(8).enumerate() {
+            lump_name[i] = c.to_ascii_uppercase();
+        }
+        if let Some(graphic) = self.graphics.borrow().get(&lump_name) {
+            return graphic.clone();
+        }
+        let graphic = match (self.reader.directories(), self.palettes.first()) {
+            (Some(directories), Some(palette)) => {
+                self.extract_patch(&directories, &lump_name).map(|patch| {
+                    let offset = patch.header.offset;
+                    Rc::new(Graphic {
+                        texture: self.build_patch_as_texture(&patch, palette),
+                        offset: Vector2::new(offset[0] as i16, offset[1] as i16)
+                    })
+                })
+            },
+            _ => None
+        };
+        self.graphics.borrow_mut().insert(lump_name, graphic.clone());
+        return graphic;
+    }
+
+    // Font patches STCFN033 to STCFN095, the font has no lower case
+    pub fn font_glyph(&self, c: char) -> Option<Rc<Graphic>> {
+        let c = c.to_ascii_uppercase() as u32;
+        if c < 33 || c > 95 {
+            return None;
+        }
+        return self.graphic(&format!("STCFN{:03}", c));
+    }
+
+    // COLORMAP of a light level in [0, 1], as the 32 light maps of the renderer
+    pub fn colormap(&self, light_level: f32) -> Option<&'a ColorMap> {
+        let index = ((1.0 - light_level) * 32.0).clamp(0.0, 31.0) as usize;
+        self.colormaps.get(index).copied()
+    }
+
     // Textures
     fn extract_patch(&self, directories: &wad::DirectoryList, name: &[u8; 8]) -> Option<Patch<'a>> {
         let str_name = u8str_to_string(&name).ok().unwrap_or_default();
@@ -657,7 +803,8 @@ impl<'a> DataTextures<'a> {
     fn build_textures(&mut self) {
         self.textures.as_ref().borrow_mut().reserve(self.texture_maps.len());
         for texture_map in &self.texture_maps {
-            self.textures.as_ref().borrow_mut().push(Rc::new(Texture {
+            let mut texture_indices: Vec<u8> = vec![0; texture_map.size[0] as usize * texture_map.size[1] as usize];
+            self.textures.as_ref().borrow_mut().push(Rc::new(self.keep_texels(Texture {
                 size: Vector2::new(texture_map.size[0], texture_map.size[1]),
                 colors : {
                     // Texture
@@ -689,6 +836,7 @@ impl<'a> DataTextures<'a> {
                                             let texture_idx = texture_data_span + texture_data_x as usize;
                                             texture_data[texture_idx][0..3].copy_from_slice(&(*palette)[palette_id]);
                                             texture_data[texture_idx][3] = 0xFF;
+                                            texture_indices[texture_idx] = *pidx;
                                             texture_data_y += 1;
                                         }
                                     }
@@ -701,8 +849,10 @@ impl<'a> DataTextures<'a> {
                         }
                     }
                     texture_data
-                }
-            }));
+                },
+                indices: texture_indices,
+                holes: vec![]
+            })));
         }
         // Put unknown texture at the end
         self.texture_maps.push(&TextureMap {
@@ -713,10 +863,12 @@ impl<'a> DataTextures<'a> {
             patch_map_count: 0,
             patch_maps: []
         });
-        self.textures.as_ref().borrow_mut().push(Rc::new(Texture { 
+        self.textures.as_ref().borrow_mut().push(Rc::new(self.keep_texels(Texture { 
             size: Vector2::new(64, 64),
-            colors: self.unknown_texture()
-        }));
+            colors: self.unknown_texture(),
+            indices: self.to_indices(&self.unknown_texture()),
+            holes: vec![]
+        })));
     }
 
     pub fn texture(&self, name: &[u8; 8]) -> Option<Rc<Texture<4>>> {
@@ -1,11 +1,11 @@
 // Using engine
-use crate::actors::Actor;
+use crate::actors::{actor_id, Actor, Monster, Pickup, Transform};
 use crate::bsp::BSP;
-use crate::configure::Configure;
+use crate::configure::{self, Configure};
 use crate::collision::CollisionSolver;
 use crate::render::{
     render_2d::{RenderBSP, RenderCamera, RenderMap, RenderCollision, RenderTextures},
-    render_3d::RenderSoftware,
+    render_3d::{RenderSoftware, RenderWeapon},
     Render,
 };
 use crate::shape::Size;
@@ -13,14 +13,36 @@ use crate::wad::Reader;
 use crate::window::DoomSurface;
 use crate::{actors::Player, map::Map};
 use crate::data_textures::DataTextures;
+use crate::automap::{Automap, RenderAutomap};
+use crate::hud::{RenderHud, RenderStatusBar};
+use crate::text::RenderMessages;
+use crate::intermission::{Intermission, IntermissionState, RenderIntermission};
+use crate::level::{LevelStats, MapSlot};
+use crate::console::{Command, Console, RenderConsole};
+use crate::wipe::Wipe;
+use crate::palette::PaletteShift;
+use crate::sound::{sound_info, SoundBank};
+use crate::mixer::{adjust_sound_params, Mixer, WavRecorder};
+use crate::music::{intermission_music, map_music, Mus, MusicPlayer};
+use crate::opl::{GenMidi, OplSynth};
+use crate::noise::SoundTargets;
+use crate::ticcmd::{Demo, DemoPlayback, DemoRecorder, InputSource, LocalInput, TicCmd};
+use crate::menu::{Menu, MenuAction, RenderMenu, Settings};
+use crate::savegame::{load_game, save_game};
+use crate::math::{Vector2, Vector4};
+use crate::collision::CollisionClass;
+use crate::random::Random;
+use crate::types::{Skill, ThingType};
+use crate::time::tics;
 // Utils
 use std::boxed::Box;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
 use std::rc::Rc;
 use std::vec::Vec;
 use winit::dpi::PhysicalSize;
 use winit::window::Window;
-use winit::{event::Event, keyboard::KeyCode};
+use winit::event::Event;
 use winit_input_helper::WinitInputHelper;
 
 pub struct Doom<'wad> {
@@ -31,6 +53,35 @@ pub struct Doom<'wad> {
     pub data_textures: Rc<DataTextures<'wad>>,
     pub bsp: BSP<'wad>,
     pub actors: Vec<Rc<RefCell<Box<dyn Actor>>>>,
+    pub spawns: RefCell<Vec<Rc<RefCell<Box<dyn Actor>>>>>,
+    pub random: RefCell<Random>,
+    pub configure: Configure,
+    pub messages: RefCell<VecDeque<String>>,
+    pub automap: Automap,
+    pub menu: Menu,
+    pub console: Console,
+    pub skill: Skill,
+    screen_size: i32,
+    pub stats: RefCell<LevelStats>,
+    pub sound_targets: RefCell<SoundTargets>,
+    pub intermission: Option<Intermission>,
+    pub wipe: Option<Wipe>,
+    palette_shift: PaletteShift,
+    pub sounds: SoundBank,
+    pub mixer: RefCell<Mixer>,
+    // Where the player hears from, cached as it is borrowed while it runs
+    listener: RefCell<Option<Transform>>,
+    recorder: Option<WavRecorder>,
+    pub music: MusicPlayer,
+    synth: Option<OplSynth>,
+    // Commands of the player, a scripted input can replace it
+    pub input_source: Box<dyn InputSource>,
+    demo_recorder: Option<DemoRecorder>,
+    // Time not yet run by the tics
+    tic_time: f32,
+    restart: Cell<bool>,
+    // Deferred level exit, true for the secret exit
+    exit: Cell<Option<bool>>,
 
     pub surface: Rc<RefCell<DoomSurface>>,
     pub renders: Vec<Rc<RefCell<Box<dyn Render + 'wad>>>>,
@@ -47,7 +98,12 @@ impl<'wad> Doom<'wad> {
     pub fn new(window: &Window, configure: &Configure) -> Box<Self> {
         let wad = Rc::new(Reader::new(&configure.resource.wad).unwrap());
         let map = Rc::new(Map::new(&wad, &configure.map).unwrap());
-        let data_textures = Rc::new(DataTextures::new(&wad).unwrap());
+        let data_textures = Rc::new(DataTextures::new(&wad, configure.screen.indexed).unwrap());
+        let episodes = Doom::episodes(&wad);
+        let sounds = SoundBank::new(&wad, configure.sound.rate, configure.sound.sfx);
+        let synth = Doom::create_synth(&wad, &configure.sound);
+        let actors = Doom::create_actors(&map, &configure, Skill::Medium);
+        let stats = LevelStats::new(&map, &actors);
         let surface = Rc::new(RefCell::new(
             DoomSurface::new(
                 PhysicalSize::<u32>::new(
@@ -59,7 +115,10 @@ impl<'wad> Doom<'wad> {
             )
             .unwrap(),
         ));
-        Box::new(Doom {
+        if let (true, Some(palette)) = (configure.screen.indexed, data_textures.palettes.first()) {
+            surface.borrow_mut().enable_indexed(palette);
+        }
+        let mut doom = Box::new(Doom {
             // Resource
             wad,
             // Logic
@@ -67,88 +126,615 @@ impl<'wad> Doom<'wad> {
             map: map.clone(),
             data_textures: data_textures.clone(),
             bsp: BSP::new(&map),
-            actors: Doom::create_actors(&map, &configure),
+            actors,
+            spawns: RefCell::new(vec![]),
+            random: RefCell::new(Random::new()),
+            configure: configure.clone(),
+            messages: RefCell::new(VecDeque::new()),
+            automap: Automap::new(&map),
+            menu: Menu::new(episodes),
+            console: Console::new(),
+            skill: Skill::Medium,
+            screen_size: Settings::new().screen_size,
+            stats: RefCell::new(stats),
+            sound_targets: RefCell::new(SoundTargets::new(&map)),
+            intermission: None,
+            wipe: None,
+            palette_shift: PaletteShift::new(&data_textures.palettes),
+            sounds,
+            mixer: RefCell::new(Mixer::new(configure.sound.rate, Doom::sound_channels(&configure.sound))),
+            listener: RefCell::new(None),
+            recorder: configure.sound.wav.as_ref().and_then(|path| {
+                WavRecorder::new(path, configure.sound.rate).map_err(|err| eprintln!("Failed to record the sound: {}", err)).ok()
+            }),
+            music: MusicPlayer::new(),
+            synth,
+            input_source: Box::new(LocalInput::new(configure)),
+            demo_recorder: None,
+            tic_time: 0.0,
+            restart: Cell::new(false),
+            exit: Cell::new(None),
             // Render
             surface,
-            renders: {
-                let mut renders = vec![];
-                if let Some(render) = &configure.render {
-                    if let Some(software_3d) = &render.software_3d {
-                        renders.push(crea_render!(RenderSoftware::new(
-                            &map,
-                            &data_textures,
-                            software_3d.zw(),
-                            software_3d.xy(),
-                            &configure.camera
-                        )));
-                    }
-                    if let Some(sprite_2d) = &render.texture_2d {
-                        renders.push(crea_render!(RenderTextures::new(
-                            &data_textures,
-                            &data_textures.textures,
-                            sprite_2d.zw(),
-                            sprite_2d.xy(),
-                        )));
-                    }
-                    if let Some(sprite_2d) = &render.sprite_2d {
-                        renders.push(crea_render!(RenderTextures::new(
-                            &data_textures,
-                            &data_textures.sprites,
-                            sprite_2d.zw(),
-                            sprite_2d.xy(),
-                        )));
-                    }
-                    if let Some(flat_2d) = &render.flat_2d {
-                        renders.push(crea_render!(RenderTextures::new(
-                            &data_textures,
-                            &data_textures.flats,
-                            flat_2d.zw(),
-                            flat_2d.xy(),
-                        )));
-                    }
-                    if let Some(map_2d) = &render.map_2d {
-                        renders.push(crea_render!(RenderMap::new(
-                            &map,
-                            map_2d.zw(),
-                            map_2d.xy()
-                        )));
-                    }
-                    if let Some(bsp_2d) = &render.bsp_2d {
-                        renders.push(crea_render!(RenderBSP::new(
-                            &map,
-                            bsp_2d.zw(),
-                            bsp_2d.xy()
-                        )));
-                    }
-                    if let Some(camera_2d) = &render.camera_2d {
-                        renders.push(crea_render!(RenderCamera::new(
-                            &map,
-                            camera_2d.zw(),
-                            camera_2d.xy(),
-                            &configure.camera
-                        )));
-                    }
-                    if let Some(collision_2d) = &render.collision_2d {
-                        renders.push(crea_render!(RenderCollision::new(
-                            &map,
-                            collision_2d.zw(),
-                            collision_2d.xy(),
-                        )));
-                    }
-                }
-                renders
-            },
+            renders: Doom::create_renders(&map, &data_textures, &configure, Settings::new().screen_size),
             collider: Rc::new(RefCell::new(CollisionSolver::new(&map)))
-        })
+        });
+        doom.change_map_music();
+        doom.start_demos();
+        return doom;
+    }
+
+    // Playback starts the map of the demo, recording saves the current one
+    fn start_demos(&mut self) {
+        if let Some(path) = self.configure.demo.play.clone() {
+            match Demo::load(&path) {
+                Ok(demo) => {
+                    let episode = MapSlot::Episode { episode: demo.episode, map: demo.map };
+                    let name = if self.has_map(&episode.name()) { episode.name() } else { MapSlot::Commercial { map: demo.map }.name() };
+                    self.skill = Skill::ALL.get(demo.skill as usize).copied().unwrap_or(Skill::Medium);
+                    if let Err(err) = self.load_map(&name, false) {
+                        self.message(&err);
+                    }
+                    self.input_source = Box::new(DemoPlayback::new(demo));
+                },
+                Err(err) => self.message(&err)
+            }
+        }
+        if let Some(path) = self.configure.demo.record.clone() {
+            let (episode, map) = match MapSlot::parse(self.map_name()) {
+                Some(MapSlot::Episode { episode, map }) => (episode, map),
+                Some(MapSlot::Commercial { map }) => (1, map),
+                None => (1, 1)
+            };
+            let skill = Skill::ALL.iter().position(|skill| *skill == self.skill).unwrap_or(2) as u8;
+            self.demo_recorder = Some(DemoRecorder::new(&path, Demo::new(skill, episode, map)));
+        }
+    }
+
+    // The view shrinks with the screen size, at 11 it takes the status bar place and the HUD replaces it
+    fn view_layout(render: &configure::Render, screen_size: i32) -> (Option<Vector4<i32>>, Option<Vector4<i32>>, Option<Vector4<i32>>) {
+        let software_3d = match render.software_3d {
+            Some(view) => view,
+            None => return (None, render.status_bar, render.hud)
+        };
+        if screen_size > 10 {
+            let view = match render.status_bar {
+                Some(bar) => {
+                    let min = Vector2::new(software_3d.x.min(bar.x), software_3d.y.min(bar.y));
+                    let max = Vector2::new((software_3d.x + software_3d.z).max(bar.x + bar.z), (software_3d.y + software_3d.w).max(bar.y + bar.w));
+                    Vector4::new(min.x, min.y, max.x - min.x, max.y - min.y)
+                },
+                None => software_3d
+            };
+            return (Some(view), None, Some(render.hud.unwrap_or(view)));
+        }
+        let size = Vector2::new(software_3d.z * screen_size / 10, software_3d.w * screen_size / 10);
+        let view = Vector4::new(
+            software_3d.x + (software_3d.z - size.x) / 2,
+            software_3d.y + (software_3d.w - size.y) / 2,
+            size.x,
+            size.y
+        );
+        return (Some(view), render.status_bar, render.hud);
+    }
+
+    fn create_renders(map: &Rc<Map<'wad>>, data_textures: &Rc<DataTextures<'wad>>, configure: &Configure, screen_size: i32) -> Vec<Rc<RefCell<Box<dyn Render + 'wad>>>> {
+        let mut renders = vec![];
+        if let Some(render) = &configure.render {
+            let (software_3d, status_bar, hud) = Doom::view_layout(render, screen_size);
+            if let Some(software_3d) = &software_3d {
+                renders.push(crea_render!(RenderSoftware::new(
+                    &map,
+                    &data_textures,
+                    software_3d.zw(),
+                    software_3d.xy(),
+                    &configure.camera
+                )));
+                renders.push(crea_render!(RenderWeapon::new(
+                    &data_textures,
+                    software_3d.zw(),
+                    software_3d.xy()
+                )));
+            }
+            if let Some(automap) = &render.automap {
+                renders.push(crea_render!(RenderAutomap::new(
+                    &map,
+                    &data_textures,
+                    automap.zw(),
+                    automap.xy()
+                )));
+            }
+            if let Some(status_bar) = &status_bar {
+                renders.push(crea_render!(RenderStatusBar::new(
+                    &data_textures,
+                    status_bar.zw(),
+                    status_bar.xy()
+                )));
+            }
+            if let Some(hud) = &hud {
+                renders.push(crea_render!(RenderHud::new(
+                    &data_textures,
+                    hud.zw(),
+                    hud.xy()
+                )));
+            }
+            if let Some(messages) = &render.messages {
+                renders.push(crea_render!(RenderMessages::new(
+                    &data_textures,
+                    messages.zw(),
+                    messages.xy()
+                )));
+            }
+            if let Some(menu) = &render.menu {
+                renders.push(crea_render!(RenderMenu::new(
+                    &data_textures,
+                    menu.zw(),
+                    menu.xy()
+                )));
+            }
+            if let Some(intermission) = &render.intermission {
+                renders.push(crea_render!(RenderIntermission::new(
+                    &data_textures,
+                    intermission.zw(),
+                    intermission.xy()
+                )));
+            }
+            if let Some(console) = &render.console {
+                renders.push(crea_render!(RenderConsole::new(
+                    &data_textures,
+                    console.zw(),
+                    console.xy()
+                )));
+            }
+            if let Some(sprite_2d) = &render.texture_2d {
+                renders.push(crea_render!(RenderTextures::new(
+                    &data_textures,
+                    &data_textures.textures,
+                    sprite_2d.zw(),
+                    sprite_2d.xy(),
+                )));
+            }
+            if let Some(sprite_2d) = &render.sprite_2d {
+                renders.push(crea_render!(RenderTextures::new(
+                    &data_textures,
+                    &data_textures.sprites,
+                    sprite_2d.zw(),
+                    sprite_2d.xy(),
+                )));
+            }
+            if let Some(flat_2d) = &render.flat_2d {
+                renders.push(crea_render!(RenderTextures::new(
+                    &data_textures,
+                    &data_textures.flats,
+                    flat_2d.zw(),
+                    flat_2d.xy(),
+                )));
+            }
+            if let Some(map_2d) = &render.map_2d {
+                renders.push(crea_render!(RenderMap::new(
+                    &map,
+                    map_2d.zw(),
+                    map_2d.xy()
+                )));
+            }
+            if let Some(bsp_2d) = &render.bsp_2d {
+                renders.push(crea_render!(RenderBSP::new(
+                    &map,
+                    bsp_2d.zw(),
+                    bsp_2d.xy()
+                )));
+            }
+            if let Some(camera_2d) = &render.camera_2d {
+                renders.push(crea_render!(RenderCamera::new(
+                    &map,
+                    camera_2d.zw(),
+                    camera_2d.xy(),
+                    &configure.camera
+                )));
+            }
+            if let Some(collision_2d) = &render.collision_2d {
+                renders.push(crea_render!(RenderCollision::new(
+                    &map,
+                    collision_2d.zw(),
+                    collision_2d.xy(),
+                )));
+            }
+        }
+        return renders;
+    }
+
+    // The PC speaker plays one sound at a time
+    fn sound_channels(sound: &configure::Sound) -> usize {
+        match sound.sfx {
+            configure::SoundDevice::Digital => sound.channels,
+            configure::SoundDevice::Speaker => 1,
+        }
+    }
+
+    // FM synth of the music, it needs the GENMIDI instruments of the WAD
+    pub fn create_synth(wad: &Reader, sound: &configure::Sound) -> Option<OplSynth> {
+        let opl3 = match sound.music {
+            configure::MusicDevice::None => return None,
+            configure::MusicDevice::Opl2 => false,
+            configure::MusicDevice::Opl3 => true,
+        };
+        let bank = GenMidi::parse(wad.lump("GENMIDI")?).ok()?;
+        return Some(OplSynth::new(bank, sound.rate, opl3));
+    }
+
+    // Episodes with a first map in the WAD
+    fn episodes(wad: &Reader) -> [bool; 4] {
+        match wad.directories() {
+            Some(directories) => std::array::from_fn(|episode| directories.index_of(&format!("E{}M1", episode + 1)).is_some()),
+            None => [false; 4]
+        }
     }
 
     pub fn update(&mut self, last_frame_time: f64, blending_factor: f64) {
+        self.update_sound(last_frame_time as f32);
+        // The game waits for the screen wipe, as D_Display
+        if let Some(wipe) = &mut self.wipe {
+            wipe.update(last_frame_time as f32);
+            if wipe.is_done() {
+                self.wipe = None;
+            }
+            return;
+        }
+        // The game is paused while the menu is open
+        if self.menu.is_active() {
+            self.menu.update(last_frame_time as f32);
+            return;
+        }
+        // Intermission between the levels
+        if let Some(intermission) = &mut self.intermission {
+            intermission.update(last_frame_time as f32);
+            if intermission.state() == IntermissionState::Done {
+                self.world_done();
+            }
+            return;
+        }
+        // The game runs on fixed tics, as G_Ticker, a frame may run none or several of them
+        self.tic_time += last_frame_time as f32;
+        while self.tic_time >= tics(1) {
+            self.tic_time -= tics(1);
+            self.run_tic(blending_factor);
+            // A wipe, the menu or the intermission stop the tics left
+            if self.wipe.is_some() || self.menu.is_active() || self.intermission.is_some() {
+                self.tic_time = 0.0;
+                break;
+            }
+        }
+        // Automap view
+        let player_position = self.player().map(|player| *player.borrow().position());
+        self.automap.update(player_position, last_frame_time as f32);
+    }
+
+    fn run_tic(&mut self, blending_factor: f64) {
+        let tic_time = tics(1);
+        self.stats.borrow_mut().time += tic_time;
+        // Command of the tic, the keyboard takes over at the end of a demo
+        let cmd = match self.input_source.build(tic_time) {
+            Some(cmd) => cmd,
+            None => {
+                self.input_source = Box::new(LocalInput::new(&self.configure));
+                TicCmd::default()
+            }
+        };
+        // The recorded command is the one played, with the turn the demo can store
+        let cmd = match &mut self.demo_recorder {
+            Some(demo_recorder) => demo_recorder.record(&cmd),
+            None => cmd
+        };
+        if let Some(player) = self.player() {
+            player.borrow_mut().command(&cmd);
+        }
         // Update actors
         for actor in &self.actors {
-            actor.borrow_mut().update(self, last_frame_time, blending_factor);
+            actor.borrow_mut().update(self, tic_time as f64, blending_factor);
         }
         // Update collisions
-        self.collider.clone().borrow_mut().update(self, last_frame_time, blending_factor);
+        self.collider.clone().borrow_mut().update(self, tic_time as f64, blending_factor);
+        // Remove dead actors and add the spawned ones
+        self.actors.retain(|actor| !actor.borrow().is_removed());
+        let spawns: Vec<_> = self.spawns.borrow_mut().drain(..).collect();
+        self.actors.extend(spawns);
+        // Restart the level from its things
+        if self.restart.replace(false) {
+            self.reset_level(false);
+        }
+        // Level exit
+        if let Some(secret) = self.exit.take() {
+            self.complete_level(secret);
+        }
+    }
+
+    pub fn map_name(&self) -> &str {
+        &self.configure.map.name
+    }
+
+    // Respawn the level things on the current skill, the player can keep its status and inventory
+    pub fn reset_level(&mut self, keep_player: bool) {
+        let previous = if keep_player { self.player() } else { None };
+        self.actors = Doom::create_actors(&self.map, &self.configure, self.skill);
+        if let (Some(previous), Some(player)) = (previous, self.player()) {
+            if let (Some(previous), Some(player)) = (previous.borrow().as_player(), player.borrow_mut().as_player_mut()) {
+                player.carry_over(previous);
+            }
+        }
+        self.spawns.borrow_mut().clear();
+        self.automap = Automap::new(&self.map);
+        self.stats = RefCell::new(LevelStats::new(&self.map, &self.actors));
+        self.sound_targets.borrow_mut().clear();
+        self.mixer.borrow_mut().stop_all();
+        self.change_map_music();
+        self.start_wipe();
+    }
+
+    // Melt from the last drawn frame to the next ones
+    pub fn start_wipe(&mut self) {
+        self.wipe = Some(Wipe::new(&self.surface.borrow()));
+    }
+
+    // Ends the level from an exit line, deferred as the actors are borrowed meanwhile
+    pub fn exit_level(&self, secret: bool) {
+        self.exit.set(Some(secret));
+    }
+
+    // As G_DoCompleted, maps out of the naming schemes just restart
+    fn complete_level(&mut self, secret: bool) {
+        match MapSlot::parse(self.map_name()) {
+            Some(finished) => {
+                let next = finished.next(secret).filter(|next| self.has_map(&next.name()));
+                let stats = self.stats.borrow().clone();
+                self.change_music(intermission_music(&finished), true);
+                self.intermission = Some(Intermission::new(finished, next, stats));
+                self.start_wipe();
+            },
+            None => self.reset_level(true)
+        }
+    }
+
+    // As G_WorldDone, after the episode end it goes back to its first map with the menu open
+    fn world_done(&mut self) {
+        let intermission = match self.intermission.take() {
+            Some(intermission) => intermission,
+            None => return
+        };
+        let next = match intermission.next() {
+            Some(next) => next,
+            None => {
+                self.menu.open_main();
+                match MapSlot::parse(self.map_name()) {
+                    Some(MapSlot::Episode { episode, .. }) => MapSlot::Episode { episode, map: 1 },
+                    _ => MapSlot::Commercial { map: 1 }
+                }
+            }
+        };
+        // The player carries its weapons, ammo, health and armor into the next map, not into a new episode
+        let keep_player = intermission.next().is_some();
+        if let Err(err) = self.load_map(&next.name(), keep_player) {
+            self.message(&err);
+            self.reset_level(keep_player);
+        }
+    }
+
+    pub fn has_map(&self, name: &str) -> bool {
+        self.wad.directories().map(|directories| directories.index_of(&name.to_string()).is_some()).unwrap_or(false)
+    }
+
+    // Unload the current map and load another one from the WAD, the WAD and the textures are kept.
+    // The player can be carried into it, else it starts anew. On error the current map stays loaded.
+    pub fn load_map(&mut self, name: &str, keep_player: bool) -> Result<(), String> {
+        let name = name.to_ascii_uppercase();
+        if !self.has_map(&name) {
+            return Err(format!("Map {} not found", name));
+        }
+        let configure = self.configure.with_map(&name);
+        let map = Rc::new(Map::new(&self.wad, &configure.map).ok_or(format!("Map {} is not valid", name))?);
+        self.configure = configure;
+        self.intermission = None;
+        self.exit.set(None);
+        self.restart.set(false);
+        self.map = map.clone();
+        self.bsp = BSP::new(&map);
+        self.sound_targets = RefCell::new(SoundTargets::new(&map));
+        self.collider = Rc::new(RefCell::new(CollisionSolver::new(&map)));
+        self.renders = Doom::create_renders(&map, &self.data_textures, &self.configure, self.screen_size);
+        self.reset_level(keep_player);
+        return Ok(());
+    }
+
+    // Doom II has no episodes and starts from MAP01
+    fn new_game(&mut self, episode: u8, skill: Skill) {
+        self.skill = skill;
+        let first = MapSlot::Episode { episode, map: 1 };
+        let name = if self.has_map(&first.name()) { first.name() } else { MapSlot::Commercial { map: 1 }.name() };
+        if let Err(err) = self.load_map(&name, false) {
+            self.message(&err);
+            self.reset_level(false);
+        }
+    }
+
+    fn execute(&mut self, command: Command) {
+        match command {
+            // The player keeps what it carries, as through a level exit
+            Command::Map(name) => {
+                if let Err(err) = self.load_map(&name, true) {
+                    self.message(&err);
+                }
+            },
+            Command::Unknown(line) => self.message(&format!("Unknown command: {}", line))
+        }
+    }
+
+    fn menu_action(&mut self, action: MenuAction) -> bool {
+        match action {
+            MenuAction::NewGame { episode, skill } => self.new_game(episode, skill),
+            MenuAction::LoadGame(slot) => {
+                if let Err(err) = load_game(self, slot) {
+                    self.message(&err);
+                }
+            },
+            MenuAction::SaveGame(slot) => {
+                let description = self.map_name().to_string();
+                match save_game(self, slot, &description) {
+                    Ok(_) => self.message("game saved."),
+                    Err(err) => self.message(&err)
+                }
+            },
+            MenuAction::Quit => return false
+        }
+        return true;
+    }
+
+    // As S_StartSound, sounds without origin or from the listener are not positioned
+    pub fn start_sound(&self, name: &str, origin: Option<&dyn Actor>) {
+        let info = match sound_info(name) {
+            Some(info) => info,
+            None => return
+        };
+        let samples = match self.sounds.get(info.name) {
+            Some(samples) => samples,
+            None => return
+        };
+        let mut mixer = self.mixer.borrow_mut();
+        let mut volume = mixer.volume();
+        let mut separation = 128;
+        if let Some(origin) = origin.filter(|origin| origin.as_player().is_none()) {
+            if let Some(listener) = self.listener.borrow().as_ref() {
+                match adjust_sound_params(listener, origin.position(), volume) {
+                    Some(params) => (volume, separation) = params,
+                    None => return
+                }
+            }
+        }
+        mixer.start(&samples, info.priority, origin.map(actor_id), volume, separation);
+    }
+
+    // As S_ChangeMusic, the song playing goes on and a missing or invalid one stops the music
+    pub fn change_music(&mut self, lump: &str, looping: bool) {
+        if self.music.name() == Some(lump) {
+            return;
+        }
+        match self.wad.lump(lump).map(Mus::parse) {
+            Some(Ok(song)) => self.music.play(lump, song, looping),
+            _ => self.music.stop()
+        }
+        // Notes of the last song stop
+        if let Some(synth) = &mut self.synth {
+            synth.reset();
+        }
+    }
+
+    // Song of the current map, as S_Start
+    fn change_map_music(&mut self) {
+        match map_music(self.map_name()) {
+            Some(lump) => self.change_music(&lump, true),
+            None => self.music.stop()
+        }
+    }
+
+    // As P_NoiseAlert, the monsters in the sectors reached by the noise hear the emitter
+    pub fn noise_alert(&self, emitter: &dyn Actor) {
+        if let Some(sector_id) = self.bsp.sector_id(&emitter.get_transform().position_as_int()) {
+            self.sound_targets.borrow_mut().alert(&self.map, sector_id, actor_id(emitter));
+        }
+    }
+
+    // The player position before the actors run, as update_sound goes first their sounds are heard from there
+    fn update_listener(&self) {
+        let listener = self.player().map(|player| player.borrow().get_transform().clone());
+        *self.listener.borrow_mut() = listener;
+    }
+
+    // Mixes the sounds of the frame, without an audio device they are only recorded
+    fn update_sound(&mut self, last_frame_time: f32) {
+        self.update_listener();
+        let mut mixer = self.mixer.borrow_mut();
+        mixer.set_sfx_volume(self.menu.settings().sfx_volume);
+        let listener = self.listener.borrow();
+        mixer.update(listener.as_ref(), |origin| self.find_actor(origin).map(|actor| *actor.borrow().position()));
+        let synth = &mut self.synth;
+        self.music.update(last_frame_time, |event| {
+            if let Some(synth) = synth.as_mut() {
+                synth.handle_mus(event);
+            }
+        });
+        // The WAV recorder is the only output, without it nothing is mixed
+        let recorder = match &mut self.recorder {
+            Some(recorder) => recorder,
+            None => {
+                mixer.advance(last_frame_time);
+                return;
+            }
+        };
+        let mut samples = mixer.render(last_frame_time);
+        if let Some(synth) = &mut self.synth {
+            synth.set_music_volume(self.menu.settings().music_volume);
+            synth.mix(&mut samples);
+        }
+        if let Err(err) = recorder.push(&samples) {
+            eprintln!("Failed to write {}: {}", recorder.path(), err);
+            self.recorder = None;
+        }
+    }
+
+    // Closes the outputs written while playing, returns false to leave the loop
+    fn quit(&mut self) -> bool {
+        if let Some(mut recorder) = self.recorder.take() {
+            if let Err(err) = recorder.finish() {
+                eprintln!("Failed to write {}: {}", recorder.path(), err);
+            }
+        }
+        return false;
+    }
+
+    // Player messages, consumed by the HUD
+    pub fn message(&self, text: &str) {
+        self.messages.borrow_mut().push_back(text.to_string());
+    }
+
+    // Deferred to the end of the update, actors are borrowed meanwhile
+    pub fn restart_level(&self) {
+        self.restart.set(true);
+    }
+
+    pub fn spawn(&self, actor: Box<dyn Actor>) {
+        self.spawns.borrow_mut().push(Rc::new(RefCell::new(actor)));
+    }
+
+    // Actors being updated are mutably borrowed, so they are skipped
+    pub fn find_actor(&self, id: usize) -> Option<Rc<RefCell<Box<dyn Actor>>>> {
+        for actor in self.actors.iter().chain(self.spawns.borrow().iter()) {
+            if let Ok(borrowed) = actor.try_borrow() {
+                if actor_id(borrowed.as_ref()) == id {
+                    return Some(actor.clone());
+                }
+            }
+        }
+        return None;
+    }
+
+    pub fn player(&self) -> Option<Rc<RefCell<Box<dyn Actor>>>> {
+        for actor in self.actors.iter() {
+            if let Ok(borrowed) = actor.try_borrow() {
+                if borrowed.collision_class() == CollisionClass::Player {
+                    return Some(actor.clone());
+                }
+            }
+        }
+        return None;
+    }
+
+    // Palette flash of the player view, none between the levels
+    pub fn palette(&self) -> usize {
+        if self.intermission.is_some() {
+            return 0;
+        }
+        match self.player() {
+            Some(player) => player.borrow().as_player().map(|player| player.palette()).unwrap_or(0),
+            None => 0
+        }
     }
 
     pub fn draw(&mut self, last_frame_time: f64, blending_factor: f64) {
@@ -157,30 +743,88 @@ impl<'wad> Doom<'wad> {
             let render = self.renders[render_id].clone();
             render.borrow_mut().draw(self, last_frame_time, blending_factor);
         }
+        // The indexed frame takes the palette on swap, the RGBA one is blended toward it
+        let palette_id = self.palette();
+        if self.surface.borrow().is_indexed() {
+            if let Some(palette) = self.data_textures.palettes.get(palette_id) {
+                self.surface.borrow_mut().set_palette(palette);
+            }
+        } else {
+            self.palette_shift.apply(&mut self.surface.borrow_mut(), palette_id);
+        }
+        if let Some(wipe) = &self.wipe {
+            wipe.draw(&mut self.surface.borrow_mut());
+        }
         self.surface.borrow_mut().swap().unwrap();
     }
 
-    pub fn control(&mut self, event: &Event<()>, last_frame_time: f64, blending_factor: f64) -> bool {
+    pub fn control(&mut self, event: &Event<()>, _last_frame_time: f64, _blending_factor: f64) -> bool {
         // Input
         if self.input.update(&event) {
             // Close events
-            if self.input.key_pressed(KeyCode::Escape) || self.input.close_requested() {
-                return false;
+            if self.input.close_requested() {
+                return self.quit();
+            }
+            // No input during the screen wipe
+            if self.wipe.is_some() {
+                self.input_source.release();
+                return true;
+            }
+            // The console eats the input while it is open
+            if let Some(command) = self.console.control(&self.input, &self.configure.controls) {
+                self.execute(command);
+            }
+            if self.console.is_active() {
+                self.input_source.release();
+                return true;
+            }
+            if let Some(action) = self.menu.control(&self.input, &self.configure.controls) {
+                if !self.menu_action(action) {
+                    return self.quit();
+                }
+            }
+            // Rebuild the renders when the screen size changes
+            if self.menu.settings().screen_size != self.screen_size {
+                self.screen_size = self.menu.settings().screen_size;
+                self.renders = Doom::create_renders(&self.map, &self.data_textures, &self.configure, self.screen_size);
+            }
+            // The player keys are released while something else takes them
+            if self.menu.is_active() {
+                self.input_source.release();
+                return true;
+            } else if let Some(intermission) = &mut self.intermission {
+                self.input_source.release();
+                intermission.control(&self.input, &self.configure.controls);
             } else {
-                for actor in &mut self.actors {
-                    actor.borrow_mut().control(&self.input, last_frame_time, blending_factor);
+                let taken = self.automap.control(&self.input, &self.configure.controls);
+                self.input_source.set_mouse_scale(self.menu.settings().mouse_scale());
+                self.input_source.gather(&self.input, taken);
+                if let Some(player) = self.player() {
+                    if let Some(player) = player.borrow_mut().as_player_mut() {
+                        player.check_cheats(&self.input);
+                    }
                 }
             }
         }
         return true;
     }
 
-    fn create_actors(map: &Rc<Map<'wad>>, configure: &Configure) -> Vec<Rc<RefCell<Box<dyn Actor>>>> {
+    fn create_actors(map: &Rc<Map<'wad>>, configure: &Configure, skill: Skill) -> Vec<Rc<RefCell<Box<dyn Actor>>>> {
         let mut actors = vec![];
-        for thing in &map.things {
+        for (thing_id, thing) in map.things.iter().enumerate() {
+            if thing.type_id != 1 && !skill.spawns(thing) {
+                continue;
+            }
+            let thing_type = ThingType::try_from(thing.type_id).unwrap_or(ThingType::Unknown);
             match thing.type_id {
                 1 => actors.push(Rc::new(RefCell::new(Player::new(&thing, &configure)))),
-                _ => (),
+                _ => {
+                    if CollisionClass::new(thing_type) == CollisionClass::Monster {
+                        actors.push(Rc::new(RefCell::new(Monster::new(thing_id, &thing))));
+                    } else if thing_type.is_pickup() {
+                        actors.push(Rc::new(RefCell::new(Pickup::new(thing_id, &thing))));
+                    }
+                },
             }
         }
         return actors;
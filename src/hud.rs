@@ -0,0 +1,341 @@
+#![allow(dead_code)]
+use std::rc::Rc;
+// Engine
+use crate::actors::{Actor, Player};
+use crate::data_textures::{DataTextures, Graphic};
+use crate::doom::Doom;
+use crate::inventory::{AmmoType, KeyType, PowerType, WeaponType};
+use crate::math::Vector2;
+use crate::random::Random;
+use crate::render::Render;
+use crate::shape::Size;
+use crate::time::tics;
+use crate::weapons::WeaponMode;
+use crate::window::DoomSurface;
+
+mod consts {
+    // Status bar layout, in 320x200 screen units as st_stuff.c
+    pub const ST_Y: i32 = 168;
+    pub const ST_HEIGHT: i32 = 32;
+    pub const ST_AMMO: (i32, i32) = (44, 171);
+    pub const ST_HEALTH: (i32, i32) = (90, 171);
+    pub const ST_ARMS_BACKGROUND: (i32, i32) = (104, 168);
+    pub const ST_ARMS: (i32, i32) = (111, 172);
+    pub const ST_ARMS_SPACE: (i32, i32) = (12, 10);
+    pub const ST_FACE: (i32, i32) = (143, 168);
+    pub const ST_ARMOR: (i32, i32) = (221, 171);
+    pub const ST_KEYS: (i32, [i32; 3]) = (239, [171, 181, 191]);
+    pub const ST_AMMO_COUNT_X: i32 = 288;
+    pub const ST_MAX_AMMO_X: i32 = 314;
+    // Clip, shell, cell, rocket
+    pub const ST_AMMO_Y: [i32; 4] = [173, 179, 191, 185];
+    // Face
+    pub const FACE_PAIN_LEVELS: i32 = 5;
+    pub const FACE_STRAIGHT_TICS: u32 = 17;
+    pub const FACE_EVIL_GRIN_TICS: u32 = 70;
+    pub const FACE_RAMPAGE_DELAY: i32 = 70;
+    pub const FACE_MUCH_PAIN: i32 = 20;
+}
+
+// Draw a WAD graphic placed in 320x200 units over the area, as V_DrawPatch
+pub fn draw_graphic(surface: &mut DoomSurface, data_textures: &DataTextures, name: &str, x: i32, y: i32, size: &Vector2<i32>, offset: &Vector2<i32>) -> i32 {
+    match data_textures.graphic(name) {
+        Some(graphic) => draw_patch(surface, &graphic, x, y, size, offset),
+        None => 0
+    }
+}
+
+// Returns the patch width, in 320x200 units
+pub fn draw_patch(surface: &mut DoomSurface, graphic: &Graphic, x: i32, y: i32, size: &Vector2<i32>, offset: &Vector2<i32>) -> i32 {
+    let texture = &graphic.texture;
+    let scale = Vector2::new(size.width() as f32 / 320.0, size.height() as f32 / 200.0);
+    let x1 = (x - graphic.offset.x as i32) as f32 * scale.x;
+    let y1 = (y - graphic.offset.y as i32) as f32 * scale.y;
+    let x2 = x1 + texture.size.width() as f32 * scale.x;
+    let y2 = y1 + texture.size.height() as f32 * scale.y;
+    for sy in (y1.max(0.0) as i32)..(y2.ceil() as i32).min(size.height()) {
+        let v = (((sy as f32 - y1) / scale.y) as u16).min(texture.size.height() - 1);
+        for sx in (x1.max(0.0) as i32)..(x2.ceil() as i32).min(size.width()) {
+            let u = (((sx as f32 - x1) / scale.x) as u16).min(texture.size.width() - 1);
+            let position = Vector2::new((sx + offset.x) as usize, (sy + offset.y) as usize);
+            if surface.is_indexed() {
+                if texture.is_opaque(u, v) {
+                    surface.draw_index_lt(&position, texture.index(u, v));
+                }
+                continue;
+            }
+            let color = texture.get(u, v);
+            if color[3] != 0 {
+                surface.draw_lt(&position, color);
+            }
+        }
+    }
+    return texture.size.width() as i32;
+}
+
+// Right aligned number, as STlib_drawNum
+fn draw_number(surface: &mut DoomSurface, data_textures: &DataTextures, font: &str, value: i32, digits: u32, x: i32, y: i32, size: &Vector2<i32>, offset: &Vector2<i32>) {
+    let width = match data_textures.graphic(&format!("{}0", font)) {
+        Some(graphic) => graphic.texture.size.width() as i32,
+        None => return
+    };
+    let mut value = value.max(0).min(10i32.pow(digits) - 1);
+    let mut x = x;
+    loop {
+        x -= width;
+        draw_graphic(surface, data_textures, &format!("{}{}", font, value % 10), x, y, size, offset);
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+fn ready_ammo(player: &Player) -> Option<i32> {
+    player.weapon().weapon().ammo().map(|ammo| player.inventory().ammo(ammo))
+}
+
+// The Doomguy face, as ST_updateFaceWidget
+pub struct StatusFace {
+    name: String,
+    priority: i32,
+    count: u32,
+    last_attack_down: i32,
+    old_health: i32,
+    old_weapons: [bool; WeaponType::COUNT],
+    random: Random,
+    think_time: f32,
+}
+
+impl StatusFace {
+    pub fn new() -> Self {
+        StatusFace {
+            name: String::from("STFST00"),
+            priority: 0,
+            count: 0,
+            last_attack_down: -1,
+            old_health: -1,
+            old_weapons: [false; WeaponType::COUNT],
+            random: Random::new(),
+            think_time: 0.0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn pain_level(player: &Player) -> i32 {
+        let health = player.health().clamp(0, 100);
+        return (consts::FACE_PAIN_LEVELS - 1).min((100 - health) * consts::FACE_PAIN_LEVELS / 101);
+    }
+
+    pub fn update(&mut self, player: &Player, last_frame_time: f32) {
+        self.think_time += last_frame_time;
+        while self.think_time >= tics(1) {
+            self.think_time -= tics(1);
+            self.tic(player);
+        }
+    }
+
+    fn tic(&mut self, player: &Player) {
+        let level = StatusFace::pain_level(player);
+        let weapons = WeaponType::ALL.map(|weapon| player.inventory().has_weapon(weapon));
+        if self.old_health < 0 {
+            self.old_health = player.health();
+            self.old_weapons = weapons;
+        }
+        // Dead
+        if self.priority < 10 && player.health() <= 0 {
+            self.priority = 9;
+            self.name = String::from("STFDEAD0");
+            self.count = 1;
+        }
+        // Picked up a weapon
+        if self.priority < 9 && weapons.iter().zip(self.old_weapons.iter()).any(|(new, old)| *new && !*old) {
+            self.priority = 8;
+            self.name = format!("STFEVL{}", level);
+            self.count = consts::FACE_EVIL_GRIN_TICS;
+        }
+        self.old_weapons = weapons;
+        // Being hurt, the attacker is not tracked so there is no turning
+        if self.priority < 7 && player.damage_count() > 0 {
+            self.priority = 6;
+            self.name = if self.old_health - player.health() > consts::FACE_MUCH_PAIN {
+                format!("STFOUCH{}", level)
+            } else {
+                format!("STFKILL{}", level)
+            };
+            self.count = 1;
+        }
+        // Rapid firing
+        if self.priority < 6 {
+            if player.weapon().mode() == WeaponMode::Attack {
+                if self.last_attack_down == -1 {
+                    self.last_attack_down = consts::FACE_RAMPAGE_DELAY;
+                } else {
+                    self.last_attack_down -= 1;
+                    if self.last_attack_down == 0 {
+                        self.priority = 5;
+                        self.name = format!("STFKILL{}", level);
+                        self.count = 1;
+                        self.last_attack_down = 1;
+                    }
+                }
+            } else {
+                self.last_attack_down = -1;
+            }
+        }
+        // Invulnerable
+        if self.priority < 5 && (player.god_mode() || player.inventory().has_power(PowerType::Invulnerability)) {
+            self.priority = 4;
+            self.name = String::from("STFGOD0");
+            self.count = 1;
+        }
+        // Look around
+        if self.count == 0 {
+            self.name = format!("STFST{}{}", level, self.random.next() % 3);
+            self.count = consts::FACE_STRAIGHT_TICS;
+            self.priority = 0;
+        }
+        self.count -= 1;
+        self.old_health = player.health();
+    }
+}
+
+// Status bar at the bottom of the screen
+pub struct RenderStatusBar<'wad> {
+    data_textures: Rc<DataTextures<'wad>>,
+    size: Vector2<i32>,
+    offset: Vector2<i32>,
+    face: StatusFace,
+}
+
+impl<'wad> RenderStatusBar<'wad> {
+    pub fn new(data_textures: &Rc<DataTextures<'wad>>, size: Vector2<i32>, offset: Vector2<i32>) -> Self {
+        RenderStatusBar {
+            data_textures: data_textures.clone(),
+            size: size,
+            offset: offset,
+            face: StatusFace::new(),
+        }
+    }
+
+    fn draw_status_bar(&self, surface: &mut DoomSurface, player: &Player) {
+        // The area only covers the bar, so map it as the bottom of a 320x200 screen
+        let size = Vector2::new(self.size.width(), self.size.height() * 200 / consts::ST_HEIGHT);
+        let offset = Vector2::new(self.offset.x, self.offset.y - self.size.height() * consts::ST_Y / consts::ST_HEIGHT);
+        let data_textures = &self.data_textures;
+        let draw = |surface: &mut DoomSurface, name: &str, x: i32, y: i32| {
+            draw_graphic(surface, data_textures, name, x, y, &size, &offset);
+        };
+        // Background
+        draw(surface, "STBAR", 0, consts::ST_Y);
+        draw(surface, "STARMS", consts::ST_ARMS_BACKGROUND.0, consts::ST_ARMS_BACKGROUND.1);
+        // Ready weapon ammo
+        if let Some(ammo) = ready_ammo(player) {
+            draw_number(surface, data_textures, "STTNUM", ammo, 3, consts::ST_AMMO.0, consts::ST_AMMO.1, &size, &offset);
+        }
+        // Health and armor
+        draw_number(surface, data_textures, "STTNUM", player.health(), 3, consts::ST_HEALTH.0, consts::ST_HEALTH.1, &size, &offset);
+        draw(surface, "STTPRCNT", consts::ST_HEALTH.0, consts::ST_HEALTH.1);
+        draw_number(surface, data_textures, "STTNUM", player.armor(), 3, consts::ST_ARMOR.0, consts::ST_ARMOR.1, &size, &offset);
+        draw(surface, "STTPRCNT", consts::ST_ARMOR.0, consts::ST_ARMOR.1);
+        // Arms, slots 2 to 7
+        for slot in 2..=7 {
+            let owned = WeaponType::ALL.iter().any(|weapon| weapon.slot() == slot && player.inventory().has_weapon(*weapon));
+            let index = slot as i32 - 2;
+            let x = consts::ST_ARMS.0 + (index % 3) * consts::ST_ARMS_SPACE.0;
+            let y = consts::ST_ARMS.1 + (index / 3) * consts::ST_ARMS_SPACE.1;
+            draw(surface, &format!("{}{}", if owned { "STYSNUM" } else { "STGNUM" }, slot), x, y);
+        }
+        // Face
+        draw(surface, self.face.name(), consts::ST_FACE.0, consts::ST_FACE.1);
+        // Keys, the skull wins over the card
+        for (row, (card, skull)) in [(KeyType::BlueCard, KeyType::BlueSkull),
+                                     (KeyType::YellowCard, KeyType::YellowSkull),
+                                     (KeyType::RedCard, KeyType::RedSkull)].iter().enumerate() {
+            let key = if player.inventory().has_key(*skull) {
+                Some(skull)
+            } else if player.inventory().has_key(*card) {
+                Some(card)
+            } else {
+                None
+            };
+            if let Some(key) = key {
+                draw(surface, &format!("STKEYS{}", key.index()), consts::ST_KEYS.0, consts::ST_KEYS.1[row]);
+            }
+        }
+        // Ammo counts
+        for ammo in AmmoType::ALL {
+            let y = consts::ST_AMMO_Y[ammo.index()];
+            draw_number(surface, data_textures, "STYSNUM", player.inventory().ammo(ammo), 3, consts::ST_AMMO_COUNT_X, y, &size, &offset);
+            draw_number(surface, data_textures, "STYSNUM", player.inventory().max_ammo(ammo), 3, consts::ST_MAX_AMMO_X, y, &size, &offset);
+        }
+    }
+}
+
+impl Render for RenderStatusBar<'_> {
+    fn draw<'wad>(&mut self, doom: &mut Doom<'wad>, last_frame_time: f64, _blending_factor: f64) {
+        if let Some(actor) = doom.player() {
+            let actor = actor.borrow();
+            if let Some(player) = actor.as_player() {
+                self.face.update(player, last_frame_time as f32);
+                self.draw_status_bar(&mut doom.surface.borrow_mut(), player);
+            }
+        }
+    }
+}
+
+// Minimal fullscreen HUD: health, armor, ammo and keys in the corners
+pub struct RenderHud<'wad> {
+    data_textures: Rc<DataTextures<'wad>>,
+    size: Vector2<i32>,
+    offset: Vector2<i32>,
+}
+
+impl<'wad> RenderHud<'wad> {
+    const HEALTH: (i32, i32) = (50, 178);
+    const ARMOR: (i32, i32) = (120, 178);
+    const AMMO: (i32, i32) = (312, 178);
+    const KEYS: (i32, i32) = (240, 182);
+
+    pub fn new(data_textures: &Rc<DataTextures<'wad>>, size: Vector2<i32>, offset: Vector2<i32>) -> Self {
+        RenderHud {
+            data_textures: data_textures.clone(),
+            size: size,
+            offset: offset,
+        }
+    }
+
+    fn draw_hud(&self, surface: &mut DoomSurface, player: &Player) {
+        let data_textures = &self.data_textures;
+        let (size, offset) = (&self.size, &self.offset);
+        draw_number(surface, data_textures, "STTNUM", player.health(), 3, RenderHud::HEALTH.0, RenderHud::HEALTH.1, size, offset);
+        draw_graphic(surface, data_textures, "STTPRCNT", RenderHud::HEALTH.0, RenderHud::HEALTH.1, size, offset);
+        if player.armor() > 0 {
+            draw_number(surface, data_textures, "STTNUM", player.armor(), 3, RenderHud::ARMOR.0, RenderHud::ARMOR.1, size, offset);
+            draw_graphic(surface, data_textures, "STTPRCNT", RenderHud::ARMOR.0, RenderHud::ARMOR.1, size, offset);
+        }
+        if let Some(ammo) = ready_ammo(player) {
+            draw_number(surface, data_textures, "STTNUM", ammo, 3, RenderHud::AMMO.0, RenderHud::AMMO.1, size, offset);
+        }
+        let mut x = RenderHud::KEYS.0;
+        for key in KeyType::ALL {
+            if player.inventory().has_key(key) {
+                x += draw_graphic(surface, data_textures, &format!("STKEYS{}", key.index()), x, RenderHud::KEYS.1, size, offset) + 2;
+            }
+        }
+    }
+}
+
+impl Render for RenderHud<'_> {
+    fn draw<'wad>(&mut self, doom: &mut Doom<'wad>, _last_frame_time: f64, _blending_factor: f64) {
+        if let Some(actor) = doom.player() {
+            let actor = actor.borrow();
+            if let Some(player) = actor.as_player() {
+                self.draw_hud(&mut doom.surface.borrow_mut(), player);
+            }
+        }
+    }
+}
@@ -0,0 +1,376 @@
+#![allow(dead_code)]
+use std::rc::Rc;
+// Engine
+use crate::data_textures::DataTextures;
+use crate::doom::Doom;
+use crate::hud::draw_graphic;
+use crate::level::{LevelStats, MapSlot};
+use crate::math::Vector2;
+use crate::render::Render;
+use crate::shape::Size;
+use crate::text::Font;
+use crate::time::{tics, TIC_RATE};
+use crate::controls::{Action, Controls};
+use crate::window::DoomSurface;
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+mod consts {
+    // As wi_stuff.c
+    pub const TITLE_Y: i32 = 2;
+    pub const STATS_X: i32 = 50;
+    pub const STATS_Y: i32 = 50;
+    pub const TIME_X: i32 = 16;
+    pub const TIME_Y: i32 = 200 - 32;
+    pub const PERCENT_STEP: i32 = 2;
+    pub const TIME_STEP: i32 = 3;
+    pub const SHOW_NEXT_LOCATION_TICS: u32 = 4 * super::TIC_RATE as u32;
+    // As f_finale.c
+    pub const TEXT_X: i32 = 10;
+    pub const TEXT_Y: i32 = 10;
+    pub const TEXT_SPEED: u32 = 3;
+    pub const TEXT_DELAY: u32 = 10;
+    pub const E1TEXT: &str = "Once you beat the big badasses and\nclean out the moon base you're supposed\nto win, aren't you? Aren't you? Where's\nyour fat reward and ticket home? What\nthe hell is this? It's not supposed to\nend this way!\n\nIt stinks like rotten meat, but looks\nlike the lost Deimos base.  Looks like\nyou're stuck on The Shores of Hell.\nThe only way out is through.\n\nTo continue the DOOM experience, play\nThe Shores of Hell and its amazing\nsequel, Inferno!\n";
+    pub const E2TEXT: &str = "You've done it! The hideous cyber-\ndemon lord that ruled the lost Deimos\nmoon base has been slain and you\nare triumphant! But ... where are\nyou? You clamber to the edge of the\nmoon and look down to see the awful\ntruth.\n\nDeimos floats above Hell itself!\nYou've never heard of anyone escaping\nfrom Hell, but you'll make the bastards\nsorry they ever heard of you! Quickly,\nyou rappel down to  the surface of\nHell.\n\nNow, it's on to the final chapter of\nDOOM! -- Inferno.";
+    pub const E3TEXT: &str = "The loathsome spiderdemon that\nmasterminded the invasion of the moon\nbases and caused so much death has had\nits ass kicked for all time.\n\nA hidden doorway opens and you enter.\nYou've proven too tough for Hell to\ncontain, and now Hell at last plays\nfair -- for you emerge from the door\nto see the green fields of Earth!\nHome at last.\n\nYou wonder what's been happening on\nEarth while you were battling evil\nunleashed. It's good that no Hell-\nspawn could have come through that\ndoor with you ...";
+    pub const E4TEXT: &str = "the spider mastermind must have sent forth\nits legions of hellspawn before your\nfinal confrontation with that terrible\nbeast from hell.  but you stepped forward\nand brought forth eternal damnation and\nsuffering upon the horde as a true hero\nwould in the face of something so evil.\n\nbesides, someone was gonna pay for what\nhappened to daisy, your pet rabbit.\n\nbut now, you see spread before you more\npotential pain and gibbitude as a nation\nof demons run amok among our cities.\n\nnext stop, hell on earth!";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntermissionState {
+    // Counting kills, items, secrets and time
+    Stats,
+    // "Entering" the next map
+    NextLocation,
+    // Episode end text
+    Finale,
+    Done
+}
+
+pub struct Intermission {
+    state: IntermissionState,
+    finished: MapSlot,
+    next: Option<MapSlot>,
+    stats: LevelStats,
+    // Counters shown, -1 while not started
+    count_kills: i32,
+    count_items: i32,
+    count_secrets: i32,
+    count_time: i32,
+    count_par: i32,
+    // Counter being updated, 0 kills, 1 items, 2 secrets, 3 time, 4 all shown
+    stage: usize,
+    pause: u32,
+    // Tics in the current state
+    state_tics: u32,
+    think_time: f32,
+    accelerate: bool,
+}
+
+impl Intermission {
+    pub fn new(finished: MapSlot, next: Option<MapSlot>, stats: LevelStats) -> Self {
+        Intermission {
+            state: IntermissionState::Stats,
+            finished: finished,
+            next: next,
+            stats: stats,
+            count_kills: -1,
+            count_items: -1,
+            count_secrets: -1,
+            count_time: -1,
+            count_par: -1,
+            stage: 0,
+            pause: TIC_RATE as u32,
+            state_tics: 0,
+            think_time: 0.0,
+            accelerate: false,
+        }
+    }
+
+    pub fn state(&self) -> IntermissionState {
+        self.state
+    }
+
+    pub fn next(&self) -> Option<MapSlot> {
+        self.next
+    }
+
+    fn percent(value: i32, total: i32) -> i32 {
+        value * 100 / total.max(1)
+    }
+
+    fn finale_text(&self) -> Option<&'static str> {
+        match self.finished {
+            MapSlot::Episode { episode: 1, .. } => Some(consts::E1TEXT),
+            MapSlot::Episode { episode: 2, .. } => Some(consts::E2TEXT),
+            MapSlot::Episode { episode: 3, .. } => Some(consts::E3TEXT),
+            MapSlot::Episode { episode: 4, .. } => Some(consts::E4TEXT),
+            _ => None
+        }
+    }
+
+    fn finale_flat(&self) -> &'static [u8; 8] {
+        match self.finished {
+            MapSlot::Episode { episode: 2, .. } => b"SFLR6_1\0",
+            MapSlot::Episode { episode: 3, .. } => b"MFLR8_4\0",
+            MapSlot::Episode { episode: 4, .. } => b"MFLR8_3\0",
+            _ => b"FLOOR4_8"
+        }
+    }
+
+    // Fire, use or enter skip the counting, then move on
+    pub fn control(&mut self, input: &WinitInputHelper, controls: &Controls) {
+        if input.key_pressed(KeyCode::Enter)
+        || controls.pressed(input, Action::Use)
+        || controls.pressed(input, Action::Fire) {
+            self.accelerate = true;
+        }
+    }
+
+    fn set_state(&mut self, state: IntermissionState) {
+        self.state = state;
+        self.state_tics = 0;
+    }
+
+    fn show_all(&mut self) {
+        self.count_kills = Intermission::percent(self.stats.kills, self.stats.total_kills);
+        self.count_items = Intermission::percent(self.stats.items, self.stats.total_items);
+        self.count_secrets = Intermission::percent(self.stats.secrets, self.stats.total_secrets);
+        self.count_time = self.stats.time as i32;
+        self.count_par = self.finished.par_time().map(|par| par as i32).unwrap_or(-1);
+        self.stage = 4;
+    }
+
+    // As WI_updateStats, one counter at a time with a pause in between
+    fn update_stats(&mut self) {
+        if self.accelerate {
+            self.accelerate = false;
+            if self.stage < 4 {
+                self.show_all();
+            } else if self.next.is_some() {
+                self.set_state(IntermissionState::NextLocation);
+            } else if self.finale_text().is_some() {
+                self.set_state(IntermissionState::Finale);
+            } else {
+                self.set_state(IntermissionState::Done);
+            }
+            return;
+        }
+        if self.pause > 0 {
+            self.pause -= 1;
+            return;
+        }
+        let step = |count: &mut i32, target: i32, step: i32| -> bool {
+            *count = (*count + step).min(target);
+            return *count >= target;
+        };
+        let finished = match self.stage {
+            0 => step(&mut self.count_kills, Intermission::percent(self.stats.kills, self.stats.total_kills), consts::PERCENT_STEP),
+            1 => step(&mut self.count_items, Intermission::percent(self.stats.items, self.stats.total_items), consts::PERCENT_STEP),
+            2 => step(&mut self.count_secrets, Intermission::percent(self.stats.secrets, self.stats.total_secrets), consts::PERCENT_STEP),
+            3 => {
+                let par = self.finished.par_time().map(|par| par as i32).unwrap_or(-1);
+                let time = step(&mut self.count_time, self.stats.time as i32, consts::TIME_STEP);
+                let par = par < 0 || step(&mut self.count_par, par, consts::TIME_STEP);
+                time && par
+            },
+            _ => false
+        };
+        if finished {
+            self.stage += 1;
+            self.pause = TIC_RATE as u32;
+        }
+    }
+
+    fn tic(&mut self) {
+        self.state_tics += 1;
+        match self.state {
+            IntermissionState::Stats => self.update_stats(),
+            IntermissionState::NextLocation => {
+                if self.accelerate || self.state_tics >= consts::SHOW_NEXT_LOCATION_TICS {
+                    self.accelerate = false;
+                    self.set_state(IntermissionState::Done);
+                }
+            },
+            IntermissionState::Finale => {
+                // The first press shows the whole text
+                if self.accelerate {
+                    self.accelerate = false;
+                    let length = self.finale_text().map(|text| text.len() as u32).unwrap_or(0);
+                    let end = consts::TEXT_DELAY + length * consts::TEXT_SPEED;
+                    if self.state_tics < end {
+                        self.state_tics = end;
+                    } else {
+                        self.set_state(IntermissionState::Done);
+                    }
+                }
+            },
+            IntermissionState::Done => ()
+        }
+    }
+
+    pub fn update(&mut self, last_frame_time: f32) {
+        self.think_time += last_frame_time;
+        while self.think_time >= tics(1) {
+            self.think_time -= tics(1);
+            self.tic();
+        }
+    }
+}
+
+// Intermission and finale screens, in 320x200 units
+pub struct RenderIntermission<'wad> {
+    data_textures: Rc<DataTextures<'wad>>,
+    font: Font<'wad>,
+    size: Vector2<i32>,
+    offset: Vector2<i32>,
+}
+
+impl<'wad> RenderIntermission<'wad> {
+    pub fn new(data_textures: &Rc<DataTextures<'wad>>, size: Vector2<i32>, offset: Vector2<i32>) -> Self {
+        RenderIntermission {
+            data_textures: data_textures.clone(),
+            font: Font::new(data_textures),
+            size: size,
+            offset: offset,
+        }
+    }
+
+    fn draw_patch(&self, surface: &mut DoomSurface, name: &str, x: i32, y: i32) -> i32 {
+        draw_graphic(surface, &self.data_textures, name, x, y, &self.size, &self.offset)
+    }
+
+    fn patch_size(&self, name: &str) -> Vector2<i32> {
+        self.data_textures.graphic(name)
+            .map(|graphic| Vector2::new(graphic.texture.size.width() as i32, graphic.texture.size.height() as i32))
+            .unwrap_or(Vector2::new(0, 0))
+    }
+
+    fn draw_centered(&self, surface: &mut DoomSurface, name: &str, y: i32) {
+        self.draw_patch(surface, name, (320 - self.patch_size(name).x) / 2, y);
+    }
+
+    fn background(slot: &MapSlot) -> String {
+        match slot {
+            MapSlot::Episode { episode, .. } if *episode <= 3 => format!("WIMAP{}", episode - 1),
+            _ => String::from("INTERPIC")
+        }
+    }
+
+    fn level_name(slot: &MapSlot) -> String {
+        match slot {
+            MapSlot::Episode { episode, map } => format!("WILV{}{}", episode - 1, map - 1),
+            MapSlot::Commercial { map } => format!("CWILV{:02}", map - 1),
+        }
+    }
+
+    // Right aligned, with leading zeros up to the digits, returns the left x
+    fn draw_number(&self, surface: &mut DoomSurface, x: i32, y: i32, value: i32, digits: u32) -> i32 {
+        let width = self.patch_size("WINUM0").x;
+        let mut value = value.max(0);
+        let mut x = x;
+        let mut drawn = 0;
+        while value > 0 || drawn < digits.max(1) {
+            x -= width;
+            self.draw_patch(surface, &format!("WINUM{}", value % 10), x, y);
+            value /= 10;
+            drawn += 1;
+        }
+        return x;
+    }
+
+    fn draw_percent(&self, surface: &mut DoomSurface, x: i32, y: i32, value: i32) {
+        if value < 0 {
+            return;
+        }
+        self.draw_patch(surface, "WIPCNT", x, y);
+        self.draw_number(surface, x, y, value, 1);
+    }
+
+    // As WI_drawTime, minutes and seconds
+    fn draw_time(&self, surface: &mut DoomSurface, x: i32, y: i32, seconds: i32) {
+        if seconds < 0 {
+            return;
+        }
+        let x = self.draw_number(surface, x, y, seconds % 60, 2);
+        let x = x - self.patch_size("WICOLON").x;
+        self.draw_patch(surface, "WICOLON", x, y);
+        self.draw_number(surface, x, y, seconds / 60, 1);
+    }
+
+    fn draw_stats(&self, surface: &mut DoomSurface, intermission: &Intermission) {
+        // Finished
+        let title = RenderIntermission::level_name(&intermission.finished);
+        self.draw_centered(surface, &title, consts::TITLE_Y);
+        self.draw_centered(surface, "WIF", consts::TITLE_Y + self.patch_size(&title).y * 5 / 4);
+        // Counters
+        let line_height = self.patch_size("WINUM0").y * 3 / 2;
+        self.draw_patch(surface, "WIOSTK", consts::STATS_X, consts::STATS_Y);
+        self.draw_percent(surface, 320 - consts::STATS_X, consts::STATS_Y, intermission.count_kills);
+        self.draw_patch(surface, "WIOSTI", consts::STATS_X, consts::STATS_Y + line_height);
+        self.draw_percent(surface, 320 - consts::STATS_X, consts::STATS_Y + line_height, intermission.count_items);
+        self.draw_patch(surface, "WISCRT2", consts::STATS_X, consts::STATS_Y + 2 * line_height);
+        self.draw_percent(surface, 320 - consts::STATS_X, consts::STATS_Y + 2 * line_height, intermission.count_secrets);
+        self.draw_patch(surface, "WITIME", consts::TIME_X, consts::TIME_Y);
+        self.draw_time(surface, 320 / 2 - consts::TIME_X, consts::TIME_Y, intermission.count_time);
+        if intermission.finished.par_time().is_some() {
+            self.draw_patch(surface, "WIPAR", 320 / 2 + consts::TIME_X, consts::TIME_Y);
+            self.draw_time(surface, 320 - consts::TIME_X, consts::TIME_Y, intermission.count_par);
+        }
+    }
+
+    fn draw_next_location(&self, surface: &mut DoomSurface, intermission: &Intermission) {
+        if let Some(next) = &intermission.next {
+            self.draw_centered(surface, "WIENTER", consts::TITLE_Y);
+            let y = consts::TITLE_Y + self.patch_size("WIENTER").y * 5 / 4;
+            self.draw_centered(surface, &RenderIntermission::level_name(next), y);
+        }
+    }
+
+    // As F_TextWrite, the flat tiled behind the text typed a char every few tics
+    fn draw_finale(&self, surface: &mut DoomSurface, intermission: &Intermission) {
+        if let Some(flat) = self.data_textures.flat(intermission.finale_flat()) {
+            for y in 0..self.size.height() {
+                let v = ((y * 200 / self.size.height()) % flat.size.height() as i32) as u16;
+                for x in 0..self.size.width() {
+                    let u = ((x * 320 / self.size.width()) % flat.size.width() as i32) as u16;
+                    let position = Vector2::new((x + self.offset.x) as usize, (y + self.offset.y) as usize);
+                    if surface.is_indexed() {
+                        surface.draw_index_lt(&position, flat.index(u, v));
+                        continue;
+                    }
+                    let color = flat.get(u, v);
+                    surface.draw_lt(&position, &[color[0], color[1], color[2], 0xFF]);
+                }
+            }
+        }
+        if let Some(text) = intermission.finale_text() {
+            let count = (intermission.state_tics.saturating_sub(consts::TEXT_DELAY) / consts::TEXT_SPEED) as usize;
+            let shown: String = text.chars().take(count).collect();
+            self.font.draw(surface, &shown, consts::TEXT_X, consts::TEXT_Y, &self.size, &self.offset);
+        }
+    }
+}
+
+impl Render for RenderIntermission<'_> {
+    fn draw<'wad>(&mut self, doom: &mut Doom<'wad>, _last_frame_time: f64, _blending_factor: f64) {
+        let intermission = match &doom.intermission {
+            Some(intermission) => intermission,
+            None => return
+        };
+        let surface = doom.surface.clone();
+        let surface = &mut surface.borrow_mut();
+        match intermission.state {
+            IntermissionState::Stats | IntermissionState::NextLocation => {
+                self.draw_patch(surface, &RenderIntermission::background(&intermission.finished), 0, 0);
+                if intermission.state == IntermissionState::Stats {
+                    self.draw_stats(surface, intermission);
+                } else {
+                    self.draw_next_location(surface, intermission);
+                }
+            },
+            IntermissionState::Finale => self.draw_finale(surface, intermission),
+            IntermissionState::Done => ()
+        }
+    }
+}
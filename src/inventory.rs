@@ -0,0 +1,372 @@
+#![allow(dead_code)]
+// Engine
+use crate::time::{tics, TIC_RATE};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AmmoType {
+    Clip,
+    Shell,
+    Cell,
+    Misl
+}
+
+impl AmmoType {
+    pub const COUNT: usize = 4;
+    pub const ALL: [AmmoType; AmmoType::COUNT] = [AmmoType::Clip, AmmoType::Shell, AmmoType::Cell, AmmoType::Misl];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    // Without backpack
+    pub fn max_ammo(&self) -> i32 {
+        match self {
+            AmmoType::Clip => 200,
+            AmmoType::Shell => 50,
+            AmmoType::Cell => 300,
+            AmmoType::Misl => 50,
+        }
+    }
+
+    // Ammo in a single clip/shell/cell/rocket pickup
+    pub fn clip_ammo(&self) -> i32 {
+        match self {
+            AmmoType::Clip => 10,
+            AmmoType::Shell => 4,
+            AmmoType::Cell => 20,
+            AmmoType::Misl => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WeaponType {
+    Fist,
+    Pistol,
+    Shotgun,
+    Chaingun,
+    RocketLauncher,
+    PlasmaRifle,
+    BFG,
+    Chainsaw,
+    SuperShotgun
+}
+
+impl WeaponType {
+    pub const COUNT: usize = 9;
+    pub const ALL: [WeaponType; WeaponType::COUNT] = [
+        WeaponType::Fist,
+        WeaponType::Pistol,
+        WeaponType::Shotgun,
+        WeaponType::Chaingun,
+        WeaponType::RocketLauncher,
+        WeaponType::PlasmaRifle,
+        WeaponType::BFG,
+        WeaponType::Chainsaw,
+        WeaponType::SuperShotgun
+    ];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn ammo(&self) -> Option<AmmoType> {
+        match self {
+            WeaponType::Fist | WeaponType::Chainsaw => None,
+            WeaponType::Pistol | WeaponType::Chaingun => Some(AmmoType::Clip),
+            WeaponType::Shotgun | WeaponType::SuperShotgun => Some(AmmoType::Shell),
+            WeaponType::RocketLauncher => Some(AmmoType::Misl),
+            WeaponType::PlasmaRifle | WeaponType::BFG => Some(AmmoType::Cell),
+        }
+    }
+
+    pub fn ammo_per_shot(&self) -> i32 {
+        match self {
+            WeaponType::Fist | WeaponType::Chainsaw => 0,
+            WeaponType::SuperShotgun => 2,
+            WeaponType::BFG => 40,
+            _ => 1
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerType {
+    Invulnerability,
+    Strength,
+    Invisibility,
+    IronFeet,
+    AllMap,
+    Infrared
+}
+
+impl PowerType {
+    pub const COUNT: usize = 6;
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    // Seconds, None for the powers lasting the whole level
+    pub fn duration(&self) -> Option<f32> {
+        match self {
+            PowerType::Invulnerability => Some(tics(30 * TIC_RATE as u32)),
+            PowerType::Invisibility => Some(tics(60 * TIC_RATE as u32)),
+            PowerType::IronFeet => Some(tics(60 * TIC_RATE as u32)),
+            PowerType::Infrared => Some(tics(120 * TIC_RATE as u32)),
+            PowerType::Strength | PowerType::AllMap => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyColor {
+    Blue,
+    Yellow,
+    Red
+}
+
+impl KeyColor {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyColor::Blue => "blue",
+            KeyColor::Yellow => "yellow",
+            KeyColor::Red => "red",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyType {
+    BlueCard,
+    YellowCard,
+    RedCard,
+    BlueSkull,
+    YellowSkull,
+    RedSkull
+}
+
+impl KeyType {
+    pub const COUNT: usize = 6;
+    pub const ALL: [KeyType; KeyType::COUNT] = [
+        KeyType::BlueCard,
+        KeyType::YellowCard,
+        KeyType::RedCard,
+        KeyType::BlueSkull,
+        KeyType::YellowSkull,
+        KeyType::RedSkull
+    ];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn color(&self) -> KeyColor {
+        match self {
+            KeyType::BlueCard | KeyType::BlueSkull => KeyColor::Blue,
+            KeyType::YellowCard | KeyType::YellowSkull => KeyColor::Yellow,
+            KeyType::RedCard | KeyType::RedSkull => KeyColor::Red,
+        }
+    }
+
+    pub fn is_skull(&self) -> bool {
+        matches!(self, KeyType::BlueSkull | KeyType::YellowSkull | KeyType::RedSkull)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Inventory {
+    ammo: [i32; AmmoType::COUNT],
+    max_ammo: [i32; AmmoType::COUNT],
+    backpack: bool,
+    weapons: [bool; WeaponType::COUNT],
+    // Weapon to switch to, taken by the weapon system
+    pending_weapon: Option<WeaponType>,
+    // Time left, or time since pickup for the strength
+    powers: [f32; PowerType::COUNT],
+    keys: [bool; KeyType::COUNT],
+}
+
+impl Inventory {
+    // As G_PlayerReborn: fist, pistol and a clip of 50 bullets
+    pub fn new() -> Self {
+        let mut inventory = Inventory {
+            ammo: [0; AmmoType::COUNT],
+            max_ammo: AmmoType::ALL.map(|ammo| ammo.max_ammo()),
+            backpack: false,
+            weapons: [false; WeaponType::COUNT],
+            pending_weapon: None,
+            powers: [0.0; PowerType::COUNT],
+            keys: [false; KeyType::COUNT],
+        };
+        inventory.weapons[WeaponType::Fist.index()] = true;
+        inventory.weapons[WeaponType::Pistol.index()] = true;
+        inventory.ammo[AmmoType::Clip.index()] = 50;
+        return inventory;
+    }
+
+    pub fn ammo(&self, ammo: AmmoType) -> i32 {
+        self.ammo[ammo.index()]
+    }
+
+    pub fn max_ammo(&self, ammo: AmmoType) -> i32 {
+        self.max_ammo[ammo.index()]
+    }
+
+    // Restored by the save games
+    pub fn set_ammo(&mut self, ammo: AmmoType, count: i32) {
+        self.ammo[ammo.index()] = count.clamp(0, self.max_ammo[ammo.index()]);
+    }
+
+    pub fn use_ammo(&mut self, ammo: AmmoType, count: i32) {
+        self.ammo[ammo.index()] = (self.ammo[ammo.index()] - count).max(0);
+    }
+
+    pub fn has_backpack(&self) -> bool {
+        self.backpack
+    }
+
+    pub fn has_weapon(&self, weapon: WeaponType) -> bool {
+        self.weapons[weapon.index()]
+    }
+
+    pub fn set_weapon(&mut self, weapon: WeaponType, owned: bool) {
+        self.weapons[weapon.index()] = owned;
+    }
+
+    pub fn pending_weapon(&self) -> Option<WeaponType> {
+        self.pending_weapon
+    }
+
+    pub fn set_pending_weapon(&mut self, weapon: Option<WeaponType>) {
+        self.pending_weapon = weapon;
+    }
+
+    pub fn has_ammo_for(&self, weapon: WeaponType) -> bool {
+        match weapon.ammo() {
+            Some(ammo) => self.ammo(ammo) >= weapon.ammo_per_shot(),
+            None => true
+        }
+    }
+
+    // Preferred weapon once out of ammo, as in P_CheckAmmo
+    pub fn best_weapon(&self) -> WeaponType {
+        const ORDER: [WeaponType; 8] = [
+            WeaponType::PlasmaRifle,
+            WeaponType::SuperShotgun,
+            WeaponType::Chaingun,
+            WeaponType::Shotgun,
+            WeaponType::Pistol,
+            WeaponType::Chainsaw,
+            WeaponType::RocketLauncher,
+            WeaponType::BFG
+        ];
+        for weapon in ORDER {
+            if self.has_weapon(weapon) && self.has_ammo_for(weapon) {
+                return weapon;
+            }
+        }
+        return WeaponType::Fist;
+    }
+
+    pub fn has_key(&self, key: KeyType) -> bool {
+        self.keys[key.index()]
+    }
+
+    // Doors accept either the card or the skull of the same color
+    pub fn has_key_color(&self, color: KeyColor) -> bool {
+        KeyType::ALL.iter().any(|key| key.color() == color && self.has_key(*key))
+    }
+
+    // As P_GiveCard
+    pub fn give_key(&mut self, key: KeyType) -> bool {
+        if self.keys[key.index()] {
+            return false;
+        }
+        self.keys[key.index()] = true;
+        return true;
+    }
+
+    pub fn power(&self, power: PowerType) -> f32 {
+        self.powers[power.index()]
+    }
+
+    pub fn has_power(&self, power: PowerType) -> bool {
+        self.powers[power.index()] > 0.0
+    }
+
+    // As P_GiveAmmo, count in clips, a zero count gives half a clip
+    pub fn give_ammo(&mut self, ammo: AmmoType, clips: i32) -> bool {
+        let index = ammo.index();
+        if self.ammo[index] == self.max_ammo[index] {
+            return false;
+        }
+        let count = if clips > 0 { clips * ammo.clip_ammo() } else { ammo.clip_ammo() / 2 };
+        self.ammo[index] = (self.ammo[index] + count).min(self.max_ammo[index]);
+        return true;
+    }
+
+    // Doubles the capacity only the first time, always gives a clip of each
+    pub fn give_backpack(&mut self) {
+        if !self.backpack {
+            for ammo in AmmoType::ALL {
+                self.max_ammo[ammo.index()] *= 2;
+            }
+            self.backpack = true;
+        }
+        for ammo in AmmoType::ALL {
+            self.give_ammo(ammo, 1);
+        }
+    }
+
+    // As P_GiveWeapon, dropped weapons carry one clip instead of two
+    pub fn give_weapon(&mut self, weapon: WeaponType, dropped: bool) -> bool {
+        let gave_ammo = match weapon.ammo() {
+            Some(ammo) => self.give_ammo(ammo, if dropped { 1 } else { 2 }),
+            None => false
+        };
+        let gave_weapon = !self.weapons[weapon.index()];
+        if gave_weapon {
+            self.weapons[weapon.index()] = true;
+            self.pending_weapon = Some(weapon);
+        }
+        return gave_weapon || gave_ammo;
+    }
+
+    // As P_GivePower
+    pub fn give_power(&mut self, power: PowerType) -> bool {
+        match power.duration() {
+            Some(duration) => {
+                self.powers[power.index()] = duration;
+                return true;
+            }
+            None => {
+                if power == PowerType::Strength {
+                    self.powers[power.index()] = 1.0;
+                    return true;
+                }
+                if self.has_power(power) {
+                    return false;
+                }
+                self.powers[power.index()] = 1.0;
+                return true;
+            }
+        }
+    }
+
+    // Keys and powers only last a level
+    pub fn finish_level(&mut self) {
+        self.keys = [false; KeyType::COUNT];
+        self.powers = [0.0; PowerType::COUNT];
+    }
+
+    // Timed powers count down, the strength counts up for the fading red
+    pub fn update(&mut self, last_frame_time: f32) {
+        for power in [PowerType::Invulnerability, PowerType::Invisibility, PowerType::IronFeet, PowerType::Infrared] {
+            self.powers[power.index()] = (self.powers[power.index()] - last_frame_time).max(0.0);
+        }
+        if self.has_power(PowerType::Strength) {
+            self.powers[PowerType::Strength.index()] += last_frame_time;
+        }
+    }
+}
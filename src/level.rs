@@ -0,0 +1,192 @@
+#![allow(dead_code)]
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+// Engine
+use crate::actors::Actor;
+use crate::map::Map;
+
+mod consts {
+    // Sector special of the secret areas
+    pub const SECRET_SECTOR: i16 = 9;
+    // Par times in seconds, as pars and cpars in g_game.c
+    pub const PARS: [[u32; 9]; 3] = [
+        [30, 75, 120, 90, 165, 180, 180, 30, 165],
+        [90, 90, 90, 120, 90, 360, 240, 30, 170],
+        [90, 45, 90, 150, 90, 90, 165, 30, 135],
+    ];
+    pub const CPARS: [u32; 32] = [
+        30, 90, 120, 120, 90, 150, 120, 120, 270, 90,
+        210, 150, 150, 150, 210, 150, 420, 150, 210, 150,
+        240, 150, 180, 150, 150, 300, 330, 420, 300, 180,
+        120, 30
+    ];
+}
+
+// Map names are ExMy for Doom and MAPxx for Doom II
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapSlot {
+    Episode { episode: u8, map: u8 },
+    Commercial { map: u8 }
+}
+
+impl MapSlot {
+    pub fn parse(name: &str) -> Option<MapSlot> {
+        let name = name.to_ascii_uppercase();
+        let bytes = name.as_bytes();
+        if bytes.len() == 4 && bytes[0] == b'E' && bytes[2] == b'M' && bytes[1].is_ascii_digit() && bytes[3].is_ascii_digit() {
+            return Some(MapSlot::Episode { episode: bytes[1] - b'0', map: bytes[3] - b'0' });
+        }
+        if let Some(number) = name.strip_prefix("MAP") {
+            return number.parse().ok().map(|map| MapSlot::Commercial { map });
+        }
+        return None;
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            MapSlot::Episode { episode, map } => format!("E{}M{}", episode, map),
+            MapSlot::Commercial { map } => format!("MAP{:02}", map),
+        }
+    }
+
+    // As G_DoCompleted, None at the end of the episode or of the game
+    pub fn next(&self, secret: bool) -> Option<MapSlot> {
+        match *self {
+            MapSlot::Episode { episode, map } => {
+                let next = match map {
+                    8 => return None,
+                    _ if secret => 9,
+                    // Back to the map after the one holding the secret exit
+                    9 => match episode {
+                        1 => 4,
+                        2 => 6,
+                        3 => 7,
+                        _ => 3
+                    },
+                    _ => map + 1
+                };
+                return Some(MapSlot::Episode { episode, map: next });
+            },
+            MapSlot::Commercial { map } => {
+                let next = match map {
+                    30 => return None,
+                    15 if secret => 31,
+                    31 if secret => 32,
+                    31 | 32 => 16,
+                    _ => map + 1
+                };
+                return Some(MapSlot::Commercial { map: next });
+            }
+        }
+    }
+
+    pub fn par_time(&self) -> Option<u32> {
+        match *self {
+            MapSlot::Episode { episode, map } if (1..=3).contains(&episode) && (1..=9).contains(&map) => {
+                Some(consts::PARS[episode as usize - 1][map as usize - 1])
+            },
+            MapSlot::Commercial { map } if (1..=32).contains(&map) => Some(consts::CPARS[map as usize - 1]),
+            _ => None
+        }
+    }
+}
+
+// Kills, items and secrets of the current level, as the wbstartstruct_t counters
+#[derive(Debug, Clone, Default)]
+pub struct LevelStats {
+    pub kills: i32,
+    pub total_kills: i32,
+    pub items: i32,
+    pub total_items: i32,
+    pub secrets: i32,
+    pub total_secrets: i32,
+    // Seconds
+    pub time: f32,
+    found_secrets: HashSet<usize>,
+}
+
+impl LevelStats {
+    pub fn new(map: &Map, actors: &[Rc<RefCell<Box<dyn Actor>>>]) -> Self {
+        LevelStats {
+            total_kills: actors.iter().filter(|actor| actor.borrow().thing_type().counts_kill()).count() as i32,
+            total_items: actors.iter().filter(|actor| actor.borrow().thing_type().counts_item()).count() as i32,
+            total_secrets: map.sectors.iter().filter(|sector| sector.special_type == consts::SECRET_SECTOR).count() as i32,
+            ..Default::default()
+        }
+    }
+
+    // Sectors of the secrets found, for the save games
+    pub fn found_secrets(&self) -> Vec<usize> {
+        let mut sectors: Vec<usize> = self.found_secrets.iter().copied().collect();
+        sectors.sort();
+        return sectors;
+    }
+
+    pub fn set_found_secrets(&mut self, sectors: &[usize]) {
+        self.found_secrets = sectors.iter().copied().collect();
+        self.secrets = self.found_secrets.len() as i32;
+    }
+
+    // As P_PlayerInSpecialSector, a secret counts once
+    pub fn enter_sector(&mut self, map: &Map, sector_id: usize) {
+        if let Some(sector) = map.sectors.get(sector_id) {
+            if sector.special_type == consts::SECRET_SECTOR && self.found_secrets.insert(sector_id) {
+                self.secrets += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode(episode: u8, map: u8) -> MapSlot {
+        MapSlot::Episode { episode, map }
+    }
+
+    fn commercial(map: u8) -> MapSlot {
+        MapSlot::Commercial { map }
+    }
+
+    #[test]
+    fn parse_both_naming_schemes() {
+        assert_eq!(MapSlot::parse("e2m4"), Some(episode(2, 4)));
+        assert_eq!(MapSlot::parse("MAP07"), Some(commercial(7)));
+        assert_eq!(MapSlot::parse("TEST"), None);
+        assert_eq!(commercial(7).name(), "MAP07");
+        assert_eq!(episode(2, 4).name(), "E2M4");
+    }
+
+    #[test]
+    fn secret_maps_return_after_their_exit() {
+        assert_eq!(episode(1, 3).next(true), Some(episode(1, 9)));
+        assert_eq!(episode(1, 9).next(false), Some(episode(1, 4)));
+        assert_eq!(episode(2, 9).next(false), Some(episode(2, 6)));
+        assert_eq!(episode(3, 9).next(false), Some(episode(3, 7)));
+        assert_eq!(episode(4, 9).next(false), Some(episode(4, 3)));
+        assert_eq!(episode(1, 7).next(false), Some(episode(1, 8)));
+        assert_eq!(episode(1, 8).next(false), None);
+    }
+
+    #[test]
+    fn commercial_secret_maps() {
+        assert_eq!(commercial(15).next(false), Some(commercial(16)));
+        assert_eq!(commercial(15).next(true), Some(commercial(31)));
+        assert_eq!(commercial(31).next(true), Some(commercial(32)));
+        assert_eq!(commercial(31).next(false), Some(commercial(16)));
+        assert_eq!(commercial(32).next(false), Some(commercial(16)));
+        assert_eq!(commercial(30).next(false), None);
+    }
+
+    #[test]
+    fn par_times() {
+        assert_eq!(episode(1, 1).par_time(), Some(30));
+        assert_eq!(episode(3, 9).par_time(), Some(135));
+        assert_eq!(episode(4, 1).par_time(), None);
+        assert_eq!(commercial(1).par_time(), Some(30));
+        assert_eq!(commercial(32).par_time(), Some(30));
+        assert_eq!(commercial(33).par_time(), None);
+    }
+}
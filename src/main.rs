@@ -14,42 +14,131 @@ mod camera;
 mod data_textures;
 mod collision;
 mod types;
+mod random;
+mod trace;
+mod attack;
+mod projectiles;
+mod inventory;
+mod weapons;
+mod specials;
+mod hud;
+mod text;
+mod automap;
+mod savegame;
+mod menu;
+mod level;
+mod intermission;
+mod console;
+mod wipe;
+mod palette;
+mod sound;
+mod mixer;
+mod music;
+mod opl;
+mod noise;
+mod controls;
+mod ticcmd;
 // Using engine
 use configure::Configure;
 use doom::Doom;
-use window::{doom_loop, doom_window};
+use mixer::write_wav;
+use music::Mus;
+use wad::Reader;
+use window::{doom_loop, doom_window, grab_cursor};
 // Using
 use winit::event_loop::EventLoop;
 use std::env;
 use std::process::ExitCode;
 
-fn cli_handler() -> Result<String, String> {
+enum CliCommand {
+    Play(String),
+    ExportMusic { config_file: String, lump: String, output: String },
+    RenderMusic { config_file: String, lump: String, output: String },
+}
+
+fn cli_handler() -> Result<CliCommand, String> {
     let args: Vec<String> = env::args().collect();
 
     match args.len() {
         1 => {
             Err(format!(
-                "Error: No arguments provided.\nUsage: {} <config-file>",
+                "Error: No arguments provided.\nUsage: {} <config-file> [--export-music <lump> <file.mid> | --render-music <lump> <file.wav>]",
                 args[0]
             ))
         }
         2 => {
             let config_file = &args[1];
-            Ok(config_file.clone())
+            Ok(CliCommand::Play(config_file.clone()))
+        }
+        5 if args[2] == "--export-music" => {
+            Ok(CliCommand::ExportMusic {
+                config_file: args[1].clone(),
+                lump: args[3].to_ascii_uppercase(),
+                output: args[4].clone(),
+            })
+        }
+        5 if args[2] == "--render-music" => {
+            Ok(CliCommand::RenderMusic {
+                config_file: args[1].clone(),
+                lump: args[3].to_ascii_uppercase(),
+                output: args[4].clone(),
+            })
         }
         _ => {
             Err(format!(
-                "Error: Too many arguments.\nUsage: {} <config-file>",
+                "Error: Invalid arguments.\nUsage: {} <config-file> [--export-music <lump> <file.mid> | --render-music <lump> <file.wav>]",
                 args[0]
             ))
         }
     }
 }
 
+fn load_wad(config_file: String) -> Result<(Configure, Reader), String> {
+    let configure = Configure::load_from_file(config_file).ok_or(String::from("Invalid config file"))?;
+    let wad = Reader::new(&configure.resource.wad).ok_or(format!("Invalid WAD {}", configure.resource.wad))?;
+    return Ok((configure, wad));
+}
+
+// Writes a MUS lump of the configured WAD as a MIDI file
+fn export_music(config_file: String, lump: &str, output: &str) -> Result<(), String> {
+    let (_, wad) = load_wad(config_file)?;
+    let data = wad.lump(lump).ok_or(format!("Lump {} not found", lump))?;
+    let midi = Mus::parse(data)?.to_midi();
+    return std::fs::write(output, midi).map_err(|err| err.to_string());
+}
+
+// Plays a MUS lump once through the FM synth into a WAV file
+fn render_music(config_file: String, lump: &str, output: &str) -> Result<(), String> {
+    let (configure, wad) = load_wad(config_file)?;
+    let data = wad.lump(lump).ok_or(format!("Lump {} not found", lump))?;
+    let song = Mus::parse(data)?;
+    let mut synth = Doom::create_synth(&wad, &configure.sound).ok_or(String::from("No music synth, check GENMIDI and the music device"))?;
+    let samples = synth.render_song(&song);
+    return write_wav(output, synth.rate(), &samples);
+}
+
 fn main() -> ExitCode {
     let config_file = {
         match cli_handler() {
-            Ok(config_file) => config_file,
+            Ok(CliCommand::Play(config_file)) => config_file,
+            Ok(CliCommand::ExportMusic { config_file, lump, output }) => {
+                return match export_music(config_file, &lump, &output) {
+                    Ok(_) => ExitCode::SUCCESS,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        ExitCode::FAILURE
+                    }
+                };
+            }
+            Ok(CliCommand::RenderMusic { config_file, lump, output }) => {
+                return match render_music(config_file, &lump, &output) {
+                    Ok(_) => ExitCode::SUCCESS,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        ExitCode::FAILURE
+                    }
+                };
+            }
             Err(err) => { 
                 eprintln!("{}", err); 
                 return ExitCode::FAILURE;
@@ -64,6 +153,9 @@ fn main() -> ExitCode {
         &event_loop,
     )
     .unwrap();
+    if configure.mouse.enabled && !grab_cursor(&window) {
+        eprintln!("Failed to grab the mouse cursor");
+    }
     let doom = Doom::new(&window, &configure);
 
     make_doom_loop!(event_loop, window, doom, configure.screen.frame_rate).unwrap();
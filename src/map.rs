@@ -193,6 +193,27 @@ impl LineDefFlags {
     }
 }
 
+#[repr(u16)]
+pub enum ThingFlags {
+    Easy = 1,
+    Medium = 2,
+    Hard = 4,
+    Ambush = 8,
+    NotSinglePlayer = 16
+}
+
+impl ThingFlags {
+    pub fn value(self) -> u16 {
+        self as u16
+    }
+}
+
+impl Thing {
+    pub fn has_flag(&self, mask: ThingFlags) -> bool {
+        (self.flags & mask.value()) != 0
+    }
+}
+
 impl MapLumpsIndex {
     pub fn value(self) -> usize {
         self as usize
@@ -283,6 +304,20 @@ impl LineDef {
         return &map.vertices[self.end_vertex_id as usize];
     }
 
+    pub fn front_sector<'a>(&'a self, map: &Map<'a>) -> Option<&'a Sector> {
+        if let Some(side_def) = self.front_side(&map) {
+            return Some(side_def.sector(&map));
+        }
+        return None;
+    }
+
+    pub fn back_sector<'a>(&'a self, map: &Map<'a>) -> Option<&'a Sector> {
+        if let Some(side_def) = self.back_side(&map) {
+            return Some(side_def.sector(&map));
+        }
+        return None;
+    }
+
     pub fn has_flag(&self, mask:LineDefFlags) -> bool {
         (self.flag & mask.value()) != 0
     }
@@ -482,6 +517,53 @@ impl<'a> Blockmaps<'a> {
         }
     }
 
+    pub fn get_on_path(&self, from: &Vector2<f32>, to: &Vector2<f32>) -> Vec<Rc<Vec<&'a LineDef>>> {
+        // Position in block units
+        let block_size = Blockmaps::BLOCKSIZE as f32;
+        let start = Vector2::new(
+            (from.x - self.header.x as f32) / block_size,
+            (from.y - self.header.y as f32) / block_size
+        );
+        let end = Vector2::new(
+            (to.x - self.header.x as f32) / block_size,
+            (to.y - self.header.y as f32) / block_size
+        );
+        let delta = end - start;
+        // Current and last block
+        let mut block = Vector2::new(start.x.floor() as i32, start.y.floor() as i32);
+        let last_block = Vector2::new(end.x.floor() as i32, end.y.floor() as i32);
+        // Step direction and distance (in ray fraction) between block borders
+        let step = Vector2::new(delta.x.signum() as i32, delta.y.signum() as i32);
+        let fraction_delta = Vector2::new(
+            if delta.x != 0.0 { (1.0 / delta.x).abs() } else { f32::INFINITY },
+            if delta.y != 0.0 { (1.0 / delta.y).abs() } else { f32::INFINITY }
+        );
+        let mut fraction_next = Vector2::new(
+            if delta.x > 0.0 { (block.x as f32 + 1.0 - start.x) * fraction_delta.x } 
+            else { (start.x - block.x as f32) * fraction_delta.x },
+            if delta.y > 0.0 { (block.y as f32 + 1.0 - start.y) * fraction_delta.y } 
+            else { (start.y - block.y as f32) * fraction_delta.y }
+        );
+        // Visit all the blocks crossed by the segment
+        let mut result = Vec::new();
+        let columns = self.header.columns as i32;
+        let rows = self.header.rows as i32;
+        let max_steps = (last_block.x - block.x).abs() + (last_block.y - block.y).abs() + 1;
+        for _ in 0..max_steps {
+            if block.x >= 0 && block.x < columns && block.y >= 0 && block.y < rows {
+                result.push(self.metrix_lines[(columns * block.y + block.x) as usize].clone());
+            }
+            if fraction_next.x < fraction_next.y {
+                fraction_next.x += fraction_delta.x;
+                block.x += step.x;
+            } else {
+                fraction_next.y += fraction_delta.y;
+                block.y += step.y;
+            }
+        }
+        result
+    }
+
     pub fn get_with_radius(&self, x: i16, y: i16, radius: u16) -> Vec<Rc<Vec<&'a LineDef>>> {
         // Calcola l'offset relativo all'origine della blockmap
         let m_x = (x as i32 - self.header.x as i32) / Blockmaps::BLOCKSIZE;
@@ -0,0 +1,535 @@
+#![allow(dead_code)]
+use std::rc::Rc;
+// Engine
+use crate::data_textures::DataTextures;
+use crate::doom::Doom;
+use crate::hud::draw_graphic;
+use crate::math::Vector2;
+use crate::render::Render;
+use crate::savegame::{slot_description, SAVE_SLOTS};
+use crate::text::Font;
+use crate::time::tics;
+use crate::types::Skill;
+use crate::controls::{Action, Controls};
+use crate::window::DoomSurface;
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+mod consts {
+    // As m_menu.c
+    pub const LINE_HEIGHT: i32 = 16;
+    pub const SKULL_X_OFFSET: i32 = -32;
+    pub const SKULL_Y_OFFSET: i32 = -5;
+    pub const SKULL_TICS: u32 = 8;
+    pub const SLOT_WIDTH: i32 = 24;
+    pub const QUIT_MESSAGE: &str = "are you sure you want to\nquit this great game?\n\n(press y to quit.)";
+    pub const NIGHTMARE_MESSAGE: &str = "are you sure? this skill level\nisn't even remotely fair.\n\npress y or n.";
+    pub const SHAREWARE_MESSAGE: &str = "this is the shareware version of doom.\n\nyou need to order the entire trilogy.\n\npress a key.";
+    pub const EMPTY_SLOT: &str = "empty slot";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuAction {
+    NewGame { episode: u8, skill: Skill },
+    LoadGame(usize),
+    SaveGame(usize),
+    Quit
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Page {
+    Main,
+    Episode,
+    Skill,
+    Options,
+    Sound,
+    Load,
+    Save
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Prompt {
+    Quit,
+    Nightmare,
+    Shareware
+}
+
+impl Prompt {
+    fn text(&self) -> &'static str {
+        match self {
+            Prompt::Quit => consts::QUIT_MESSAGE,
+            Prompt::Nightmare => consts::NIGHTMARE_MESSAGE,
+            Prompt::Shareware => consts::SHAREWARE_MESSAGE,
+        }
+    }
+
+    // Waits for a yes or no, otherwise any key closes it
+    fn needs_answer(&self) -> bool {
+        !matches!(self, Prompt::Shareware)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ItemKind {
+    Action,
+    // Number of steps
+    Slider(i32),
+    // Row taken by the slider above
+    Empty,
+    // Load and save slot
+    Slot
+}
+
+struct Item {
+    patch: &'static str,
+    kind: ItemKind,
+}
+
+const fn item(patch: &'static str, kind: ItemKind) -> Item {
+    Item { patch, kind }
+}
+
+struct PageDef {
+    // Patch names and positions
+    titles: &'static [(&'static str, i32, i32)],
+    items: &'static [Item],
+    x: i32,
+    y: i32,
+    parent: Option<Page>,
+}
+
+const MAIN_PAGE: PageDef = PageDef {
+    titles: &[("M_DOOM", 94, 2)],
+    items: &[
+        item("M_NGAME", ItemKind::Action),
+        item("M_OPTION", ItemKind::Action),
+        item("M_LOADG", ItemKind::Action),
+        item("M_SAVEG", ItemKind::Action),
+        item("M_QUITG", ItemKind::Action),
+    ],
+    x: 97, y: 64,
+    parent: None,
+};
+
+const EPISODE_PAGE: PageDef = PageDef {
+    titles: &[("M_EPISOD", 54, 38)],
+    items: &[
+        item("M_EPI1", ItemKind::Action),
+        item("M_EPI2", ItemKind::Action),
+        item("M_EPI3", ItemKind::Action),
+        item("M_EPI4", ItemKind::Action),
+    ],
+    x: 48, y: 63,
+    parent: Some(Page::Main),
+};
+
+const SKILL_PAGE: PageDef = PageDef {
+    titles: &[("M_NEWG", 96, 14), ("M_SKILL", 54, 38)],
+    items: &[
+        item("M_JKILL", ItemKind::Action),
+        item("M_ROUGH", ItemKind::Action),
+        item("M_HURT", ItemKind::Action),
+        item("M_ULTRA", ItemKind::Action),
+        item("M_NMARE", ItemKind::Action),
+    ],
+    x: 48, y: 63,
+    parent: Some(Page::Episode),
+};
+
+const OPTIONS_PAGE: PageDef = PageDef {
+    titles: &[("M_OPTTTL", 108, 15)],
+    items: &[
+        item("M_SCRNSZ", ItemKind::Slider(9)),
+        item("", ItemKind::Empty),
+        item("M_MSENS", ItemKind::Slider(10)),
+        item("", ItemKind::Empty),
+        item("M_SVOL", ItemKind::Action),
+    ],
+    x: 60, y: 37,
+    parent: Some(Page::Main),
+};
+
+const SOUND_PAGE: PageDef = PageDef {
+    titles: &[("M_SVOL", 60, 38)],
+    items: &[
+        item("M_SFXVOL", ItemKind::Slider(16)),
+        item("", ItemKind::Empty),
+        item("M_MUSVOL", ItemKind::Slider(16)),
+        item("", ItemKind::Empty),
+    ],
+    x: 80, y: 64,
+    parent: Some(Page::Options),
+};
+
+const SLOT_ITEMS: [Item; SAVE_SLOTS] = [
+    item("", ItemKind::Slot),
+    item("", ItemKind::Slot),
+    item("", ItemKind::Slot),
+    item("", ItemKind::Slot),
+    item("", ItemKind::Slot),
+    item("", ItemKind::Slot),
+];
+
+const LOAD_PAGE: PageDef = PageDef {
+    titles: &[("M_LOADG", 72, 28)],
+    items: &SLOT_ITEMS,
+    x: 80, y: 54,
+    parent: Some(Page::Main),
+};
+
+const SAVE_PAGE: PageDef = PageDef {
+    titles: &[("M_SAVEG", 72, 28)],
+    items: &SLOT_ITEMS,
+    x: 80, y: 54,
+    parent: Some(Page::Main),
+};
+
+impl Page {
+    const COUNT: usize = 7;
+
+    fn def(&self) -> &'static PageDef {
+        match self {
+            Page::Main => &MAIN_PAGE,
+            Page::Episode => &EPISODE_PAGE,
+            Page::Skill => &SKILL_PAGE,
+            Page::Options => &OPTIONS_PAGE,
+            Page::Sound => &SOUND_PAGE,
+            Page::Load => &LOAD_PAGE,
+            Page::Save => &SAVE_PAGE,
+        }
+    }
+}
+
+// Values changed from the options menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    // 0 to 9
+    pub mouse_sensitivity: i32,
+    // 3 to 11, 10 is the full view over the status bar, 11 hides the status bar
+    pub screen_size: i32,
+    // 0 to 15
+    pub sfx_volume: i32,
+    pub music_volume: i32,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Settings {
+            mouse_sensitivity: 5,
+            screen_size: 10,
+            sfx_volume: 8,
+            music_volume: 8,
+        }
+    }
+
+    // As the mouse sensitivity of vanilla, the default 5 keeps the configured one
+    pub fn mouse_scale(&self) -> f32 {
+        (self.mouse_sensitivity + 5) as f32 / 10.0
+    }
+}
+
+pub struct Menu {
+    active: bool,
+    page: Page,
+    item_on: [usize; Page::COUNT],
+    prompt: Option<Prompt>,
+    // Episodes found in the WAD
+    episodes: [bool; 4],
+    episode: u8,
+    slots: [Option<String>; SAVE_SLOTS],
+    settings: Settings,
+    skull: usize,
+    skull_time: f32,
+}
+
+impl Menu {
+    pub fn new(episodes: [bool; 4]) -> Self {
+        Menu {
+            active: false,
+            page: Page::Main,
+            item_on: [0; Page::COUNT],
+            prompt: None,
+            episodes: episodes,
+            episode: 1,
+            slots: Default::default(),
+            settings: Settings::new(),
+            skull: 0,
+            skull_time: 0.0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn open_main(&mut self) {
+        self.open(Page::Main);
+    }
+
+    fn open(&mut self, page: Page) {
+        self.active = true;
+        self.page = page;
+        if page == Page::Load || page == Page::Save {
+            self.slots = std::array::from_fn(slot_description);
+        }
+    }
+
+    fn close(&mut self) {
+        self.active = false;
+        self.prompt = None;
+    }
+
+    fn item_on(&self) -> usize {
+        self.item_on[self.page as usize]
+    }
+
+    // Skips the empty rows, as the cursor in M_Responder
+    fn move_cursor(&mut self, step: isize) {
+        let items = self.page.def().items;
+        let mut item_on = self.item_on();
+        loop {
+            item_on = (item_on as isize + step).rem_euclid(items.len() as isize) as usize;
+            if items[item_on].kind != ItemKind::Empty {
+                break;
+            }
+        }
+        self.item_on[self.page as usize] = item_on;
+    }
+
+    fn slider_value(&mut self, page: Page, item: usize) -> Option<&mut i32> {
+        match (page, item) {
+            (Page::Options, 0) => Some(&mut self.settings.screen_size),
+            (Page::Options, 2) => Some(&mut self.settings.mouse_sensitivity),
+            (Page::Sound, 0) => Some(&mut self.settings.sfx_volume),
+            (Page::Sound, 2) => Some(&mut self.settings.music_volume),
+            _ => None
+        }
+    }
+
+    // The screen size slider starts from 3 as the screenblocks
+    fn slider_min(page: Page, item: usize) -> i32 {
+        if page == Page::Options && item == 0 { 3 } else { 0 }
+    }
+
+    fn change_slider(&mut self, step: i32) {
+        let (page, item_on) = (self.page, self.item_on());
+        if let ItemKind::Slider(steps) = page.def().items[item_on].kind {
+            let min = Menu::slider_min(page, item_on);
+            if let Some(value) = self.slider_value(page, item_on) {
+                *value = (*value + step).clamp(min, min + steps - 1);
+            }
+        }
+    }
+
+    // As M_NewGame, without episodes in the WAD the skill is chosen right away
+    fn is_commercial(&self) -> bool {
+        self.episodes.iter().all(|episode| !episode)
+    }
+
+    fn select(&mut self) -> Option<MenuAction> {
+        let item_on = self.item_on();
+        match self.page {
+            Page::Main => match item_on {
+                0 if self.is_commercial() => {
+                    self.episode = 1;
+                    self.open(Page::Skill);
+                },
+                0 => self.open(Page::Episode),
+                1 => self.open(Page::Options),
+                2 => self.open(Page::Load),
+                3 => self.open(Page::Save),
+                _ => self.prompt = Some(Prompt::Quit),
+            },
+            Page::Episode => {
+                if self.episodes[item_on] {
+                    self.episode = item_on as u8 + 1;
+                    self.open(Page::Skill);
+                } else {
+                    self.prompt = Some(Prompt::Shareware);
+                }
+            },
+            Page::Skill => {
+                let skill = Skill::ALL[item_on];
+                if skill == Skill::Nightmare {
+                    self.prompt = Some(Prompt::Nightmare);
+                } else {
+                    self.close();
+                    return Some(MenuAction::NewGame { episode: self.episode, skill });
+                }
+            },
+            Page::Options => {
+                if item_on == 4 {
+                    self.open(Page::Sound);
+                }
+            },
+            Page::Sound => (),
+            Page::Load => {
+                if self.slots[item_on].is_some() {
+                    self.close();
+                    return Some(MenuAction::LoadGame(item_on));
+                }
+            },
+            Page::Save => {
+                self.close();
+                return Some(MenuAction::SaveGame(item_on));
+            },
+        }
+        return None;
+    }
+
+    fn answer(&mut self, prompt: Prompt, input: &WinitInputHelper) -> Option<MenuAction> {
+        if !prompt.needs_answer() {
+            if input.key_pressed(KeyCode::Enter) || input.key_pressed(KeyCode::Escape) || input.key_pressed(KeyCode::Space) {
+                self.prompt = None;
+            }
+            return None;
+        }
+        if input.key_pressed(KeyCode::KeyY) {
+            self.close();
+            return match prompt {
+                Prompt::Quit => Some(MenuAction::Quit),
+                _ => Some(MenuAction::NewGame { episode: self.episode, skill: Skill::Nightmare }),
+            };
+        }
+        if input.key_pressed(KeyCode::KeyN) || input.key_pressed(KeyCode::Escape) {
+            self.prompt = None;
+        }
+        return None;
+    }
+
+    // As M_Responder, the menu key opens and closes the menu
+    pub fn control(&mut self, input: &WinitInputHelper, controls: &Controls) -> Option<MenuAction> {
+        if !self.active {
+            if controls.pressed(input, Action::Menu) {
+                self.open(Page::Main);
+            }
+            return None;
+        }
+        if let Some(prompt) = self.prompt {
+            return self.answer(prompt, input);
+        }
+        if controls.pressed(input, Action::Menu) {
+            self.close();
+        } else if input.key_pressed(KeyCode::Backspace) {
+            match self.page.def().parent {
+                Some(Page::Episode) if self.is_commercial() => self.open(Page::Main),
+                Some(parent) => self.open(parent),
+                None => self.close()
+            }
+        } else if input.key_pressed(KeyCode::ArrowDown) {
+            self.move_cursor(1);
+        } else if input.key_pressed(KeyCode::ArrowUp) {
+            self.move_cursor(-1);
+        } else if input.key_pressed(KeyCode::ArrowLeft) {
+            self.change_slider(-1);
+        } else if input.key_pressed(KeyCode::ArrowRight) {
+            self.change_slider(1);
+        } else if input.key_pressed(KeyCode::Enter) {
+            return self.select();
+        }
+        return None;
+    }
+
+    // Blinking skull
+    pub fn update(&mut self, last_frame_time: f32) {
+        self.skull_time += last_frame_time;
+        if self.skull_time >= tics(consts::SKULL_TICS) {
+            self.skull_time = 0.0;
+            self.skull ^= 1;
+        }
+    }
+}
+
+// Menu pages drawn over the game, in 320x200 units
+pub struct RenderMenu<'wad> {
+    data_textures: Rc<DataTextures<'wad>>,
+    font: Font<'wad>,
+    size: Vector2<i32>,
+    offset: Vector2<i32>,
+}
+
+impl<'wad> RenderMenu<'wad> {
+    pub fn new(data_textures: &Rc<DataTextures<'wad>>, size: Vector2<i32>, offset: Vector2<i32>) -> Self {
+        RenderMenu {
+            data_textures: data_textures.clone(),
+            font: Font::new(data_textures),
+            size: size,
+            offset: offset,
+        }
+    }
+
+    fn draw_patch(&self, surface: &mut DoomSurface, name: &str, x: i32, y: i32) -> i32 {
+        draw_graphic(surface, &self.data_textures, name, x, y, &self.size, &self.offset)
+    }
+
+    // As M_DrawThermo
+    fn draw_slider(&self, surface: &mut DoomSurface, x: i32, y: i32, steps: i32, value: i32) {
+        let mut xx = x;
+        xx += self.draw_patch(surface, "M_THERML", xx, y);
+        for _ in 0..steps {
+            xx += self.draw_patch(surface, "M_THERMM", xx, y);
+        }
+        self.draw_patch(surface, "M_THERMR", xx, y);
+        self.draw_patch(surface, "M_THERMO", x + 8 + value * 8, y);
+    }
+
+    // As M_DrawSaveLoadBorder
+    fn draw_slot(&self, surface: &mut DoomSurface, x: i32, y: i32, text: &str) {
+        self.draw_patch(surface, "M_LSLEFT", x - 8, y + 7);
+        let mut xx = x;
+        for _ in 0..consts::SLOT_WIDTH {
+            self.draw_patch(surface, "M_LSCNTR", xx, y + 7);
+            xx += 8;
+        }
+        self.draw_patch(surface, "M_LSRGHT", xx, y + 7);
+        self.font.draw(surface, text, x, y, &self.size, &self.offset);
+    }
+
+    fn draw_page(&self, surface: &mut DoomSurface, menu: &mut Menu) {
+        let page = menu.page;
+        let def = page.def();
+        for (name, x, y) in def.titles {
+            self.draw_patch(surface, name, *x, *y);
+        }
+        for (index, item) in def.items.iter().enumerate() {
+            let y = def.y + index as i32 * consts::LINE_HEIGHT;
+            match item.kind {
+                ItemKind::Action => {
+                    self.draw_patch(surface, item.patch, def.x, y);
+                },
+                ItemKind::Slider(steps) => {
+                    self.draw_patch(surface, item.patch, def.x, y);
+                    let min = Menu::slider_min(page, index);
+                    let value = menu.slider_value(page, index).map(|value| *value).unwrap_or(min);
+                    self.draw_slider(surface, def.x, y + consts::LINE_HEIGHT, steps, value - min);
+                },
+                ItemKind::Slot => {
+                    let text = menu.slots[index].clone().unwrap_or(String::from(consts::EMPTY_SLOT));
+                    self.draw_slot(surface, def.x, y, &text);
+                },
+                ItemKind::Empty => ()
+            }
+        }
+        let skull = if menu.skull == 0 { "M_SKULL1" } else { "M_SKULL2" };
+        let skull_y = def.y + menu.item_on() as i32 * consts::LINE_HEIGHT + consts::SKULL_Y_OFFSET;
+        self.draw_patch(surface, skull, def.x + consts::SKULL_X_OFFSET, skull_y);
+    }
+}
+
+impl Render for RenderMenu<'_> {
+    fn draw<'wad>(&mut self, doom: &mut Doom<'wad>, _last_frame_time: f64, _blending_factor: f64) {
+        if !doom.menu.is_active() {
+            return;
+        }
+        let surface = doom.surface.clone();
+        let surface = &mut surface.borrow_mut();
+        match doom.menu.prompt {
+            Some(prompt) => self.font.draw_centered(surface, prompt.text(), &self.size, &self.offset),
+            None => self.draw_page(surface, &mut doom.menu)
+        }
+    }
+}
@@ -0,0 +1,408 @@
+#![allow(dead_code)]
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::rc::Rc;
+// Engine
+use crate::actors::Transform;
+use crate::math::{Vector2, radians};
+
+mod consts {
+    // As s_sound.c, in map units
+    pub const CLIPPING_DIST: f32 = 1200.0;
+    pub const CLOSE_DIST: f32 = 200.0;
+    pub const STEREO_SWING: f32 = 96.0;
+    pub const NORM_SEP: i32 = 128;
+    pub const MAX_VOLUME: i32 = 127;
+    pub const MAX_SFX_VOLUME: i32 = 15;
+    pub const WAV_HEADER_SIZE: usize = 44;
+}
+
+// Volume and stereo separation of a sound, as S_AdjustSoundParams.
+// None when the source is too far to be heard.
+pub fn adjust_sound_params(listener: &Transform, source: &Vector2<f32>, volume: i32) -> Option<(i32, i32)> {
+    let delta = *source - listener.position;
+    let (adx, ady) = (delta.x.abs(), delta.y.abs());
+    // Same approximation of the distance as vanilla
+    let distance = adx + ady - adx.min(ady) / 2.0;
+    if distance > consts::CLIPPING_DIST {
+        return None;
+    }
+    let angle = radians(delta.y.atan2(delta.x).to_degrees() - listener.angle);
+    let separation = consts::NORM_SEP - (consts::STEREO_SWING * angle.sin()) as i32;
+    let volume = if distance < consts::CLOSE_DIST {
+        volume
+    } else {
+        (volume as f32 * (consts::CLIPPING_DIST - distance) / (consts::CLIPPING_DIST - consts::CLOSE_DIST)) as i32
+    };
+    if volume <= 0 {
+        return None;
+    }
+    return Some((volume, separation.clamp(0, 255)));
+}
+
+struct Channel {
+    samples: Rc<Vec<i16>>,
+    position: usize,
+    // 0 to 127
+    volume: i32,
+    // 0 left, 128 center, 255 right
+    separation: i32,
+    priority: i32,
+    // Actor making the sound, it follows its position
+    origin: Option<usize>,
+}
+
+// Mixes the playing sounds into interleaved stereo 16-bit PCM
+pub struct Mixer {
+    rate: u32,
+    channels: Vec<Option<Channel>>,
+    // 0 to 15, as the menu
+    sfx_volume: i32,
+    // Fraction of frame left by the last render
+    pending: f32,
+}
+
+impl Mixer {
+    pub fn new(rate: u32, channels: usize) -> Self {
+        Mixer {
+            rate: rate,
+            channels: (0..channels.max(1)).map(|_| None).collect(),
+            sfx_volume: 8,
+            pending: 0.0,
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    pub fn set_sfx_volume(&mut self, volume: i32) {
+        self.sfx_volume = volume.clamp(0, consts::MAX_SFX_VOLUME);
+    }
+
+    // Full volume for the sfx volume setting
+    pub fn volume(&self) -> i32 {
+        self.sfx_volume * consts::MAX_VOLUME / consts::MAX_SFX_VOLUME
+    }
+
+    pub fn playing(&self) -> usize {
+        self.channels.iter().filter(|channel| channel.is_some()).count()
+    }
+
+    pub fn is_playing(&self, origin: usize) -> bool {
+        self.channels.iter().flatten().any(|channel| channel.origin == Some(origin))
+    }
+
+    pub fn stop_origin(&mut self, origin: usize) {
+        for channel in self.channels.iter_mut() {
+            if channel.as_ref().map(|channel| channel.origin == Some(origin)).unwrap_or(false) {
+                *channel = None;
+            }
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        self.channels.iter_mut().for_each(|channel| *channel = None);
+    }
+
+    // As S_getChannel, an actor plays one sound at a time and a busy mixer
+    // steals the channel of a sound not more important than the new one
+    pub fn start(&mut self, samples: &Rc<Vec<i16>>, priority: i32, origin: Option<usize>, volume: i32, separation: i32) -> Option<usize> {
+        if let Some(origin) = origin {
+            self.stop_origin(origin);
+        }
+        let channel_id = match self.channels.iter().position(|channel| channel.is_none()) {
+            Some(channel_id) => channel_id,
+            None => {
+                // The least important first
+                let (channel_id, lowest) = self.channels.iter()
+                    .enumerate()
+                    .filter_map(|(id, channel)| channel.as_ref().map(|channel| (id, channel.priority)))
+                    .max_by_key(|(_, priority)| *priority)?;
+                if lowest < priority {
+                    return None;
+                }
+                channel_id
+            }
+        };
+        self.channels[channel_id] = Some(Channel {
+            samples: samples.clone(),
+            position: 0,
+            volume: volume.clamp(0, consts::MAX_VOLUME),
+            separation: separation.clamp(0, 255),
+            priority: priority,
+            origin: origin,
+        });
+        return Some(channel_id);
+    }
+
+    // As S_UpdateSounds, the sounds follow their origin and stop once out of range
+    pub fn update(&mut self, listener: Option<&Transform>, locate: impl Fn(usize) -> Option<Vector2<f32>>) {
+        let volume = self.volume();
+        let listener = match listener {
+            Some(listener) => listener,
+            None => return
+        };
+        for slot in self.channels.iter_mut() {
+            if let Some(channel) = slot {
+                if let Some(origin) = channel.origin {
+                    match locate(origin).map(|position| adjust_sound_params(listener, &position, volume)) {
+                        Some(Some((volume, separation))) => {
+                            channel.volume = volume;
+                            channel.separation = separation;
+                        },
+                        Some(None) => *slot = None,
+                        // Removed actors leave their sound where it was
+                        None => channel.origin = None
+                    }
+                }
+            }
+        }
+    }
+
+    // Mixes the next frames into the interleaved left and right buffer
+    pub fn mix(&mut self, output: &mut [i16]) {
+        let mut buffer = vec![0i32; output.len()];
+        for slot in self.channels.iter_mut() {
+            if let Some(channel) = slot {
+                // As I_StartSound volume and separation to the left and right volumes
+                let separation = channel.separation + 1;
+                let left = channel.volume - ((channel.volume * separation * separation) >> 16);
+                let separation = separation - 257;
+                let right = channel.volume - ((channel.volume * separation * separation) >> 16);
+                for frame in buffer.chunks_exact_mut(2) {
+                    if channel.position >= channel.samples.len() {
+                        break;
+                    }
+                    let sample = channel.samples[channel.position] as i32;
+                    frame[0] += sample * left / consts::MAX_VOLUME;
+                    frame[1] += sample * right / consts::MAX_VOLUME;
+                    channel.position += 1;
+                }
+                if channel.position >= channel.samples.len() {
+                    *slot = None;
+                }
+            }
+        }
+        for (out, mixed) in output.iter_mut().zip(buffer.iter()) {
+            *out = (*mixed).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        }
+    }
+
+    // Whole frames of the given time, the fraction is kept for the next call
+    fn frames(&mut self, seconds: f32) -> usize {
+        self.pending += seconds * self.rate as f32;
+        let frames = self.pending as usize;
+        self.pending -= frames as f32;
+        return frames;
+    }
+
+    // Mixes the given time, as interleaved stereo
+    pub fn render(&mut self, seconds: f32) -> Vec<i16> {
+        let mut output = vec![0; self.frames(seconds) * 2];
+        self.mix(&mut output);
+        return output;
+    }
+
+    // Plays the given time without mixing it, for when no output takes the samples
+    pub fn advance(&mut self, seconds: f32) {
+        let frames = self.frames(seconds);
+        for slot in self.channels.iter_mut() {
+            if let Some(channel) = slot {
+                channel.position += frames;
+                if channel.position >= channel.samples.len() {
+                    *slot = None;
+                }
+            }
+        }
+    }
+}
+
+// Header of a 16-bit stereo PCM WAV file
+fn wav_header(rate: u32, data_size: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(consts::WAV_HEADER_SIZE);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36u32.saturating_add(data_size)).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, 2 channels
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&rate.to_le_bytes());
+    header.extend_from_slice(&(rate * 4).to_le_bytes());
+    header.extend_from_slice(&4u16.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_size.to_le_bytes());
+    return header;
+}
+
+fn write_samples(writer: &mut impl Write, samples: &[i16]) -> Result<(), String> {
+    let bytes: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+    return writer.write_all(&bytes).map_err(|err| err.to_string());
+}
+
+// 16-bit stereo PCM WAV file
+pub fn write_wav(path: &str, rate: u32, samples: &[i16]) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| err.to_string())?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&wav_header(rate, (samples.len() * 2) as u32)).map_err(|err| err.to_string())?;
+    write_samples(&mut writer, samples)?;
+    return writer.flush().map_err(|err| err.to_string());
+}
+
+// Headless output, streams the mixed sound to the file, the sizes of the header are set by finish
+pub struct WavRecorder {
+    path: String,
+    rate: u32,
+    writer: Option<BufWriter<File>>,
+    data_size: u32,
+}
+
+impl WavRecorder {
+    pub fn new(path: &str, rate: u32) -> Result<Self, String> {
+        let file = File::create(path).map_err(|err| format!("{}: {}", path, err))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&wav_header(rate, 0)).map_err(|err| err.to_string())?;
+        return Ok(WavRecorder {
+            path: String::from(path),
+            rate: rate,
+            writer: Some(writer),
+            data_size: 0,
+        });
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn push(&mut self, samples: &[i16]) -> Result<(), String> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => return Err(String::from("Recording finished"))
+        };
+        write_samples(writer, samples)?;
+        self.data_size = self.data_size.saturating_add((samples.len() * 2) as u32);
+        return Ok(());
+    }
+
+    // Patches the header with the recorded size and closes the file
+    pub fn finish(&mut self) -> Result<(), String> {
+        let mut writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => return Ok(())
+        };
+        writer.seek(SeekFrom::Start(0)).map_err(|err| err.to_string())?;
+        writer.write_all(&wav_header(self.rate, self.data_size)).map_err(|err| err.to_string())?;
+        return writer.flush().map_err(|err| err.to_string());
+    }
+}
+
+// Unfinished recordings are closed on a best effort
+impl Drop for WavRecorder {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listener() -> Transform {
+        Transform::set(Vector2::new(0.0, 0.0), 0.0, 41.0)
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(name).to_string_lossy().to_string()
+    }
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    }
+
+    #[test]
+    fn close_sounds_in_front_are_full_and_centred() {
+        assert_eq!(adjust_sound_params(&listener(), &Vector2::new(100.0, 0.0), 127), Some((127, 128)));
+    }
+
+    #[test]
+    fn sounds_attenuate_with_distance_and_pan() {
+        // On the left at 500 units
+        assert_eq!(adjust_sound_params(&listener(), &Vector2::new(0.0, 500.0), 127), Some((88, 32)));
+        // On the right
+        let (_, separation) = adjust_sound_params(&listener(), &Vector2::new(0.0, -500.0), 127).unwrap();
+        assert_eq!(separation, 224);
+    }
+
+    #[test]
+    fn far_sounds_are_not_heard() {
+        assert_eq!(adjust_sound_params(&listener(), &Vector2::new(2000.0, 0.0), 127), None);
+    }
+
+    #[test]
+    fn busy_mixer_steals_the_least_important_channel() {
+        let samples = Rc::new(vec![0; 64]);
+        let mut mixer = Mixer::new(11025, 2);
+        assert_eq!(mixer.start(&samples, 64, None, 127, 128), Some(0));
+        assert_eq!(mixer.start(&samples, 100, None, 127, 128), Some(1));
+        assert_eq!(mixer.start(&samples, 70, None, 127, 128), Some(1));
+        // Both playing sounds are more important
+        assert_eq!(mixer.start(&samples, 200, None, 127, 128), None);
+        assert_eq!(mixer.playing(), 2);
+    }
+
+    #[test]
+    fn an_origin_plays_one_sound() {
+        let samples = Rc::new(vec![0; 64]);
+        let mut mixer = Mixer::new(11025, 4);
+        mixer.start(&samples, 64, Some(7), 127, 128);
+        mixer.start(&samples, 64, Some(7), 127, 128);
+        assert_eq!(mixer.playing(), 1);
+        assert!(mixer.is_playing(7));
+    }
+
+    #[test]
+    fn advance_ends_the_sounds_as_render() {
+        let samples = Rc::new(vec![0; 100]);
+        let mut mixer = Mixer::new(1000, 2);
+        mixer.start(&samples, 64, None, 127, 128);
+        mixer.advance(0.05);
+        assert_eq!(mixer.playing(), 1);
+        mixer.advance(0.05);
+        assert_eq!(mixer.playing(), 0);
+    }
+
+    #[test]
+    fn write_wav_header() {
+        let path = temp_path("rust-doom-write-wav.wav");
+        write_wav(&path, 22050, &[1, -1, 2, -2]).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(data.len(), consts::WAV_HEADER_SIZE + 8);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(read_u32(&data, 4), 36 + 8);
+        assert_eq!(&data[8..16], b"WAVEfmt ");
+        assert_eq!(u16::from_le_bytes([data[22], data[23]]), 2);
+        assert_eq!(read_u32(&data, 24), 22050);
+        assert_eq!(read_u32(&data, 28), 22050 * 4);
+        assert_eq!(&data[36..40], b"data");
+        assert_eq!(read_u32(&data, 40), 8);
+        assert_eq!(&data[44..46], &1i16.to_le_bytes());
+    }
+
+    #[test]
+    fn recorder_patches_the_sizes_on_finish() {
+        let path = temp_path("rust-doom-recorder.wav");
+        let mut recorder = WavRecorder::new(&path, 44100).unwrap();
+        recorder.push(&[0; 6]).unwrap();
+        recorder.push(&[0; 4]).unwrap();
+        recorder.finish().unwrap();
+        assert!(recorder.push(&[0; 2]).is_err());
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(data.len(), consts::WAV_HEADER_SIZE + 20);
+        assert_eq!(read_u32(&data, 4), 36 + 20);
+        assert_eq!(read_u32(&data, 40), 20);
+    }
+}
@@ -0,0 +1,406 @@
+#![allow(dead_code)]
+// Engine
+use crate::level::MapSlot;
+
+mod consts {
+    pub const MUS_ID: &[u8; 4] = b"MUS\x1A";
+    pub const HEADER_SIZE: usize = 16;
+    // MUS tics per second
+    pub const TIC_RATE: u32 = 140;
+    pub const PERCUSSION_CHANNEL: u8 = 15;
+    pub const MIDI_PERCUSSION_CHANNEL: u8 = 9;
+    // 70 ticks per quarter at 120 bpm make the 140 Hz of MUS
+    pub const MIDI_DIVISION: u16 = 70;
+    pub const MIDI_TEMPO: u32 = 500000;
+    // MUS controllers 1 to 9, the 0 is the program change
+    pub const MIDI_CONTROLLERS: [u8; 10] = [0, 0, 1, 7, 10, 11, 91, 93, 64, 67];
+    // MUS system events 10 to 14
+    pub const MIDI_SYSTEM: [u8; 5] = [120, 123, 126, 127, 121];
+    // Doom II songs by map, as S_music
+    pub const COMMERCIAL_SONGS: [&str; 32] = [
+        "RUNNIN", "STALKS", "COUNTD", "BETWEE", "DOOM", "THE_DA", "SHAWN", "DDTBLU",
+        "IN_CIT", "DEAD", "STLKS2", "THEDA2", "DOOM2", "DDTBL2", "RUNNI2", "DEAD2",
+        "STLKS3", "ROMERO", "SHAWN2", "MESSAG", "COUNT2", "DDTBL3", "AMPIE", "THEDA3",
+        "ADRIAN", "MESSG2", "ROMER2", "TENSE", "SHAWN3", "OPENIN", "EVIL", "ULTIMA",
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MusEvent {
+    ReleaseNote { channel: u8, note: u8 },
+    // Without volume the last one of the channel is used
+    PlayNote { channel: u8, note: u8, volume: Option<u8> },
+    // 0 to 255, 128 is the center
+    PitchBend { channel: u8, value: u8 },
+    System { channel: u8, controller: u8 },
+    // Controller 0 is the instrument
+    Controller { channel: u8, controller: u8, value: u8 },
+    EndOfMeasure,
+    ScoreEnd,
+}
+
+// Event and the MUS tics to wait after it
+#[derive(Debug, Clone, Copy)]
+pub struct MusTimedEvent {
+    pub event: MusEvent,
+    pub delay: u32,
+}
+
+pub struct Mus {
+    pub primary_channels: u16,
+    pub secondary_channels: u16,
+    pub instruments: Vec<u16>,
+    pub events: Vec<MusTimedEvent>,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes([*data.get(offset)?, *data.get(offset + 1)?]))
+}
+
+// Song lump of a map
+pub fn map_music(map_name: &str) -> Option<String> {
+    match MapSlot::parse(map_name)? {
+        MapSlot::Episode { episode, map } => Some(format!("D_E{}M{}", episode, map)),
+        MapSlot::Commercial { map } => consts::COMMERCIAL_SONGS.get((map as usize).checked_sub(1)?).map(|song| format!("D_{}", song)),
+    }
+}
+
+// As WI_Start, Doom II has its own intermission song
+pub fn intermission_music(slot: &MapSlot) -> &'static str {
+    match slot {
+        MapSlot::Episode { .. } => "D_INTER",
+        MapSlot::Commercial { .. } => "D_DM2INT",
+    }
+}
+
+pub fn is_mus(data: &[u8]) -> bool {
+    data.starts_with(consts::MUS_ID)
+}
+
+impl Mus {
+    pub fn tic_rate() -> u32 {
+        consts::TIC_RATE
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if !is_mus(data) || data.len() < consts::HEADER_SIZE {
+            return Err(String::from("Not a MUS lump"));
+        }
+        let header = |offset: usize| read_u16(data, offset).ok_or(String::from("Invalid MUS header"));
+        let score_length = header(4)? as usize;
+        let score_start = header(6)? as usize;
+        let instrument_count = header(12)? as usize;
+        let instruments = (0..instrument_count)
+            .map(|i| read_u16(data, consts::HEADER_SIZE + i * 2).ok_or(String::from("Invalid MUS instruments")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let end = (score_start + score_length).min(data.len());
+        let score = data.get(score_start..end).ok_or(String::from("Invalid MUS score"))?;
+        let mut events = vec![];
+        let mut offset = 0;
+        let mut next = || -> Result<u8, String> {
+            let byte = *score.get(offset).ok_or(String::from("Unexpected end of the MUS score"))?;
+            offset += 1;
+            return Ok(byte);
+        };
+        loop {
+            let descriptor = next()?;
+            let channel = descriptor & 0x0F;
+            let event = match (descriptor >> 4) & 0x07 {
+                0 => MusEvent::ReleaseNote { channel, note: next()? & 0x7F },
+                1 => {
+                    let note = next()?;
+                    let volume = if note & 0x80 != 0 { Some(next()? & 0x7F) } else { None };
+                    MusEvent::PlayNote { channel, note: note & 0x7F, volume }
+                },
+                2 => MusEvent::PitchBend { channel, value: next()? },
+                3 => MusEvent::System { channel, controller: next()? & 0x7F },
+                4 => {
+                    let controller = next()? & 0x7F;
+                    MusEvent::Controller { channel, controller, value: next()? & 0x7F }
+                },
+                5 => MusEvent::EndOfMeasure,
+                6 => MusEvent::ScoreEnd,
+                kind => return Err(format!("Unknown MUS event {}", kind)),
+            };
+            // Variable length delay
+            let mut delay = 0;
+            if descriptor & 0x80 != 0 {
+                loop {
+                    let byte = next()?;
+                    delay = (delay << 7) | (byte & 0x7F) as u32;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                }
+            }
+            events.push(MusTimedEvent { event, delay });
+            if event == MusEvent::ScoreEnd {
+                break;
+            }
+        }
+        return Ok(Mus {
+            primary_channels: header(8)?,
+            secondary_channels: header(10)?,
+            instruments: instruments,
+            events: events,
+        });
+    }
+
+    // MUS channel 15 is the percussion, the other ones skip the MIDI channel 9
+    pub fn midi_channel(channel: u8) -> u8 {
+        match channel {
+            consts::PERCUSSION_CHANNEL => consts::MIDI_PERCUSSION_CHANNEL,
+            channel if channel >= consts::MIDI_PERCUSSION_CHANNEL => channel + 1,
+            channel => channel
+        }
+    }
+
+    // Channel messages of an event, as mus2mid
+    pub fn midi_message(event: &MusEvent, volumes: &mut [u8; 16]) -> Option<Vec<u8>> {
+        match *event {
+            MusEvent::ReleaseNote { channel, note } => {
+                Some(vec![0x80 | Mus::midi_channel(channel), note, 0x40])
+            },
+            MusEvent::PlayNote { channel, note, volume } => {
+                if let Some(volume) = volume {
+                    volumes[channel as usize] = volume;
+                }
+                Some(vec![0x90 | Mus::midi_channel(channel), note, volumes[channel as usize]])
+            },
+            MusEvent::PitchBend { channel, value } => {
+                let bend = (value as u16) << 6;
+                Some(vec![0xE0 | Mus::midi_channel(channel), (bend & 0x7F) as u8, (bend >> 7) as u8])
+            },
+            MusEvent::System { channel, controller } => {
+                let controller = *consts::MIDI_SYSTEM.get((controller as usize).checked_sub(10)?)?;
+                Some(vec![0xB0 | Mus::midi_channel(channel), controller, 0])
+            },
+            MusEvent::Controller { channel, controller: 0, value } => {
+                Some(vec![0xC0 | Mus::midi_channel(channel), value])
+            },
+            MusEvent::Controller { channel, controller, value } => {
+                let controller = *consts::MIDI_CONTROLLERS.get(controller as usize)?;
+                Some(vec![0xB0 | Mus::midi_channel(channel), controller, value])
+            },
+            MusEvent::EndOfMeasure | MusEvent::ScoreEnd => None
+        }
+    }
+
+    // Standard MIDI File, format 0 with one track
+    pub fn to_midi(&self) -> Vec<u8> {
+        let mut track: Vec<u8> = vec![];
+        let write_delta = |track: &mut Vec<u8>, delta: u32| {
+            let mut buffer = vec![(delta & 0x7F) as u8];
+            let mut delta = delta >> 7;
+            while delta > 0 {
+                buffer.push(0x80 | (delta & 0x7F) as u8);
+                delta >>= 7;
+            }
+            track.extend(buffer.iter().rev());
+        };
+        // Tempo
+        write_delta(&mut track, 0);
+        track.extend_from_slice(&[0xFF, 0x51, 0x03]);
+        track.extend_from_slice(&consts::MIDI_TEMPO.to_be_bytes()[1..]);
+        let mut volumes = [127u8; 16];
+        let mut delta = 0;
+        for timed in self.events.iter() {
+            if let Some(message) = Mus::midi_message(&timed.event, &mut volumes) {
+                write_delta(&mut track, delta);
+                track.extend(message);
+                delta = 0;
+            }
+            delta += timed.delay;
+        }
+        // End of track
+        write_delta(&mut track, delta);
+        track.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+        let mut midi = vec![];
+        midi.extend_from_slice(b"MThd");
+        midi.extend_from_slice(&6u32.to_be_bytes());
+        midi.extend_from_slice(&0u16.to_be_bytes());
+        midi.extend_from_slice(&1u16.to_be_bytes());
+        midi.extend_from_slice(&consts::MIDI_DIVISION.to_be_bytes());
+        midi.extend_from_slice(b"MTrk");
+        midi.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        midi.extend(track);
+        return midi;
+    }
+}
+
+// Sequences the current song, the events go to the synth
+pub struct MusicPlayer {
+    name: Option<String>,
+    song: Option<Mus>,
+    looping: bool,
+    event_id: usize,
+    // MUS tics to wait before the next event
+    wait: f32,
+}
+
+impl MusicPlayer {
+    pub fn new() -> Self {
+        MusicPlayer {
+            name: None,
+            song: None,
+            looping: false,
+            event_id: 0,
+            wait: 0.0,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.song.is_some()
+    }
+
+    // As S_ChangeMusic, the same song keeps playing
+    pub fn play(&mut self, name: &str, song: Mus, looping: bool) {
+        if self.name.as_deref() == Some(name) {
+            return;
+        }
+        self.name = Some(String::from(name));
+        self.song = Some(song);
+        self.looping = looping;
+        self.event_id = 0;
+        self.wait = 0.0;
+    }
+
+    pub fn stop(&mut self) {
+        self.name = None;
+        self.song = None;
+    }
+
+    // Sends the events due in the elapsed time
+    pub fn update(&mut self, last_frame_time: f32, mut sink: impl FnMut(&MusEvent)) {
+        let song = match &self.song {
+            Some(song) => song,
+            None => return
+        };
+        self.wait -= last_frame_time * consts::TIC_RATE as f32;
+        while self.wait <= 0.0 {
+            let timed = match song.events.get(self.event_id) {
+                Some(timed) => timed,
+                None => {
+                    self.song = None;
+                    self.name = None;
+                    return;
+                }
+            };
+            self.event_id += 1;
+            if timed.event == MusEvent::ScoreEnd {
+                if !self.looping {
+                    self.song = None;
+                    self.name = None;
+                    return;
+                }
+                self.event_id = 0;
+            }
+            sink(&timed.event);
+            self.wait += timed.delay as f32;
+            // Empty songs would never wait
+            if self.event_id == 0 && self.wait <= 0.0 {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // MUS lump around the given score, without instruments
+    fn mus_lump(score: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(consts::MUS_ID);
+        data.extend_from_slice(&(score.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(consts::HEADER_SIZE as u16).to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(score);
+        return data;
+    }
+
+    #[test]
+    fn parse_reads_the_delay_varint() {
+        // Play note 60 at volume 100 then wait 128 tics, release it and end
+        let mus = Mus::parse(&mus_lump(&[0x90, 0xBC, 100, 0x81, 0x00, 0x00, 60, 0x60])).unwrap();
+        assert_eq!(mus.events.len(), 3);
+        assert_eq!(mus.events[0].event, MusEvent::PlayNote { channel: 0, note: 60, volume: Some(100) });
+        assert_eq!(mus.events[0].delay, 128);
+        assert_eq!(mus.events[1].event, MusEvent::ReleaseNote { channel: 0, note: 60 });
+        assert_eq!(mus.events[1].delay, 0);
+        assert_eq!(mus.events[2].event, MusEvent::ScoreEnd);
+    }
+
+    #[test]
+    fn parse_fails_without_the_score_end() {
+        assert!(Mus::parse(&mus_lump(&[0x00, 60])).is_err());
+        assert!(Mus::parse(b"MThd").is_err());
+    }
+
+    #[test]
+    fn percussion_goes_to_the_midi_channel_9() {
+        assert_eq!(Mus::midi_channel(0), 0);
+        assert_eq!(Mus::midi_channel(8), 8);
+        assert_eq!(Mus::midi_channel(9), 10);
+        assert_eq!(Mus::midi_channel(14), 15);
+        assert_eq!(Mus::midi_channel(15), 9);
+    }
+
+    #[test]
+    fn system_and_controller_events_map_to_midi() {
+        let mut volumes = [127u8; 16];
+        let system = |controller| Mus::midi_message(&MusEvent::System { channel: 15, controller }, &mut [127u8; 16]);
+        assert_eq!(system(10), Some(vec![0xB9, 120, 0]));
+        assert_eq!(system(14), Some(vec![0xB9, 121, 0]));
+        assert_eq!(system(15), None);
+        // Controller 0 is the program change
+        let program = MusEvent::Controller { channel: 1, controller: 0, value: 30 };
+        assert_eq!(Mus::midi_message(&program, &mut volumes), Some(vec![0xC1, 30]));
+        let volume = MusEvent::Controller { channel: 1, controller: 3, value: 90 };
+        assert_eq!(Mus::midi_message(&volume, &mut volumes), Some(vec![0xB1, 7, 90]));
+        let unknown = MusEvent::Controller { channel: 1, controller: 10, value: 0 };
+        assert_eq!(Mus::midi_message(&unknown, &mut volumes), None);
+    }
+
+    #[test]
+    fn notes_without_volume_take_the_last_one() {
+        let mut volumes = [127u8; 16];
+        Mus::midi_message(&MusEvent::PlayNote { channel: 2, note: 40, volume: Some(64) }, &mut volumes);
+        let message = Mus::midi_message(&MusEvent::PlayNote { channel: 2, note: 41, volume: None }, &mut volumes);
+        assert_eq!(message, Some(vec![0x92, 41, 64]));
+    }
+
+    #[test]
+    fn to_midi_frames_one_track() {
+        let mus = Mus::parse(&mus_lump(&[0x90, 0xBC, 100, 0x81, 0x00, 0x00, 60, 0x60])).unwrap();
+        let midi = mus.to_midi();
+        assert_eq!(&midi[0..4], b"MThd");
+        assert_eq!(u32::from_be_bytes([midi[4], midi[5], midi[6], midi[7]]), 6);
+        // Format 0, one track
+        assert_eq!(&midi[8..12], &[0, 0, 0, 1]);
+        assert_eq!(u16::from_be_bytes([midi[12], midi[13]]), consts::MIDI_DIVISION);
+        assert_eq!(&midi[14..18], b"MTrk");
+        let length = u32::from_be_bytes([midi[18], midi[19], midi[20], midi[21]]) as usize;
+        assert_eq!(midi.len(), 22 + length);
+        assert!(midi.ends_with(&[0xFF, 0x2F, 0x00]));
+        // The release waits the 128 tics, as a two bytes delta
+        let release = midi.windows(5).position(|bytes| bytes == [0x81, 0x00, 0x80, 60, 0x40]);
+        assert!(release.is_some());
+    }
+
+    #[test]
+    fn map_songs() {
+        assert_eq!(map_music("E1M1").as_deref(), Some("D_E1M1"));
+        assert_eq!(map_music("MAP01").as_deref(), Some("D_RUNNIN"));
+        assert_eq!(map_music("MAP32").as_deref(), Some("D_ULTIMA"));
+        assert_eq!(map_music("MAP33"), None);
+    }
+}
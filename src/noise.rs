@@ -0,0 +1,81 @@
+#![allow(dead_code)]
+// Engine
+use crate::map::{LineDefFlags, Map};
+use crate::trace::line_opening;
+
+mod consts {
+    // The noise stops at the second sound blocking line
+    pub const MAX_SOUND_BLOCKS: u8 = 1;
+}
+
+// Actor heard in each sector, as the soundtarget of sector_t
+pub struct SoundTargets {
+    // Lines bounding each sector
+    sector_lines: Vec<Vec<usize>>,
+    targets: Vec<Option<usize>>,
+}
+
+impl SoundTargets {
+    pub fn new(map: &Map) -> Self {
+        let mut sector_lines = vec![vec![]; map.sectors.len()];
+        for (line_id, line) in map.line_defs.iter().enumerate() {
+            for side in [line.front_side(map), line.back_side(map)].into_iter().flatten() {
+                if let Some(lines) = sector_lines.get_mut(side.sector_id as usize) {
+                    if !lines.contains(&line_id) {
+                        lines.push(line_id);
+                    }
+                }
+            }
+        }
+        SoundTargets {
+            targets: vec![None; map.sectors.len()],
+            sector_lines: sector_lines,
+        }
+    }
+
+    pub fn target(&self, sector_id: usize) -> Option<usize> {
+        self.targets.get(sector_id).copied().flatten()
+    }
+
+    pub fn clear(&mut self) {
+        self.targets.iter_mut().for_each(|target| *target = None);
+    }
+
+    // As P_NoiseAlert, the noise floods the sectors joined by open two sided lines
+    pub fn alert(&mut self, map: &Map, sector_id: usize, target: usize) {
+        // Sound blocks crossed to reach each sector, as soundtraversed
+        let mut traversed = vec![None; self.targets.len()];
+        self.recursive_sound(map, &mut traversed, sector_id, 0, target);
+    }
+
+    // As P_RecursiveSound, a sector is visited again by a path with fewer sound blocks
+    fn recursive_sound(&mut self, map: &Map, traversed: &mut Vec<Option<u8>>, sector_id: usize, sound_blocks: u8, target: usize) {
+        match traversed.get(sector_id) {
+            Some(Some(blocks)) if *blocks <= sound_blocks => return,
+            Some(_) => (),
+            None => return
+        }
+        traversed[sector_id] = Some(sound_blocks);
+        self.targets[sector_id] = Some(target);
+        for line_id in self.sector_lines[sector_id].clone() {
+            let line = map.line_defs[line_id];
+            // Closed doors stop the noise
+            match line_opening(map, line) {
+                Some(opening) if opening.range > 0.0 => (),
+                _ => continue
+            }
+            let (front, back) = match (line.front_side(map), line.back_side(map)) {
+                (Some(front), Some(back)) => (front.sector_id as usize, back.sector_id as usize),
+                _ => continue
+            };
+            let other = if front == sector_id { back } else { front };
+            if line.has_flag(LineDefFlags::SoundBlock) {
+                if sound_blocks < consts::MAX_SOUND_BLOCKS {
+                    self.recursive_sound(map, traversed, other, sound_blocks + 1, target);
+                }
+            } else {
+                self.recursive_sound(map, traversed, other, sound_blocks, target);
+            }
+        }
+    }
+}
@@ -0,0 +1,578 @@
+#![allow(dead_code)]
+use std::f32::consts::PI;
+// Engine
+use crate::music::{Mus, MusEvent};
+
+mod consts {
+    pub const GENMIDI_ID: &[u8; 8] = b"#OPL_II#";
+    pub const INSTRUMENT_COUNT: usize = 175;
+    pub const INSTRUMENT_SIZE: usize = 36;
+    pub const VOICE_SIZE: usize = 16;
+    // GENMIDI flags
+    pub const FLAG_FIXED_PITCH: u16 = 0x01;
+    pub const FLAG_DOUBLE_VOICE: u16 = 0x04;
+    // Percussion instruments are the notes 35 to 81 of the MIDI channel 9
+    pub const PERCUSSION_FIRST_NOTE: u8 = 35;
+    pub const PERCUSSION_LAST_NOTE: u8 = 81;
+    pub const PERCUSSION_CHANNEL: usize = 9;
+    pub const OPL2_VOICES: usize = 9;
+    pub const OPL3_VOICES: usize = 18;
+    // Frequency multipliers of the register 0x20
+    pub const MULTIPLIERS: [f32; 16] = [0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 10.0, 12.0, 12.0, 15.0, 15.0];
+    // Key scale levels in dB per octave
+    pub const KEY_SCALE: [f32; 4] = [0.0, 3.0, 1.5, 6.0];
+    // Envelope times in seconds for the rate 1, each step up halves them
+    pub const ATTACK_TIME: f32 = 2.826;
+    pub const DECAY_TIME: f32 = 39.28;
+    // Attenuation of a silent operator
+    pub const SILENCE_DB: f32 = 96.0;
+    pub const TREMOLO_RATE: f32 = 3.7;
+    pub const TREMOLO_DEPTH_DB: f32 = 1.0;
+    pub const VIBRATO_RATE: f32 = 6.1;
+    pub const VIBRATO_DEPTH: f32 = 0.07;
+    // Phase shift of the carrier for a full modulator output
+    pub const MODULATION_DEPTH: f32 = 4.0 * std::f32::consts::PI;
+    // Pitch bend range, in semitones
+    pub const BEND_RANGE: f32 = 2.0;
+    pub const OUTPUT_GAIN: f32 = 6000.0;
+}
+
+// One operator of a GENMIDI voice, as the OPL registers
+#[derive(Debug, Clone, Copy)]
+pub struct OperatorData {
+    pub tremolo: bool,
+    pub vibrato: bool,
+    // The envelope holds on the sustain level until the key is released
+    pub sustained: bool,
+    pub multiplier: f32,
+    pub attack: u8,
+    pub decay: u8,
+    pub sustain: u8,
+    pub release: u8,
+    pub waveform: u8,
+    pub key_scale: u8,
+    // 0 to 63, in 0.75 dB steps
+    pub level: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VoiceData {
+    pub modulator: OperatorData,
+    pub carrier: OperatorData,
+    pub feedback: u8,
+    // The modulator is heard beside the carrier instead of modulating it
+    pub additive: bool,
+    pub base_note_offset: i16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Instrument {
+    pub flags: u16,
+    pub fine_tuning: u8,
+    pub fixed_note: u8,
+    pub voices: [VoiceData; 2],
+}
+
+impl OperatorData {
+    fn parse(data: &[u8]) -> Self {
+        OperatorData {
+            tremolo: data[0] & 0x80 != 0,
+            vibrato: data[0] & 0x40 != 0,
+            sustained: data[0] & 0x20 != 0,
+            multiplier: consts::MULTIPLIERS[(data[0] & 0x0F) as usize],
+            attack: data[1] >> 4,
+            decay: data[1] & 0x0F,
+            sustain: data[2] >> 4,
+            release: data[2] & 0x0F,
+            waveform: data[3] & 0x07,
+            key_scale: (data[4] >> 6) & 0x03,
+            level: data[5] & 0x3F,
+        }
+    }
+}
+
+impl VoiceData {
+    fn parse(data: &[u8]) -> Self {
+        VoiceData {
+            modulator: OperatorData::parse(&data[0..6]),
+            feedback: (data[6] >> 1) & 0x07,
+            additive: data[6] & 0x01 != 0,
+            carrier: OperatorData::parse(&data[7..13]),
+            base_note_offset: i16::from_le_bytes([data[14], data[15]]),
+        }
+    }
+}
+
+impl Instrument {
+    pub fn is_fixed_pitch(&self) -> bool {
+        self.flags & consts::FLAG_FIXED_PITCH != 0
+    }
+
+    pub fn is_double_voice(&self) -> bool {
+        self.flags & consts::FLAG_DOUBLE_VOICE != 0
+    }
+}
+
+// GENMIDI lump, the OPL instruments of the 128 General MIDI programs and of the percussion
+pub struct GenMidi {
+    pub instruments: Vec<Instrument>,
+}
+
+impl GenMidi {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if !data.starts_with(consts::GENMIDI_ID) {
+            return Err(String::from("Not a GENMIDI lump"));
+        }
+        let start = consts::GENMIDI_ID.len();
+        let end = start + consts::INSTRUMENT_COUNT * consts::INSTRUMENT_SIZE;
+        let data = data.get(start..end).ok_or(String::from("GENMIDI lump too short"))?;
+        let instruments = data.chunks_exact(consts::INSTRUMENT_SIZE).map(|data| {
+            let voice = 4 + consts::VOICE_SIZE;
+            Instrument {
+                flags: u16::from_le_bytes([data[0], data[1]]),
+                fine_tuning: data[2],
+                fixed_note: data[3],
+                voices: [VoiceData::parse(&data[4..voice]), VoiceData::parse(&data[voice..voice + consts::VOICE_SIZE])],
+            }
+        }).collect();
+        return Ok(GenMidi { instruments });
+    }
+
+    pub fn melodic(&self, program: u8) -> Option<&Instrument> {
+        self.instruments.get((program & 0x7F) as usize)
+    }
+
+    pub fn percussion(&self, note: u8) -> Option<&Instrument> {
+        if !(consts::PERCUSSION_FIRST_NOTE..=consts::PERCUSSION_LAST_NOTE).contains(&note) {
+            return None;
+        }
+        self.instruments.get(128 + (note - consts::PERCUSSION_FIRST_NOTE) as usize)
+    }
+}
+
+// OPL2 has the first 4 waveforms, OPL3 all 8
+fn waveform(waveform: u8, phase: f32) -> f32 {
+    let phase = phase.rem_euclid(1.0);
+    let sine = (phase * 2.0 * PI).sin();
+    match waveform {
+        // Half sine
+        1 => sine.max(0.0),
+        // Absolute sine
+        2 => sine.abs(),
+        // Quarter sine pulses
+        3 => if phase.rem_euclid(0.5) < 0.25 { sine.abs() } else { 0.0 },
+        // Alternating sine
+        4 => if phase < 0.5 { (phase * 4.0 * PI).sin() } else { 0.0 },
+        // Camel sine
+        5 => if phase < 0.5 { (phase * 4.0 * PI).sin().abs() } else { 0.0 },
+        // Square
+        6 => if phase < 0.5 { 1.0 } else { -1.0 },
+        // Derived square, an exponential ramp
+        7 => {
+            let ramp = (-(phase.rem_euclid(0.5)) * 16.0).exp2();
+            if phase < 0.5 { ramp } else { -ramp }
+        },
+        _ => sine
+    }
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(-db / 20.0)
+}
+
+// Seconds for a full envelope step at an OPL rate, None for the rate 0
+fn rate_time(base: f32, rate: u8) -> Option<f32> {
+    match rate {
+        0 => None,
+        rate => Some(base / (1u32 << (rate - 1)) as f32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EnvelopeState {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Off,
+}
+
+#[derive(Clone, Copy)]
+struct Operator {
+    data: OperatorData,
+    phase: f32,
+    state: EnvelopeState,
+    // Envelope attenuation in dB
+    attenuation: f32,
+    // Last outputs, for the feedback
+    outputs: [f32; 2],
+}
+
+impl Operator {
+    fn new(data: OperatorData) -> Self {
+        Operator {
+            data: data,
+            phase: 0.0,
+            state: EnvelopeState::Attack,
+            attenuation: consts::SILENCE_DB,
+            outputs: [0.0; 2],
+        }
+    }
+
+    fn sustain_level(&self) -> f32 {
+        // Level 15 is the bottom of the envelope
+        match self.data.sustain {
+            15 => consts::SILENCE_DB,
+            sustain => sustain as f32 * 3.0
+        }
+    }
+
+    fn key_off(&mut self) {
+        if self.state != EnvelopeState::Off {
+            self.state = EnvelopeState::Release;
+        }
+    }
+
+    fn update_envelope(&mut self, dt: f32) {
+        let decay_step = |rate: u8| rate_time(consts::DECAY_TIME, rate).map(|time| consts::SILENCE_DB * dt / time).unwrap_or(0.0);
+        match self.state {
+            EnvelopeState::Attack => {
+                match rate_time(consts::ATTACK_TIME, self.data.attack) {
+                    Some(time) if self.data.attack < 15 => self.attenuation -= consts::SILENCE_DB * dt / time,
+                    Some(_) => self.attenuation = 0.0,
+                    None => ()
+                }
+                if self.attenuation <= 0.0 {
+                    self.attenuation = 0.0;
+                    self.state = EnvelopeState::Decay;
+                }
+            },
+            EnvelopeState::Decay => {
+                self.attenuation += decay_step(self.data.decay);
+                if self.attenuation >= self.sustain_level() {
+                    self.attenuation = self.sustain_level();
+                    self.state = EnvelopeState::Sustain;
+                }
+            },
+            EnvelopeState::Sustain => {
+                // Percussive envelopes go on decaying with the release rate
+                if !self.data.sustained {
+                    self.attenuation += decay_step(self.data.release);
+                }
+            },
+            EnvelopeState::Release => self.attenuation += decay_step(self.data.release),
+            EnvelopeState::Off => ()
+        }
+        if self.attenuation >= consts::SILENCE_DB {
+            self.attenuation = consts::SILENCE_DB;
+            if self.state != EnvelopeState::Attack {
+                self.state = EnvelopeState::Off;
+            }
+        }
+    }
+
+    // One sample of the operator, the modulation is a phase shift
+    fn output(&mut self, frequency: f32, modulation: f32, level_db: f32, tremolo_db: f32, rate: f32) -> f32 {
+        let tremolo_db = if self.data.tremolo { tremolo_db } else { 0.0 };
+        let gain = db_to_gain(self.attenuation + level_db + tremolo_db);
+        let output = waveform(self.data.waveform, self.phase + modulation / (2.0 * PI)) * gain;
+        self.phase = (self.phase + frequency * self.data.multiplier / rate).rem_euclid(1.0);
+        self.outputs = [self.outputs[1], output];
+        return output;
+    }
+}
+
+// Channel state of the MIDI stream
+#[derive(Clone, Copy)]
+struct MidiChannel {
+    program: u8,
+    volume: u8,
+    // 0 left, 64 center, 127 right
+    pan: u8,
+    // -1 to 1 of the bend range
+    bend: f32,
+    sustain: bool,
+}
+
+impl MidiChannel {
+    fn new() -> Self {
+        MidiChannel {
+            program: 0,
+            volume: 100,
+            pan: 64,
+            bend: 0.0,
+            sustain: false,
+        }
+    }
+}
+
+struct Voice {
+    channel: usize,
+    // Key of the MIDI note, not the played one
+    key: u8,
+    velocity: u8,
+    // Note with the offset of the instrument and the fine tuning
+    note: f32,
+    data: VoiceData,
+    modulator: Operator,
+    carrier: Operator,
+    released: bool,
+    // Order of allocation, the oldest voice is stolen first
+    age: u64,
+}
+
+impl Voice {
+    fn is_done(&self) -> bool {
+        self.carrier.state == EnvelopeState::Off && (!self.data.additive || self.modulator.state == EnvelopeState::Off)
+    }
+
+    fn release(&mut self) {
+        self.released = true;
+        self.modulator.key_off();
+        self.carrier.key_off();
+    }
+
+    // Attenuation of the key scaling, higher notes are quieter
+    fn key_scale_db(&self, operator: &OperatorData) -> f32 {
+        let octave = (self.note / 12.0 - 1.0).max(0.0);
+        return consts::KEY_SCALE[operator.key_scale as usize] * octave;
+    }
+}
+
+// Software FM synth over the GENMIDI instruments, as the DMX OPL music driver.
+// OPL2 has 9 mono voices, OPL3 18 with the stereo panning.
+pub struct OplSynth {
+    bank: GenMidi,
+    rate: u32,
+    opl3: bool,
+    voice_count: usize,
+    voices: Vec<Voice>,
+    channels: [MidiChannel; 16],
+    // Last note volumes of the MUS channels
+    mus_volumes: [u8; 16],
+    // 0 to 15, as the menu
+    music_volume: i32,
+    time: f32,
+    age: u64,
+}
+
+impl OplSynth {
+    pub fn new(bank: GenMidi, rate: u32, opl3: bool) -> Self {
+        OplSynth {
+            bank: bank,
+            rate: rate.max(1),
+            opl3: opl3,
+            voice_count: if opl3 { consts::OPL3_VOICES } else { consts::OPL2_VOICES },
+            voices: vec![],
+            channels: [MidiChannel::new(); 16],
+            mus_volumes: [127; 16],
+            music_volume: 8,
+            time: 0.0,
+            age: 0,
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    pub fn is_opl3(&self) -> bool {
+        self.opl3
+    }
+
+    pub fn set_music_volume(&mut self, volume: i32) {
+        self.music_volume = volume.clamp(0, 15);
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.voices.clear();
+        self.channels = [MidiChannel::new(); 16];
+        self.mus_volumes = [127; 16];
+    }
+
+    // MUS events go through their MIDI messages
+    pub fn handle_mus(&mut self, event: &MusEvent) {
+        if let Some(message) = Mus::midi_message(event, &mut self.mus_volumes) {
+            self.handle_midi(&message);
+        }
+    }
+
+    // Channel voice message, the status byte first
+    pub fn handle_midi(&mut self, message: &[u8]) {
+        let (status, channel) = match message.first() {
+            Some(status) => (status & 0xF0, (status & 0x0F) as usize),
+            None => return
+        };
+        let data = |index: usize| message.get(index).copied().unwrap_or(0) & 0x7F;
+        match status {
+            0x80 => self.note_off(channel, data(1)),
+            0x90 if data(2) == 0 => self.note_off(channel, data(1)),
+            0x90 => self.note_on(channel, data(1), data(2)),
+            0xB0 => self.controller(channel, data(1), data(2)),
+            0xC0 => self.channels[channel].program = data(1),
+            0xE0 => {
+                let bend = ((data(2) as i32) << 7) | data(1) as i32;
+                self.channels[channel].bend = (bend - 8192) as f32 / 8192.0;
+            },
+            _ => ()
+        }
+    }
+
+    fn controller(&mut self, channel: usize, controller: u8, value: u8) {
+        match controller {
+            7 => self.channels[channel].volume = value,
+            10 => self.channels[channel].pan = value,
+            64 => {
+                self.channels[channel].sustain = value >= 64;
+                if value < 64 {
+                    self.voices.iter_mut().filter(|voice| voice.channel == channel && voice.released).for_each(|voice| voice.release());
+                }
+            },
+            // All sounds off
+            120 => self.voices.retain(|voice| voice.channel != channel),
+            // All notes off
+            123 => self.voices.iter_mut().filter(|voice| voice.channel == channel).for_each(|voice| voice.release()),
+            // Reset all controllers
+            121 => {
+                let program = self.channels[channel].program;
+                self.channels[channel] = MidiChannel { program, ..MidiChannel::new() };
+            },
+            _ => ()
+        }
+    }
+
+    fn note_off(&mut self, channel: usize, key: u8) {
+        let sustain = self.channels[channel].sustain;
+        for voice in self.voices.iter_mut().filter(|voice| voice.channel == channel && voice.key == key && !voice.released) {
+            if sustain {
+                voice.released = true;
+            } else {
+                voice.release();
+            }
+        }
+    }
+
+    fn note_on(&mut self, channel: usize, key: u8, velocity: u8) {
+        let instrument = if channel == consts::PERCUSSION_CHANNEL {
+            self.bank.percussion(key)
+        } else {
+            self.bank.melodic(self.channels[channel].program)
+        };
+        let instrument = match instrument {
+            Some(instrument) => *instrument,
+            None => return
+        };
+        let note = if instrument.is_fixed_pitch() { instrument.fixed_note } else { key };
+        let voices = if instrument.is_double_voice() { 2 } else { 1 };
+        for voice_id in 0..voices {
+            let data = instrument.voices[voice_id];
+            let mut note = note as f32 + data.base_note_offset as f32;
+            // The second voice is detuned in 1/32 of semitone
+            if voice_id == 1 {
+                note += (instrument.fine_tuning as f32 / 2.0 - 64.0) / 32.0;
+            }
+            self.allocate(Voice {
+                channel: channel,
+                key: key,
+                velocity: velocity,
+                note: note,
+                data: data,
+                modulator: Operator::new(data.modulator),
+                carrier: Operator::new(data.carrier),
+                released: false,
+                age: self.age,
+            });
+            self.age += 1;
+        }
+    }
+
+    // Out of voices the oldest released one is stolen, else the oldest
+    fn allocate(&mut self, voice: Voice) {
+        if self.voices.len() >= self.voice_count {
+            let stolen = self.voices.iter()
+                .enumerate()
+                .min_by_key(|(_, voice)| (!voice.released, voice.age))
+                .map(|(id, _)| id);
+            if let Some(stolen) = stolen {
+                self.voices.remove(stolen);
+            }
+        }
+        self.voices.push(voice);
+    }
+
+    // Adds the next frames to the interleaved left and right buffer
+    pub fn mix(&mut self, output: &mut [i16]) {
+        let rate = self.rate as f32;
+        let dt = 1.0 / rate;
+        let music_gain = self.music_volume as f32 / 15.0 * consts::OUTPUT_GAIN;
+        for frame in output.chunks_exact_mut(2) {
+            self.time += dt;
+            let tremolo_db = (1.0 + (self.time * consts::TREMOLO_RATE * 2.0 * PI).sin()) * consts::TREMOLO_DEPTH_DB / 2.0;
+            let vibrato = (self.time * consts::VIBRATO_RATE * 2.0 * PI).sin() * consts::VIBRATO_DEPTH;
+            let (mut left, mut right) = (0.0, 0.0);
+            for voice in self.voices.iter_mut() {
+                let channel = &self.channels[voice.channel];
+                let note = voice.note + channel.bend * consts::BEND_RANGE;
+                let frequency = |vibrato_on: bool| {
+                    let note = note + if vibrato_on { vibrato } else { 0.0 };
+                    440.0 * ((note - 69.0) / 12.0).exp2()
+                };
+                let gain = (voice.velocity as f32 / 127.0) * (channel.volume as f32 / 127.0);
+                // The feedback modulates the modulator with its own last outputs
+                let feedback = match voice.data.feedback {
+                    0 => 0.0,
+                    feedback => (voice.modulator.outputs[0] + voice.modulator.outputs[1]) / 2.0 * PI * (1u32 << (feedback - 1)) as f32 / 16.0
+                };
+                let modulator_db = voice.modulator.data.level as f32 * 0.75 + voice.key_scale_db(&voice.modulator.data);
+                let carrier_db = voice.carrier.data.level as f32 * 0.75 + voice.key_scale_db(&voice.carrier.data);
+                voice.modulator.update_envelope(dt);
+                voice.carrier.update_envelope(dt);
+                let modulator = voice.modulator.output(frequency(voice.modulator.data.vibrato), feedback, modulator_db, tremolo_db, rate);
+                let sample = if voice.data.additive {
+                    modulator + voice.carrier.output(frequency(voice.carrier.data.vibrato), 0.0, carrier_db, tremolo_db, rate)
+                } else {
+                    voice.carrier.output(frequency(voice.carrier.data.vibrato), modulator * consts::MODULATION_DEPTH, carrier_db, tremolo_db, rate)
+                };
+                let sample = sample * gain;
+                // OPL3 routes each voice to the left, the right or both outputs
+                match (self.opl3, channel.pan) {
+                    (true, pan) if pan < 48 => left += sample,
+                    (true, pan) if pan > 80 => right += sample,
+                    _ => {
+                        left += sample;
+                        right += sample;
+                    }
+                }
+            }
+            frame[0] = (frame[0] as f32 + left * music_gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            frame[1] = (frame[1] as f32 + right * music_gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+        self.voices.retain(|voice| !voice.is_done());
+    }
+
+    // Offline rendering of a whole song, played once, as interleaved stereo
+    pub fn render_song(&mut self, song: &Mus) -> Vec<i16> {
+        self.reset();
+        let mut output = vec![];
+        let mut pending = 0.0;
+        for timed in song.events.iter() {
+            self.handle_mus(&timed.event);
+            pending += timed.delay as f32 * self.rate as f32 / Mus::tic_rate() as f32;
+            let frames = pending as usize;
+            pending -= frames as f32;
+            let start = output.len();
+            output.resize(start + frames * 2, 0);
+            self.mix(&mut output[start..]);
+        }
+        // Release tail
+        self.voices.iter_mut().for_each(|voice| voice.release());
+        let start = output.len();
+        output.resize(start + self.rate as usize * 2, 0);
+        self.mix(&mut output[start..]);
+        return output;
+    }
+}
@@ -0,0 +1,63 @@
+#![allow(dead_code)]
+// Engine
+use crate::data_textures::Palette;
+use crate::window::DoomSurface;
+
+// Blends the frame toward the tint of a PLAYPAL palette, as I_SetPalette does
+// for the damage, bonus and radiation suit flashes
+pub struct PaletteShift {
+    // Per channel table of each palette, from the first palette value to the tinted one
+    tints: Vec<[[u8; 256]; 3]>,
+}
+
+impl PaletteShift {
+    pub fn new(palettes: &[&Palette]) -> Self {
+        let tints = match palettes.first() {
+            Some(base) => palettes.iter().map(|palette| PaletteShift::tint(base, palette)).collect(),
+            None => vec![]
+        };
+        PaletteShift { tints }
+    }
+
+    // The flash palettes are the first one moved toward a color, c + (tint - c) * f,
+    // so each channel is fit as a * c + b over the 256 entries
+    fn tint(base: &Palette, palette: &Palette) -> [[u8; 256]; 3] {
+        let mut tables = [[0u8; 256]; 3];
+        for channel in 0..3 {
+            let pairs: Vec<(f32, f32)> = base.into_iter()
+                .zip(palette)
+                .map(|(from, to)| (from[channel] as f32, to[channel] as f32))
+                .collect();
+            let count = pairs.len().max(1) as f32;
+            let mean_x = pairs.iter().map(|(x, _)| x).sum::<f32>() / count;
+            let mean_y = pairs.iter().map(|(_, y)| y).sum::<f32>() / count;
+            let covariance = pairs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum::<f32>();
+            let variance = pairs.iter().map(|(x, _)| (x - mean_x) * (x - mean_x)).sum::<f32>();
+            let a = if variance > 0.0 { covariance / variance } else { 1.0 };
+            let b = mean_y - a * mean_x;
+            for (value, entry) in tables[channel].iter_mut().enumerate() {
+                *entry = (a * value as f32 + b).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+        return tables;
+    }
+
+    pub fn count(&self) -> usize {
+        self.tints.len()
+    }
+
+    pub fn apply(&self, surface: &mut DoomSurface, palette_id: usize) {
+        if palette_id == 0 {
+            return;
+        }
+        let tables = match self.tints.get(palette_id) {
+            Some(tables) => tables,
+            None => return
+        };
+        for pixel in surface.pixels.frame_mut().chunks_exact_mut(4) {
+            pixel[0] = tables[0][pixel[0] as usize];
+            pixel[1] = tables[1][pixel[1] as usize];
+            pixel[2] = tables[2][pixel[2] as usize];
+        }
+    }
+}
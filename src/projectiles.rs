@@ -0,0 +1,372 @@
+#![allow(dead_code)]
+// Engine
+use crate::actors::{actor_id, Actor, Transform};
+use crate::attack::aim_line_attack;
+use crate::collision::CollisionClass;
+use crate::data_textures::is_sky_texture;
+use crate::doom::Doom;
+use crate::math::{radians, Vector2};
+use crate::time::{tics, TIC_RATE};
+use crate::trace::{check_sight, line_opening, path_traverse, InterceptKind, Trace};
+use crate::types::ThingType;
+// Utils
+
+mod consts {
+    pub const MISSILE_RANGE: f32 = 32.0 * 64.0;
+    pub const AUTOAIM_ANGLE: f32 = 5.625;
+    pub const BFG_RAYS: u32 = 40;
+    pub const BFG_SPREAD: f32 = 90.0;
+    pub const BFG_RAY_DAMAGE_ROLLS: u32 = 15;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectileType {
+    ImpFireball,
+    Rocket,
+    Plasma,
+    BFG
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectileState {
+    Flying,
+    Exploding
+}
+
+impl ProjectileType {
+    // Units per tic
+    pub fn speed(&self) -> f32 {
+        match self {
+            ProjectileType::ImpFireball => 10.0,
+            ProjectileType::Rocket => 20.0,
+            ProjectileType::Plasma => 25.0,
+            ProjectileType::BFG => 25.0,
+        }
+    }
+
+    // Direct hit damage, multiplied by 1d8
+    pub fn damage(&self) -> i32 {
+        match self {
+            ProjectileType::ImpFireball => 3,
+            ProjectileType::Rocket => 20,
+            ProjectileType::Plasma => 5,
+            ProjectileType::BFG => 100,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        match self {
+            ProjectileType::ImpFireball => 6.0,
+            ProjectileType::Rocket => 11.0,
+            ProjectileType::Plasma => 13.0,
+            ProjectileType::BFG => 13.0,
+        }
+    }
+
+    pub fn height(&self) -> f32 {
+        8.0
+    }
+
+    // Damage dealt around the explosion point, if any
+    pub fn splash_damage(&self) -> i32 {
+        match self {
+            ProjectileType::Rocket => 128,
+            _ => 0
+        }
+    }
+
+    pub fn explosion_tics(&self) -> u32 {
+        match self {
+            ProjectileType::ImpFireball => 18,
+            ProjectileType::Rocket => 18,
+            ProjectileType::Plasma => 20,
+            ProjectileType::BFG => 48,
+        }
+    }
+
+    pub fn sprite(&self) -> &'static [u8; 4] {
+        match self {
+            ProjectileType::ImpFireball => b"BAL1",
+            ProjectileType::Rocket => b"MISL",
+            ProjectileType::Plasma => b"PLSS",
+            ProjectileType::BFG => b"BFS1",
+        }
+    }
+}
+
+pub struct Projectile {
+    projectile_type: ProjectileType,
+    state: ProjectileState,
+    state_time: f32,
+    // Transformation
+    transform: Transform,
+    last_transform: Transform,
+    // Units per second
+    velocity: Vector2<f32>,
+    velocity_z: f32,
+    // Who fired it, see actors::actor_id
+    owner: usize,
+    owner_angle: f32,
+}
+
+impl Projectile {
+    fn new(projectile_type: ProjectileType, owner: &dyn Actor, position: &Vector2<f32>, z: f32, angle: f32, slope: f32) -> Box<dyn Actor> {
+        let speed = projectile_type.speed() * TIC_RATE;
+        let direction = Vector2::new(radians(angle).cos(), radians(angle).sin());
+        let transform = Transform::set(*position, angle, z);
+        Box::new(Projectile {
+            projectile_type: projectile_type,
+            state: ProjectileState::Flying,
+            state_time: 0.0,
+            last_transform: transform.clone(),
+            transform: transform,
+            velocity: direction * speed,
+            velocity_z: slope * speed,
+            owner: actor_id(owner),
+            owner_angle: owner.angle(),
+        })
+    }
+
+    // Monster missile aimed at a target, as P_SpawnMissile
+    pub fn spawn_missile<'wad>(engine: &Doom<'wad>, projectile_type: ProjectileType, source: &dyn Actor, target: &dyn Actor) {
+        let z = source.floor_z() + 32.0;
+        let delta = *target.position() - *source.position();
+        let angle = delta.y.atan2(delta.x).to_degrees();
+        let distance = delta.magnitude().max(1.0);
+        let slope = (target.floor_z() - source.floor_z()) / distance;
+        engine.spawn(Projectile::new(projectile_type, source, source.position(), z, angle, slope));
+    }
+
+    // Player missile with autoaim, as P_SpawnPlayerMissile
+    pub fn spawn_player_missile<'wad>(engine: &Doom<'wad>, projectile_type: ProjectileType, source: &dyn Actor) {
+        let mut angle = source.angle();
+        let mut slope = 0.0;
+        for offset in [0.0, consts::AUTOAIM_ANGLE, -consts::AUTOAIM_ANGLE] {
+            let aim = aim_line_attack(engine, source, source.angle() + offset, consts::MISSILE_RANGE);
+            if aim.target.is_some() {
+                angle = source.angle() + offset;
+                slope = aim.slope;
+                break;
+            }
+        }
+        let z = source.floor_z() + 32.0;
+        engine.spawn(Projectile::new(projectile_type, source, source.position(), z, angle, slope));
+    }
+
+    pub fn projectile_type(&self) -> ProjectileType {
+        self.projectile_type
+    }
+
+    pub fn state(&self) -> ProjectileState {
+        self.state
+    }
+
+    fn explode<'wad>(&mut self, engine: &Doom<'wad>) {
+        self.state = ProjectileState::Exploding;
+        self.state_time = tics(self.projectile_type.explosion_tics());
+        self.velocity = Vector2::zeros();
+        self.velocity_z = 0.0;
+        // Explosions randomly shorten, as in P_ExplodeMissile
+        self.state_time = (self.state_time - tics((engine.random.borrow_mut().next() & 3) as u32)).max(tics(1));
+        // Splash
+        let splash_damage = self.projectile_type.splash_damage();
+        if splash_damage > 0 {
+            radius_attack(engine, self, splash_damage);
+        }
+        if self.projectile_type == ProjectileType::BFG {
+            self.bfg_spray(engine);
+        }
+    }
+
+    // Rays in a cone from the shooter, as A_BFGSpray
+    fn bfg_spray<'wad>(&self, engine: &Doom<'wad>) {
+        let owner = match engine.find_actor(self.owner) {
+            Some(owner) => owner,
+            None => return
+        };
+        let owner = owner.borrow();
+        for ray in 0..consts::BFG_RAYS {
+            let angle = self.owner_angle - consts::BFG_SPREAD / 2.0
+                      + consts::BFG_SPREAD / consts::BFG_RAYS as f32 * ray as f32;
+            let aim = aim_line_attack(engine, owner.as_ref(), angle, 16.0 * 64.0);
+            if let Some(target) = aim.target {
+                let mut damage = 0;
+                for _ in 0..consts::BFG_RAY_DAMAGE_ROLLS {
+                    damage += (engine.random.borrow_mut().next() & 7) + 1;
+                }
+                target.borrow_mut().damage(engine, damage);
+            }
+        }
+    }
+
+    fn hit_actor<'wad>(&mut self, engine: &Doom<'wad>, target: &mut dyn Actor) {
+        let damage = ((engine.random.borrow_mut().next() % 8) + 1) * self.projectile_type.damage();
+        target.damage(engine, damage);
+    }
+
+    // Move along the velocity, returns false if something has been hit
+    fn try_move<'wad>(&mut self, engine: &Doom<'wad>, last_frame_time: f32) -> bool {
+        let from = self.transform.position;
+        let to = from + self.velocity * last_frame_time;
+        let z = self.transform.height + self.velocity_z * last_frame_time;
+        let trace = Trace::new(&from, &to);
+        for intercept in path_traverse(&engine.map, &engine.actors, &trace, true, true) {
+            match intercept.kind {
+                InterceptKind::Line(line) => {
+                    let blocked = match line_opening(&engine.map, line) {
+                        Some(opening) => z < opening.bottom || z + self.projectile_type.height() > opening.top,
+                        None => true
+                    };
+                    if blocked {
+                        self.transform.position = trace.point(intercept.fraction);
+                        // Missiles hitting the sky just vanish
+                        if let Some(back) = line.back_sector(&engine.map) {
+                            if is_sky_texture(&back.ceiling_texture) && z > back.ceiling_height as f32 {
+                                self.state = ProjectileState::Exploding;
+                                self.state_time = 0.0;
+                                return false;
+                            }
+                        }
+                        self.explode(engine);
+                        return false;
+                    }
+                }
+                InterceptKind::Thing(rc_actor) => {
+                    let mut actor = rc_actor.borrow_mut();
+                    if actor_id(actor.as_ref()) == self.owner
+                    || !actor.collision_class().blocks_movement()
+                    || actor.health() <= 0 {
+                        continue;
+                    }
+                    // Flying over or under
+                    if z > actor.floor_z() + actor.body_height()
+                    || z + self.projectile_type.height() < actor.floor_z() {
+                        continue;
+                    }
+                    self.transform.position = trace.point(intercept.fraction);
+                    self.hit_actor(engine, actor.as_mut());
+                    drop(actor);
+                    self.explode(engine);
+                    return false;
+                }
+            }
+        }
+        self.transform.position = to;
+        self.transform.height = z;
+        // Floor and ceiling
+        let position = self.transform.position_as_int();
+        if let Some(sector) = engine.bsp.sector(&position) {
+            if z <= sector.floor_height as f32 {
+                self.transform.height = sector.floor_height as f32;
+                self.explode(engine);
+                return false;
+            }
+            if z + self.projectile_type.height() >= sector.ceiling_height as f32 {
+                if is_sky_texture(&sector.ceiling_texture) {
+                    self.state = ProjectileState::Exploding;
+                    self.state_time = 0.0;
+                } else {
+                    self.transform.height = sector.ceiling_height as f32 - self.projectile_type.height();
+                    self.explode(engine);
+                }
+                return false;
+            }
+        }
+        return true;
+    }
+}
+
+// Damage every shootable actor in range that can see the blast, as P_RadiusAttack
+pub fn radius_attack<'wad>(engine: &Doom<'wad>, spot: &dyn Actor, damage: i32) {
+    let position = *spot.position();
+    let z = spot.height();
+    for rc_actor in engine.actors.iter() {
+        if let Ok(mut actor) = rc_actor.try_borrow_mut() {
+            if !actor.collision_class().is_shootable() || actor.health() <= 0 {
+                continue;
+            }
+            // Boss monsters are immune to splash damage
+            if matches!(actor.thing_type(), ThingType::Cyberdemon | ThingType::SpiderDemon) {
+                continue;
+            }
+            let delta = *actor.position() - position;
+            let distance = (delta.x.abs().max(delta.y.abs()) - actor.size() as f32).max(0.0);
+            if distance >= damage as f32 {
+                continue;
+            }
+            let target_z = actor.floor_z() + actor.body_height() / 2.0;
+            if check_sight(&engine.map, &position, z, actor.position(), target_z) {
+                let amount = damage - distance as i32;
+                actor.damage(engine, amount);
+            }
+        }
+    }
+}
+
+#[allow(unused_variables)]
+impl Actor for Projectile {
+    fn update<'wad>(&mut self, engine: &Doom<'wad>, last_frame_time: f64, blending_factor: f64) {
+        let last_frame_time = last_frame_time as f32;
+        self.last_transform = self.transform.clone();
+        match self.state {
+            ProjectileState::Flying => {
+                self.try_move(engine, last_frame_time);
+            }
+            ProjectileState::Exploding => {
+                self.state_time -= last_frame_time;
+            }
+        }
+    }
+
+    fn type_id(&self) -> u16 {
+        ThingType::Unknown.id()
+    }
+
+    fn thing_type(&self) -> ThingType {
+        ThingType::Unknown
+    }
+
+    fn collision_class(&self) -> CollisionClass {
+        CollisionClass::Projectile
+    }
+
+    fn flags(&self) -> u16 {
+        0
+    }
+
+    fn size(&self) -> u16 {
+        self.projectile_type.radius() as u16
+    }
+
+    fn position(&self) -> &Vector2<f32> {
+        &self.transform.position
+    }
+
+    fn angle(&self) -> f32 {
+        self.transform.angle()
+    }
+
+    fn height(&self) -> f32 {
+        self.transform.height()
+    }
+
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn get_last_transform(&self) -> &Transform {
+        &self.last_transform
+    }
+
+    fn set_transform(&mut self, transform: &Transform) {
+        self.transform = transform.clone();
+    }
+
+    fn body_height(&self) -> f32 {
+        self.projectile_type.height()
+    }
+
+    fn is_removed(&self) -> bool {
+        self.state == ProjectileState::Exploding && self.state_time <= 0.0
+    }
+}
@@ -409,6 +409,10 @@ pub mod render_2d {
                 for x in start_x..end_x {
                     let texture_x = x - start_x;
                     let texture_y = y - start_y;
+                    if surface.is_indexed() {
+                        surface.draw_index_lt(&Vector2::new(x, y), texture.index(texture_x as u16, texture_y as u16));
+                        continue;
+                    }
                     surface.draw_lt(
                         &Vector2::new(x, y), 
                         &texture.colors[texture_y * texture.size.width() as usize + texture_x]
@@ -455,7 +459,8 @@ pub mod render_3d {
     use crate::math::{Vector2, radians};
     use crate::shape::Size;
     use crate::window::DoomSurface;
-    use crate::data_textures::{Texture, DataTextures, is_sky_texture, remap_sky_texture};
+    use crate::data_textures::{ColorMap, Texture, DataTextures, is_sky_texture, remap_sky_texture};
+    use crate::weapons::Psprite;
     // For simd
     #[cfg(all(target_arch = "x86_64", target_feature = "sse4.1"))]
     use std::arch::x86_64::*;
@@ -522,6 +527,8 @@ pub mod render_3d {
         data_textures: Rc<DataTextures<'wad>>,
         size: Vector2<i32>,
         h_size: Vector2<f32>,
+        // Row of the horizon, moved by the vertical look
+        horizon: f32,
         offset: Vector2<i32>,
         camera: Camera,
         screen_range: Vec<bool>,
@@ -529,7 +536,9 @@ pub mod render_3d {
         lower_clip: Box<Vec<i32>>,
         mask_walls: Vec<MaskWall<'wad>>,
         sky_inv_scale: f32,
-        sky_texture_alt: i16
+        sky_texture_alt: i16,
+        // Lines drawn in the frame, for the automap
+        drawn_lines: Vec<usize>
     }
     #[inline(always)]
     fn circular_tex(value: f32, size: u16) -> u16 {
@@ -544,6 +553,7 @@ pub mod render_3d {
                 data_textures: data_textures.clone(),
                 size: size,
                 h_size: Vector2::<f32>::from(&size) * 0.5,
+                horizon: size.height() as f32 * 0.5,
                 offset: offset,
                 camera: Camera::new(configure.fov, size.width().try_into().unwrap()),
                 screen_range: vec![false; size.width() as usize],
@@ -552,6 +562,7 @@ pub mod render_3d {
                 mask_walls: vec![],
                 sky_inv_scale : consts::SKY_SCALE / size.width() as f32,
                 sky_texture_alt : consts::SKY_ALT,
+                drawn_lines: vec![],
             }.preprocessing()
         }
 
@@ -670,6 +681,7 @@ pub mod render_3d {
             self.upper_clip.fill(0);
             self.lower_clip.fill(self.size.height());
             self.mask_walls.clear();
+            self.drawn_lines.clear();
         }
 
         fn name_to_color(array: &[u8; 8], mut light_level: &f32) -> [u8; 4] {
@@ -738,6 +750,14 @@ pub mod render_3d {
             rgba
         }
 
+        // Light map of the indexed pipeline, None when drawing RGBA
+        fn indexed_colormap(data_textures: &DataTextures<'wad>, surface: &DoomSurface, light_level: f32) -> Option<&'wad ColorMap> {
+            if surface.is_indexed() {
+                return data_textures.colormap(light_level);
+            }
+            return None;
+        }
+
         fn draw_line(&self, surface: &mut DoomSurface, x: i32, mut y1: i32, mut y2: i32, color: &[u8]) {
             y1 = math::clamp(y1, 0, self.size.height());
             y2 = math::clamp(y2, 0, self.size.height());
@@ -765,7 +785,15 @@ pub mod render_3d {
                 x += self.offset.x;
                 y1 += self.offset.y;
                 y2 += self.offset.y;
-                let mut v: f32 = texture_alt as f32 + ((y1 as f32 - self.h_size.height()) * inv_scale);
+                let mut v: f32 = texture_alt as f32 + ((y1 as f32 - self.horizon) * inv_scale);
+                if let Some(colormap) = RenderSoftware::indexed_colormap(&self.data_textures, surface, light_level) {
+                    for y in y1..y2 {
+                        let index = tex.index(u, circular_tex(v, tex.size.height()));
+                        surface.draw_index_lt(&Vector2::new(x as usize, y as usize), colormap[index as usize]);
+                        v += inv_scale;
+                    }
+                    return;
+                }
                 for y in y1..y2 {
                     let mut color = tex.get(u, circular_tex(v, tex.size.height())).clone();
                     surface.draw_lt(
@@ -794,14 +822,22 @@ pub mod render_3d {
                 x += self.offset.x;
                 y1 += self.offset.y;
                 y2 += self.offset.y;
-                let mut v: f32 = texture_alt as f32 + ((y1 as f32 - self.h_size.height()) * inv_scale);
+                let mut v: f32 = texture_alt as f32 + ((y1 as f32 - self.horizon) * inv_scale);
+                let colormap = RenderSoftware::indexed_colormap(&self.data_textures, surface, light_level);
                 for y in y1..y2 {
-                    let mut color = tex.get(u, circular_tex(v, tex.size.height())).clone();
-                    if color != [0; C] {
-                        surface.draw_lt(
-                        &Vector2::new(x as usize, y as usize), 
-                        RenderSoftware::apply_light_to_color(&mut color, light_level)
-                        );
+                    let tex_v = circular_tex(v, tex.size.height());
+                    if let Some(colormap) = colormap {
+                        if tex.is_opaque(u, tex_v) {
+                            surface.draw_index_lt(&Vector2::new(x as usize, y as usize), colormap[tex.index(u, tex_v) as usize]);
+                        }
+                    } else {
+                        let mut color = tex.get(u, tex_v).clone();
+                        if color != [0; C] {
+                            surface.draw_lt(
+                            &Vector2::new(x as usize, y as usize), 
+                            RenderSoftware::apply_light_to_color(&mut color, light_level)
+                            );
+                        }
                     }
                     v += inv_scale;
                 }
@@ -825,9 +861,10 @@ pub mod render_3d {
                 let player_dir_x = player_anglese_rad.cos();
                 let player_dir_y = player_anglese_rad.sin();
                 let world_z_float = world_z as f32;
+                let colormap = RenderSoftware::indexed_colormap(&self.data_textures, surface, light_level);
         
                 for iy in y1..y2 {
-                    let z = self.h_size.width() * world_z_float / (self.h_size.height() - iy as f32);
+                    let z = self.h_size.width() * world_z_float / (self.horizon - iy as f32);
         
                     let px = player_dir_x * z + player_pos.x;
                     let py = player_dir_y * z + player_pos.y;
@@ -842,6 +879,11 @@ pub mod render_3d {
         
                     let tx = circular_tex(left_x + dx * x as f32, tex.size.width());
                     let ty = circular_tex(left_y + dy * x as f32, tex.size.height());
+
+                    if let Some(colormap) = colormap {
+                        surface.draw_index_lt(&Vector2::new(x as usize, iy as usize), colormap[tex.index(tx, ty) as usize]);
+                        continue;
+                    }
         
                     let mut color = tex.get(tx, ty).clone();
 
@@ -865,7 +907,7 @@ pub mod render_3d {
                     let position = actor.position();
                     let height = actor.get_transform().height_as_int();
                     let start_vertex = Vector2::<f32>::from( seg.start_vertex(&self.map) );
-                    let half_height = self.h_size.height();
+                    let half_height = self.horizon;
                     // Texture
                     let light_level = seg_ex.light_level;
                     // Get texture
@@ -1036,7 +1078,7 @@ pub mod render_3d {
                     let position = actor.position();
                     let height = actor.get_transform().height_as_int();
                     let start_vertex = Vector2::<f32>::from( seg.start_vertex(&self.map) );
-                    let half_height = self.h_size.height();
+                    let half_height = self.horizon;
                     // Get texture
                     let ceiling_texture = seg_ex.ceiling_texture_id.and_then(|id| self.data_textures.flat_id(id));
                     let upper_texture = seg_ex.upper_texture_id.and_then(|id| self.data_textures.texture_id(id));
@@ -1410,7 +1452,7 @@ pub mod render_3d {
              let sector = seg.front_sector(&self.map).unwrap();
              let angle = actor.angle();
              let height = actor.get_transform().height_as_int();
-             let half_height = self.h_size.height();
+             let half_height = self.horizon;
              // Texture
              let light_level = seg_ex.light_level;
              // Get texture
@@ -1507,6 +1549,7 @@ pub mod render_3d {
                     xe += 1;
                 }
                 if (xe - xs) > 0 {
+                    self.drawn_lines.push(seg_ex.seg.line_def_id as usize);
                     self.draw_wall(actor, surface, &seg_ex, xs, xe, wall_angle);
                     xs = xe + 1;
                 } else {
@@ -1555,6 +1598,9 @@ pub mod render_3d {
             // Draw player 1
             match doom.actors.iter().find(|&actor| actor.as_ref().borrow().type_id() == 1) {
                 Some(actor) => {
+                    // Y-shearing, the horizon moves as the projection of the pitch
+                    let pitch = actor.as_ref().borrow().as_player().map(|player| player.pitch()).unwrap_or(0.0);
+                    render.horizon = render.h_size.height() + render.h_size.width() * radians(pitch).tan();
                     // Draw wall
                     bsp.visit(
                         &actor.as_ref().borrow().get_transform().position_as_int(), 
@@ -1569,8 +1615,87 @@ pub mod render_3d {
                 },
                 None => ()
             } 
+            // Mark the drawn lines as seen
+            for line_id in render.drawn_lines.iter() {
+                doom.automap.mark_seen(*line_id);
+            }
+        }
+
+    }
+
+    // Render the player weapon on top of the 3D view
+    pub struct RenderWeapon<'wad> {
+        data_textures: Rc<DataTextures<'wad>>,
+        size: Vector2<i32>,
+        offset: Vector2<i32>,
+    }
+
+    impl<'wad> RenderWeapon<'wad> {
+        pub fn new(data_textures: &Rc<DataTextures<'wad>>, size: Vector2<i32>, offset: Vector2<i32>) -> Self {
+            RenderWeapon {
+                data_textures: data_textures.clone(),
+                size: size,
+                offset: offset,
+            }
         }
 
+        // Psprites live in a 320x200 screen stretched over the view, as in R_DrawPSprite
+        fn draw_psprite(&self, surface: &mut DoomSurface, psprite: &Psprite, light_level: f32) {
+            let texture = match self.data_textures.sprite(&psprite.name) {
+                Some(texture) => texture,
+                None => return
+            };
+            let origin = self.data_textures.sprite_offset(&psprite.name).unwrap_or(Vector2::new(0, 0));
+            let scale = Vector2::new(self.size.width() as f32 / 320.0, self.size.height() as f32 / 200.0);
+            let x1 = (psprite.x - origin.x as f32) * scale.x;
+            let y1 = (psprite.y - origin.y as f32) * scale.y;
+            let x2 = x1 + texture.size.width() as f32 * scale.x;
+            let y2 = y1 + texture.size.height() as f32 * scale.y;
+            let start_x = x1.max(0.0) as i32;
+            let end_x = (x2.ceil() as i32).min(self.size.width());
+            let start_y = y1.max(0.0) as i32;
+            let end_y = (y2.ceil() as i32).min(self.size.height());
+            let colormap = RenderSoftware::indexed_colormap(&self.data_textures, surface, light_level);
+            for y in start_y..end_y {
+                let v = (((y as f32 - y1) / scale.y) as u16).min(texture.size.height() - 1);
+                for x in start_x..end_x {
+                    let u = (((x as f32 - x1) / scale.x) as u16).min(texture.size.width() - 1);
+                    let position = Vector2::new(x, y) + self.offset;
+                    if let Some(colormap) = colormap {
+                        if texture.is_opaque(u, v) {
+                            surface.draw_index_lt(&Vector2::new(position.x as usize, position.y as usize), colormap[texture.index(u, v) as usize]);
+                        }
+                        continue;
+                    }
+                    let mut color = texture.get(u, v).clone();
+                    if color[3] == 0 {
+                        continue;
+                    }
+                    surface.draw_lt(
+                        &Vector2::new(position.x as usize, position.y as usize),
+                        RenderSoftware::apply_light_to_color(&mut color, light_level)
+                    );
+                }
+            }
+        }
     }
 
-}
\ No newline at end of file
+    impl crate::render::Render for RenderWeapon<'_> {
+        fn draw<'wad>(&mut self, doom: &mut Doom<'wad>, _last_frame_time: f64, _blending_factor: f64) {
+            if let Some(actor) = doom.player() {
+                let actor = actor.borrow();
+                if let Some(player) = actor.as_player() {
+                    let light_level = doom.bsp
+                        .sector(&player.get_transform().position_as_int())
+                        .and_then(|sector| Some(math::clamp(sector.light_level as f32 / 255.0, 0.0, 1.0)))
+                        .unwrap_or(1.0);
+                    let surface = doom.surface.clone();
+                    for psprite in player.weapon().psprites() {
+                        let light_level = if psprite.full_bright { 1.0 } else { light_level };
+                        self.draw_psprite(&mut surface.borrow_mut(), &psprite, light_level);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+#![allow(dead_code)]
+use ini::Ini;
+// Engine
+use crate::actors::{Actor, ArmorType, Transform};
+use crate::doom::Doom;
+use crate::inventory::{AmmoType, KeyType, WeaponType};
+use crate::math::Vector2;
+use crate::types::Skill;
+
+mod consts {
+    pub const DIRECTORY: &str = "savegames";
+}
+
+// Same count as the vanilla load and save menus
+pub const SAVE_SLOTS: usize = 6;
+
+pub fn slot_path(slot: usize) -> String {
+    format!("{}/doomsav{}.dsg", consts::DIRECTORY, slot)
+}
+
+// Description shown in the load and save menus, None for an empty slot
+pub fn slot_description(slot: usize) -> Option<String> {
+    let ini = Ini::load_from_file(slot_path(slot)).ok()?;
+    return ini.section(Some("Game"))?.get("description").map(String::from);
+}
+
+fn join<T: ToString>(values: impl Iterator<Item = T>) -> String {
+    values.map(|value| value.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn split<T: std::str::FromStr>(value: Option<&str>) -> Vec<T> {
+    value.unwrap_or("").split(',').filter_map(|part| part.trim().parse().ok()).collect()
+}
+
+// The map things are saved by their index, the ones taken or removed are left out
+pub fn save_game(engine: &Doom, slot: usize, description: &str) -> Result<(), String> {
+    let actor = engine.player().ok_or("No player to save")?;
+    let actor = actor.borrow();
+    let player = actor.as_player().ok_or("No player to save")?;
+    let inventory = player.inventory();
+    let mut ini = Ini::new();
+    ini.with_section(Some("Game"))
+        .set("description", description)
+        .set("map", engine.map_name())
+        .set("skill", engine.skill.index().to_string());
+    ini.with_section(Some("Player"))
+        .set("position", join([actor.position().x, actor.position().y].iter()))
+        .set("angle", actor.angle().to_string())
+        .set("health", player.health().to_string())
+        .set("armor", player.armor().to_string())
+        .set("armor_type", (player.armor_type() as usize).to_string())
+        .set("weapon", player.weapon().weapon().index().to_string());
+    ini.with_section(Some("Inventory"))
+        .set("backpack", inventory.has_backpack().to_string())
+        .set("ammo", join(AmmoType::ALL.iter().map(|ammo| inventory.ammo(*ammo))))
+        .set("weapons", join(WeaponType::ALL.iter().map(|weapon| inventory.has_weapon(*weapon) as u8)))
+        .set("keys", join(KeyType::ALL.iter().map(|key| inventory.has_key(*key) as u8)));
+    let stats = engine.stats.borrow();
+    ini.with_section(Some("Stats"))
+        .set("kills", stats.kills.to_string())
+        .set("items", stats.items.to_string())
+        .set("secrets", join(stats.found_secrets().iter()))
+        .set("time", stats.time.to_string());
+    for thing in engine.actors.iter() {
+        if let Ok(thing) = thing.try_borrow() {
+            if let Some(thing_id) = thing.thing_id() {
+                ini.with_section(Some("Things"))
+                    .set(thing_id.to_string(), join([thing.position().x, thing.position().y, thing.angle(), thing.health() as f32].iter()));
+            }
+        }
+    }
+    std::fs::create_dir_all(consts::DIRECTORY).map_err(|err| err.to_string())?;
+    return ini.write_to_file(slot_path(slot)).map_err(|err| err.to_string());
+}
+
+pub fn load_game(engine: &mut Doom, slot: usize) -> Result<(), String> {
+    let ini = Ini::load_from_file(slot_path(slot)).map_err(|err| err.to_string())?;
+    let game = ini.section(Some("Game")).ok_or("Invalid save game")?;
+    let player_props = ini.section(Some("Player")).ok_or("Invalid save game")?;
+    let inventory_props = ini.section(Some("Inventory")).ok_or("Invalid save game")?;
+    let map = game.get("map").ok_or("Invalid save game")?;
+    let skill = game.get("skill").and_then(|skill| skill.parse::<usize>().ok()).unwrap_or(0);
+    engine.skill = Skill::ALL[skill.min(Skill::COUNT - 1)];
+    if map != engine.map_name() {
+        engine.load_map(map, false)?;
+    } else {
+        engine.reset_level(false);
+    }
+    // Things, respawned from the WAD and moved back where they were
+    if let Some(things) = ini.section(Some("Things")) {
+        engine.actors.retain(|actor| match actor.borrow().thing_id() {
+            Some(thing_id) => things.contains_key(thing_id.to_string()),
+            None => true
+        });
+        for actor in engine.actors.iter() {
+            let mut actor = actor.borrow_mut();
+            let values: Vec<f32> = match actor.thing_id() {
+                Some(thing_id) => split(things.get(thing_id.to_string())),
+                None => continue
+            };
+            if values.len() == 4 {
+                let transform = Transform::set(Vector2::new(values[0], values[1]), values[2], actor.height());
+                actor.set_transform(&transform);
+                actor.set_health(values[3] as i32);
+            }
+        }
+    }
+    // Counters, the totals are the ones of the respawned level
+    if let Some(stats_props) = ini.section(Some("Stats")) {
+        let mut stats = engine.stats.borrow_mut();
+        stats.kills = stats_props.get("kills").and_then(|kills| kills.parse().ok()).unwrap_or(0);
+        stats.items = stats_props.get("items").and_then(|items| items.parse().ok()).unwrap_or(0);
+        stats.time = stats_props.get("time").and_then(|time| time.parse().ok()).unwrap_or(0.0);
+        stats.set_found_secrets(&split::<usize>(stats_props.get("secrets")));
+    }
+    // Player
+    let actor = engine.player().ok_or("No player in the map")?;
+    let mut actor = actor.borrow_mut();
+    let position: Vec<f32> = split(player_props.get("position"));
+    if position.len() == 2 {
+        let angle = player_props.get("angle").and_then(|angle| angle.parse().ok()).unwrap_or(actor.angle());
+        let transform = Transform::set(Vector2::new(position[0], position[1]), angle, actor.height());
+        actor.set_transform(&transform);
+    }
+    let player = actor.as_player_mut().ok_or("No player in the map")?;
+    let get = |name: &str| player_props.get(name).and_then(|value| value.parse::<i32>().ok()).unwrap_or(0);
+    let armor_type = match get("armor_type") {
+        1 => ArmorType::Green,
+        2 => ArmorType::Blue,
+        _ => ArmorType::None
+    };
+    let weapon = WeaponType::ALL[(get("weapon") as usize).min(WeaponType::COUNT - 1)];
+    player.set_status(get("health"), get("armor"), armor_type, weapon);
+    // Inventory
+    let inventory = player.inventory_mut();
+    if inventory_props.get("backpack") == Some("true") {
+        inventory.give_backpack();
+    }
+    for (ammo, count) in AmmoType::ALL.iter().zip(split::<i32>(inventory_props.get("ammo"))) {
+        inventory.set_ammo(*ammo, count);
+    }
+    for (weapon, owned) in WeaponType::ALL.iter().zip(split::<u8>(inventory_props.get("weapons"))) {
+        inventory.set_weapon(*weapon, owned != 0);
+    }
+    for (key, owned) in KeyType::ALL.iter().zip(split::<u8>(inventory_props.get("keys"))) {
+        if owned != 0 {
+            inventory.give_key(*key);
+        }
+    }
+    return Ok(());
+}
@@ -0,0 +1,345 @@
+#![allow(dead_code)]
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+// Engine
+use crate::configure::SoundDevice;
+use crate::wad::Reader;
+
+mod consts {
+    // DMX digital sound header: format, sample rate and sample count
+    pub const DMX_FORMAT: u16 = 3;
+    pub const HEADER_SIZE: usize = 8;
+    // Bytes repeated before and after the samples
+    pub const PADDING: usize = 16;
+    // PC speaker header: format 0 and tone count
+    pub const SPEAKER_FORMAT: u16 = 0;
+    pub const SPEAKER_HEADER_SIZE: usize = 4;
+    // The tones change at 140 Hz
+    pub const SPEAKER_TONE_RATE: u32 = 140;
+    pub const SPEAKER_AMPLITUDE: i16 = 8192;
+    // Clock of the PC timer driving the speaker
+    pub const PIT_FREQUENCY: f32 = 1193181.0;
+    // Timer divisors of the 128 tones, 0 is silence
+    pub const SPEAKER_DIVISORS: [u16; 128] = [
+        0,
+        6818, 6628, 6449, 6279, 6087, 5906, 5736, 5575,
+        5423, 5279, 5120, 4971, 4830, 4697, 4554, 4435,
+        4307, 4186, 4058, 3950, 3836, 3728, 3615, 3519,
+        3418, 3323, 3224, 3131, 3043, 2960, 2875, 2794,
+        2711, 2633, 2560, 2485, 2415, 2348, 2281, 2213,
+        2153, 2089, 2032, 1975, 1918, 1864, 1810, 1757,
+        1709, 1659, 1612, 1565, 1521, 1478, 1435, 1395,
+        1355, 1316, 1280, 1242, 1207, 1173, 1140, 1107,
+        1075, 1045, 1015, 986, 959, 931, 905, 879,
+        854, 829, 806, 783, 760, 739, 718, 697,
+        677, 658, 640, 621, 604, 586, 570, 553,
+        538, 522, 507, 493, 479, 465, 452, 439,
+        427, 415, 403, 391, 380, 369, 359, 348,
+        339, 329, 319, 310, 302, 293, 285, 276,
+        269, 261, 253, 246, 239, 232, 226, 219,
+        213, 207, 201, 195, 190, 184, 179,
+    ];
+}
+
+// Rate every sound is resampled to
+pub const OUTPUT_RATE: u32 = 44100;
+
+pub struct SoundInfo {
+    pub name: &'static str,
+    // Lower is more important, as the sfxinfo_t priorities
+    pub priority: i32,
+}
+
+const fn sound(name: &'static str, priority: i32) -> SoundInfo {
+    SoundInfo { name, priority }
+}
+
+// As S_sfx in sounds.c
+pub const SOUNDS: [SoundInfo; 108] = [
+    sound("pistol", 64), sound("shotgn", 64), sound("sgcock", 64), sound("dshtgn", 64),
+    sound("dbopn", 64), sound("dbcls", 64), sound("dbload", 64), sound("plasma", 64),
+    sound("bfg", 64), sound("sawup", 64), sound("sawidl", 118), sound("sawful", 64),
+    sound("sawhit", 64), sound("rlaunc", 64), sound("rxplod", 70), sound("firsht", 70),
+    sound("firxpl", 70), sound("pstart", 100), sound("pstop", 100), sound("doropn", 100),
+    sound("dorcls", 100), sound("stnmov", 119), sound("swtchn", 78), sound("swtchx", 78),
+    sound("plpain", 96), sound("dmpain", 96), sound("popain", 96), sound("vipain", 96),
+    sound("mnpain", 96), sound("pepain", 96), sound("slop", 78), sound("itemup", 78),
+    sound("wpnup", 78), sound("oof", 96), sound("telept", 32), sound("posit1", 98),
+    sound("posit2", 98), sound("posit3", 98), sound("bgsit1", 98), sound("bgsit2", 98),
+    sound("sgtsit", 98), sound("cacsit", 98), sound("brssit", 94), sound("cybsit", 92),
+    sound("spisit", 90), sound("bspsit", 90), sound("kntsit", 90), sound("vilsit", 90),
+    sound("mansit", 90), sound("pesit", 90), sound("sklatk", 70), sound("sgtatk", 70),
+    sound("skepch", 70), sound("vilatk", 70), sound("claw", 70), sound("skeswg", 70),
+    sound("pldeth", 32), sound("pdiehi", 32), sound("podth1", 70), sound("podth2", 70),
+    sound("podth3", 70), sound("bgdth1", 70), sound("bgdth2", 70), sound("sgtdth", 70),
+    sound("cacdth", 70), sound("skldth", 70), sound("brsdth", 32), sound("cybdth", 32),
+    sound("spidth", 32), sound("bspdth", 32), sound("vildth", 32), sound("kntdth", 32),
+    sound("pedth", 32), sound("skedth", 32), sound("posact", 120), sound("bgact", 120),
+    sound("dmact", 120), sound("bspact", 100), sound("bspwlk", 100), sound("vilact", 100),
+    sound("noway", 78), sound("barexp", 60), sound("punch", 64), sound("hoof", 70),
+    sound("metal", 70), sound("chgun", 64), sound("tink", 60), sound("bdopn", 100),
+    sound("bdcls", 100), sound("itmbk", 100), sound("flame", 32), sound("flamst", 32),
+    sound("getpow", 60), sound("bospit", 70), sound("boscub", 70), sound("bossit", 70),
+    sound("bospn", 70), sound("bosdth", 70), sound("manatk", 70), sound("mandth", 70),
+    sound("sssit", 70), sound("ssdth", 70), sound("keenpn", 70), sound("keendt", 70),
+    sound("skeact", 70), sound("skesit", 70), sound("skeatk", 70), sound("radio", 60),
+];
+
+pub fn sound_info(name: &str) -> Option<&'static SoundInfo> {
+    SOUNDS.iter().find(|info| info.name.eq_ignore_ascii_case(name))
+}
+
+// Digital sounds are the DS lumps
+pub fn lump_name(name: &str) -> String {
+    format!("DS{}", name.to_ascii_uppercase())
+}
+
+// PC speaker sounds are the DP lumps
+pub fn speaker_lump_name(name: &str) -> String {
+    format!("DP{}", name.to_ascii_uppercase())
+}
+
+// Sound effect as stored in the WAD, 8-bit unsigned PCM
+pub struct SoundEffect {
+    pub sample_rate: u32,
+    pub samples: Vec<u8>,
+}
+
+impl SoundEffect {
+    // DMX format 3 lump, the padding bytes are dropped
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        if data.len() < consts::HEADER_SIZE {
+            return Err(String::from("Sound lump too short"));
+        }
+        let format = u16::from_le_bytes([data[0], data[1]]);
+        if format != consts::DMX_FORMAT {
+            return Err(format!("Unknown sound format {}", format));
+        }
+        let sample_rate = u16::from_le_bytes([data[2], data[3]]) as u32;
+        let count = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        // The count takes in the padding, a short lump loses the end of it
+        let end = (consts::HEADER_SIZE + count.saturating_sub(consts::PADDING)).min(data.len());
+        let start = (consts::HEADER_SIZE + consts::PADDING).min(end);
+        let samples = &data[start..end];
+        if sample_rate == 0 {
+            return Err(String::from("Invalid sound sample rate"));
+        }
+        return Ok(SoundEffect {
+            sample_rate: sample_rate,
+            samples: samples.to_vec(),
+        });
+    }
+
+    // Seconds
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate as f32
+    }
+
+    // Signed 16-bit samples at the given rate, linearly interpolated
+    pub fn resample(&self, rate: u32) -> Vec<i16> {
+        if self.samples.is_empty() || rate == 0 {
+            return vec![];
+        }
+        let to_i16 = |sample: u8| (sample as i16 - 128) << 8;
+        let length = (self.samples.len() as u64 * rate as u64 / self.sample_rate as u64).max(1) as usize;
+        let step = self.sample_rate as f64 / rate as f64;
+        let last = self.samples.len() - 1;
+        (0..length).map(|i| {
+            let position = i as f64 * step;
+            let index = (position as usize).min(last);
+            let next = (index + 1).min(last);
+            let fraction = position - index as f64;
+            let a = to_i16(self.samples[index]) as f64;
+            let b = to_i16(self.samples[next]) as f64;
+            (a + (b - a) * fraction) as i16
+        }).collect()
+    }
+}
+
+// PC speaker sound, a tone for each 1/140 of second
+pub struct SpeakerEffect {
+    pub tones: Vec<u8>,
+}
+
+impl SpeakerEffect {
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        if data.len() < consts::SPEAKER_HEADER_SIZE {
+            return Err(String::from("Speaker sound lump too short"));
+        }
+        let format = u16::from_le_bytes([data[0], data[1]]);
+        if format != consts::SPEAKER_FORMAT {
+            return Err(format!("Unknown speaker sound format {}", format));
+        }
+        let count = u16::from_le_bytes([data[2], data[3]]) as usize;
+        let end = (consts::SPEAKER_HEADER_SIZE + count).min(data.len());
+        return Ok(SpeakerEffect {
+            tones: data[consts::SPEAKER_HEADER_SIZE..end].to_vec(),
+        });
+    }
+
+    // Seconds
+    pub fn duration(&self) -> f32 {
+        self.tones.len() as f32 / consts::SPEAKER_TONE_RATE as f32
+    }
+
+    // Hz of a tone, None for the silence
+    pub fn frequency(tone: u8) -> Option<f32> {
+        match consts::SPEAKER_DIVISORS.get(tone as usize) {
+            Some(0) | None => None,
+            Some(divisor) => Some(consts::PIT_FREQUENCY / *divisor as f32)
+        }
+    }
+
+    // Square wave at the given rate, the phase runs on across the tones
+    pub fn render(&self, rate: u32) -> Vec<i16> {
+        if rate == 0 {
+            return vec![];
+        }
+        let mut samples = vec![];
+        let mut phase = 0.0;
+        let mut pending = 0.0;
+        for tone in self.tones.iter() {
+            pending += rate as f32 / consts::SPEAKER_TONE_RATE as f32;
+            let length = pending as usize;
+            pending -= length as f32;
+            match SpeakerEffect::frequency(*tone) {
+                Some(frequency) => {
+                    for _ in 0..length {
+                        samples.push(if phase < 0.5 { consts::SPEAKER_AMPLITUDE } else { -consts::SPEAKER_AMPLITUDE });
+                        phase = (phase + frequency / rate as f32) % 1.0;
+                    }
+                },
+                None => samples.extend(std::iter::repeat(0).take(length))
+            }
+        }
+        return samples;
+    }
+}
+
+// Sounds read from the WAD on first use, already at the output rate
+pub struct SoundBank {
+    reader: Rc<Reader>,
+    rate: u32,
+    device: SoundDevice,
+    cache: RefCell<HashMap<String, Option<Rc<Vec<i16>>>>>,
+}
+
+impl SoundBank {
+    pub fn new(reader: &Rc<Reader>, rate: u32, device: SoundDevice) -> Self {
+        SoundBank {
+            reader: reader.clone(),
+            rate: rate,
+            device: device,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    pub fn decode(&self, name: &str) -> Result<SoundEffect, String> {
+        let lump = lump_name(name);
+        let data = self.reader.lump(&lump).ok_or(format!("Sound {} not found", lump))?;
+        return SoundEffect::decode(data);
+    }
+
+    pub fn decode_speaker(&self, name: &str) -> Result<SpeakerEffect, String> {
+        let lump = speaker_lump_name(name);
+        let data = self.reader.lump(&lump).ok_or(format!("Sound {} not found", lump))?;
+        return SpeakerEffect::decode(data);
+    }
+
+    // Missing or invalid lumps are cached as None
+    pub fn get(&self, name: &str) -> Option<Rc<Vec<i16>>> {
+        let name = name.to_ascii_lowercase();
+        if let Some(samples) = self.cache.borrow().get(&name) {
+            return samples.clone();
+        }
+        let samples = match self.device {
+            SoundDevice::Digital => self.decode(&name).map(|effect| effect.resample(self.rate)),
+            SoundDevice::Speaker => self.decode_speaker(&name).map(|effect| effect.render(self.rate)),
+        }.ok().map(Rc::new);
+        self.cache.borrow_mut().insert(name, samples.clone());
+        return samples;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // DMX lump with the padding around the given samples
+    fn dmx_lump(format: u16, rate: u16, samples: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&rate.to_le_bytes());
+        data.extend_from_slice(&((samples.len() + consts::PADDING * 2) as u32).to_le_bytes());
+        data.extend(std::iter::repeat(samples[0]).take(consts::PADDING));
+        data.extend_from_slice(samples);
+        data.extend(std::iter::repeat(samples[samples.len() - 1]).take(consts::PADDING));
+        return data;
+    }
+
+    #[test]
+    fn decode_strips_the_padding() {
+        let sound = SoundEffect::decode(&dmx_lump(3, 11025, &[1, 2, 3, 4])).unwrap();
+        assert_eq!(sound.sample_rate, 11025);
+        assert_eq!(sound.samples, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn decode_rejects_other_formats() {
+        assert!(SoundEffect::decode(&dmx_lump(2, 11025, &[128])).is_err());
+    }
+
+    #[test]
+    fn decode_rejects_a_zero_sample_rate() {
+        assert!(SoundEffect::decode(&dmx_lump(3, 0, &[128])).is_err());
+    }
+
+    #[test]
+    fn decode_rejects_short_lumps() {
+        assert!(SoundEffect::decode(&[3, 0, 0x11, 0x2B]).is_err());
+        assert!(SpeakerEffect::decode(&[0, 0]).is_err());
+    }
+
+    #[test]
+    fn decode_clamps_the_count_to_the_lump() {
+        let mut data = dmx_lump(3, 11025, &[128; 8]);
+        data.truncate(consts::HEADER_SIZE + consts::PADDING + 4);
+        let sound = SoundEffect::decode(&data).unwrap();
+        assert_eq!(sound.samples.len(), 4);
+        // Nothing but padding
+        data.truncate(consts::HEADER_SIZE + 4);
+        let sound = SoundEffect::decode(&data).unwrap();
+        assert!(sound.samples.is_empty());
+    }
+
+    #[test]
+    fn resample_scales_the_length() {
+        let sound = SoundEffect { sample_rate: 11025, samples: vec![128; 100] };
+        assert_eq!(sound.resample(44100).len(), 400);
+        assert_eq!(sound.resample(22050).len(), 200);
+        assert!(sound.resample(0).is_empty());
+    }
+
+    #[test]
+    fn resample_interpolates_between_samples() {
+        let sound = SoundEffect { sample_rate: 11025, samples: vec![128, 192] };
+        let samples = sound.resample(22050);
+        assert_eq!(samples, vec![0, 8192, 16384, 16384]);
+    }
+
+    #[test]
+    fn speaker_decode_and_render() {
+        let speaker = SpeakerEffect::decode(&[0, 0, 3, 0, 0, 1, 60]).unwrap();
+        assert_eq!(speaker.tones, vec![0, 1, 60]);
+        let samples = speaker.render(14000);
+        assert_eq!(samples.len(), 300);
+        assert!(samples[..100].iter().all(|sample| *sample == 0));
+        assert!(samples[100..].iter().all(|sample| sample.abs() == consts::SPEAKER_AMPLITUDE));
+        assert_eq!(SpeakerEffect::frequency(0), None);
+    }
+}
@@ -0,0 +1,125 @@
+#![allow(dead_code)]
+// Engine
+use crate::actors::{Actor, Player};
+use crate::doom::Doom;
+use crate::inventory::KeyColor;
+use crate::map::{LineDef, Map};
+use crate::math::{radians, Vector2};
+use crate::trace::{line_opening, path_traverse, InterceptKind, Trace};
+
+mod consts {
+    pub const USE_RANGE: f32 = 64.0;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockKind {
+    // Manual and switched doors, "to open this door"
+    Door,
+    // Locked fast doors opened by switches, "to activate this object"
+    Object
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lock {
+    pub color: KeyColor,
+    pub kind: LockKind,
+}
+
+impl Lock {
+    // As the PD_* strings
+    pub fn message(&self) -> String {
+        match self.kind {
+            LockKind::Door => format!("You need a {} key to open this door", self.color.name()),
+            LockKind::Object => format!("You need a {} key to activate this object", self.color.name()),
+        }
+    }
+}
+
+// Key required by a line special, if any
+pub fn line_lock(line_type: u16) -> Option<Lock> {
+    let (color, kind) = match line_type {
+        // DR and D1 locked doors
+        26 | 32 => (KeyColor::Blue, LockKind::Door),
+        27 | 34 => (KeyColor::Yellow, LockKind::Door),
+        28 | 33 => (KeyColor::Red, LockKind::Door),
+        // SR and S1 locked fast doors
+        99 | 133 => (KeyColor::Blue, LockKind::Object),
+        134 | 135 => (KeyColor::Red, LockKind::Object),
+        136 | 137 => (KeyColor::Yellow, LockKind::Object),
+        _ => return None
+    };
+    return Some(Lock { color, kind });
+}
+
+// True when the point is on the front (right) side of the line, as P_PointOnLineSide
+pub fn point_on_front_side(map: &Map, line: &LineDef, point: &Vector2<f32>) -> bool {
+    let start = Vector2::<f32>::from(line.start_vertex(&map));
+    let end = Vector2::<f32>::from(line.end_vertex(&map));
+    let line_delta = end - start;
+    let delta = *point - start;
+    return delta.y * line_delta.x < line_delta.y * delta.x;
+}
+
+// Look for a special line in front of the player, as P_UseLines
+pub fn use_lines<'wad>(engine: &Doom<'wad>, player: &Player) {
+    let origin = *player.position();
+    let angle = radians(player.angle());
+    let trace = Trace::new(&origin, &(origin + Vector2::new(angle.cos(), angle.sin()) * consts::USE_RANGE));
+    for intercept in path_traverse(&engine.map, &engine.actors, &trace, true, false) {
+        if let InterceptKind::Line(line) = intercept.kind {
+            if line.line_type == 0 {
+                // Walls stop the use, openings let it through
+                match line_opening(&engine.map, line) {
+                    Some(opening) if opening.range > 0.0 => continue,
+                    _ => return
+                }
+            }
+            // Lines can be used only from the front side
+            if point_on_front_side(&engine.map, line, &origin) {
+                use_special_line(engine, player, line);
+            }
+            return;
+        }
+    }
+}
+
+// As P_UseSpecialLine, returns true if the special has been triggered
+pub fn use_special_line<'wad>(engine: &Doom<'wad>, player: &Player, line: &LineDef) -> bool {
+    if let Some(lock) = line_lock(line.line_type) {
+        if !player.inventory().has_key_color(lock.color) {
+            engine.message(&lock.message());
+            engine.start_sound("oof", Some(player));
+            return false;
+        }
+    }
+    match line.line_type {
+        // Exit and secret exit switches
+        11 => {
+            engine.start_sound("swtchx", Some(player));
+            engine.exit_level(false);
+        },
+        51 => {
+            engine.start_sound("swtchx", Some(player));
+            engine.exit_level(true);
+        },
+        _ => {}
+    }
+    // Sectors are read straight from the WAD and cannot move yet,
+    // so doors and lifts are accepted but have no visible effect.
+    return true;
+}
+
+// Walk over triggers crossed by the player moving between two points, as P_CrossSpecialLine
+pub fn cross_special_lines<'wad>(engine: &Doom<'wad>, from: &Vector2<f32>, to: &Vector2<f32>) {
+    let trace = Trace::new(from, to);
+    for intercept in path_traverse(&engine.map, &engine.actors, &trace, true, false) {
+        if let InterceptKind::Line(line) = intercept.kind {
+            match line.line_type {
+                // Exit and secret exit
+                52 => engine.exit_level(false),
+                124 => engine.exit_level(true),
+                _ => {}
+            }
+        }
+    }
+}
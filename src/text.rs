@@ -0,0 +1,141 @@
+#![allow(dead_code)]
+use std::collections::VecDeque;
+use std::rc::Rc;
+// Engine
+use crate::data_textures::DataTextures;
+use crate::doom::Doom;
+use crate::hud::draw_patch;
+use crate::math::Vector2;
+use crate::render::Render;
+use crate::shape::Size;
+use crate::time::{tics, TIC_RATE};
+use crate::window::DoomSurface;
+
+mod consts {
+    // As hu_stuff.c
+    pub const SPACE_WIDTH: i32 = 4;
+    pub const DEFAULT_LINE_HEIGHT: i32 = 8;
+    pub const MESSAGE_X: i32 = 0;
+    pub const MESSAGE_Y: i32 = 0;
+    pub const MESSAGE_LINES: usize = 4;
+}
+
+// Text written with the STCFN patches, in 320x200 units stretched over the area
+pub struct Font<'wad> {
+    data_textures: Rc<DataTextures<'wad>>,
+}
+
+impl<'wad> Font<'wad> {
+    pub fn new(data_textures: &Rc<DataTextures<'wad>>) -> Self {
+        Font {
+            data_textures: data_textures.clone(),
+        }
+    }
+
+    // Height of a glyph plus one pixel of spacing, as the hu_font lines
+    pub fn line_height(&self) -> i32 {
+        match self.data_textures.font_glyph('A') {
+            Some(glyph) => glyph.texture.size.height() as i32 + 1,
+            None => consts::DEFAULT_LINE_HEIGHT
+        }
+    }
+
+    fn char_width(&self, c: char) -> i32 {
+        match self.data_textures.font_glyph(c) {
+            Some(glyph) => glyph.texture.size.width() as i32,
+            None => consts::SPACE_WIDTH
+        }
+    }
+
+    // Width of the longest line, as M_StringWidth
+    pub fn width(&self, text: &str) -> i32 {
+        text.lines()
+            .map(|line| line.chars().map(|c| self.char_width(c)).sum())
+            .max()
+            .unwrap_or(0)
+    }
+
+    // As M_StringHeight
+    pub fn height(&self, text: &str) -> i32 {
+        text.lines().count().max(1) as i32 * self.line_height()
+    }
+
+    // Draw the text from the top left corner, unknown chars are spaces
+    pub fn draw(&self, surface: &mut DoomSurface, text: &str, x: i32, y: i32, size: &Vector2<i32>, offset: &Vector2<i32>) {
+        let mut y = y;
+        for line in text.lines() {
+            let mut x = x;
+            for c in line.chars() {
+                match self.data_textures.font_glyph(c) {
+                    Some(glyph) => x += draw_patch(surface, &glyph, x, y, size, offset),
+                    None => x += consts::SPACE_WIDTH
+                }
+            }
+            y += self.line_height();
+        }
+    }
+
+    // Every line centered on the screen width, starting from y
+    pub fn draw_centered_x(&self, surface: &mut DoomSurface, text: &str, y: i32, size: &Vector2<i32>, offset: &Vector2<i32>) {
+        let mut y = y;
+        for line in text.lines() {
+            self.draw(surface, line, (320 - self.width(line)) / 2, y, size, offset);
+            y += self.line_height();
+        }
+    }
+
+    // The whole block centered on the screen, as the menu messages
+    pub fn draw_centered(&self, surface: &mut DoomSurface, text: &str, size: &Vector2<i32>, offset: &Vector2<i32>) {
+        self.draw_centered_x(surface, text, (200 - self.height(text)) / 2, size, offset);
+    }
+}
+
+// Player messages at the top of the screen, each one fades after its timeout
+pub struct RenderMessages<'wad> {
+    font: Font<'wad>,
+    size: Vector2<i32>,
+    offset: Vector2<i32>,
+    // Text and time left
+    lines: VecDeque<(String, f32)>,
+}
+
+impl<'wad> RenderMessages<'wad> {
+    pub fn new(data_textures: &Rc<DataTextures<'wad>>, size: Vector2<i32>, offset: Vector2<i32>) -> Self {
+        RenderMessages {
+            font: Font::new(data_textures),
+            size: size,
+            offset: offset,
+            lines: VecDeque::new(),
+        }
+    }
+
+    // HU_MSGTIMEOUT
+    fn timeout() -> f32 {
+        tics(4 * TIC_RATE as u32)
+    }
+
+    fn update(&mut self, doom: &Doom, last_frame_time: f32) {
+        for line in self.lines.iter_mut() {
+            line.1 -= last_frame_time;
+        }
+        self.lines.retain(|line| line.1 > 0.0);
+        for message in doom.messages.borrow_mut().drain(..) {
+            self.lines.push_back((message, RenderMessages::timeout()));
+        }
+        while self.lines.len() > consts::MESSAGE_LINES {
+            self.lines.pop_front();
+        }
+    }
+}
+
+impl Render for RenderMessages<'_> {
+    fn draw<'wad>(&mut self, doom: &mut Doom<'wad>, last_frame_time: f64, _blending_factor: f64) {
+        self.update(doom, last_frame_time as f32);
+        let surface = doom.surface.clone();
+        let mut y = consts::MESSAGE_Y;
+        for (text, _) in self.lines.iter() {
+            self.font.draw(&mut surface.borrow_mut(), text, consts::MESSAGE_X, y, &self.size, &self.offset);
+            y += self.font.height(text);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+#![allow(dead_code)]
+use std::collections::VecDeque;
+use std::fs;
+// Engine
+use crate::configure;
+use crate::controls::{Action, Controls};
+use crate::math::Vector2;
+// Using
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+mod consts {
+    // As forwardmove and sidemove of g_game.c, walking and running
+    pub const FORWARD_MOVE: [i8; 2] = [25, 50];
+    pub const SIDE_MOVE: [i8; 2] = [24, 40];
+    // As MAXPLMOVE, strafing with both the strafe keys and the modifier reaches it
+    pub const MAX_PLAYER_MOVE: i32 = 50;
+    // Vanilla demos
+    pub const DEMO_VERSION: u8 = 109;
+    pub const DEMO_HEADER_SIZE: usize = 13;
+    pub const OLD_DEMO_HEADER_SIZE: usize = 7;
+    pub const DEMO_MARKER: u8 = 0x80;
+    pub const BT_ATTACK: u8 = 1;
+    pub const BT_USE: u8 = 2;
+    pub const BT_CHANGE: u8 = 4;
+    pub const BT_SPECIAL: u8 = 128;
+    pub const BT_WEAPON_SHIFT: u8 = 3;
+    pub const BT_WEAPON_MASK: u8 = 0x38;
+    // Degrees of a step of the turn stored in demos, the high byte of the 16 bits angle
+    pub const ANGLE_STEP: f32 = 360.0 / 256.0;
+    // Slot of the weapons by their vanilla number, the chainsaw and the super shotgun share slots
+    pub const WEAPON_SLOTS: [u8; 9] = [1, 2, 3, 4, 5, 6, 7, 1, 3];
+    // Vanilla number of the weapon of a slot
+    pub const SLOT_WEAPONS: [u8; 7] = [0, 1, 2, 3, 4, 5, 6];
+}
+
+// What the player does in a tic, as ticcmd_t
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TicCmd {
+    // 25 walking and 50 running forward, negative backward
+    pub forward_move: i8,
+    // 24 walking and 40 running to the right, negative to the left
+    pub side_move: i8,
+    // Degrees, positive to the left
+    pub angle_turn: f32,
+    // Vertical look in degrees, positive up
+    pub look: f32,
+    pub fire: bool,
+    pub use_line: bool,
+    pub jump: bool,
+    // Slots 1 to 7
+    pub weapon: Option<u8>,
+}
+
+impl TicCmd {
+    pub fn walk(speed: usize) -> (i8, i8) {
+        (consts::FORWARD_MOVE[speed.min(1)], consts::SIDE_MOVE[speed.min(1)])
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.forward_move.unsigned_abs() > consts::FORWARD_MOVE[0] as u8 || self.side_move.unsigned_abs() > consts::SIDE_MOVE[0] as u8
+    }
+}
+
+// Anything driving the player
+pub trait InputSource {
+    // Called with each input update of the window, without the keys the automap takes
+    fn gather(&mut self, _input: &WinitInputHelper, _taken: &[KeyCode]) {
+    }
+
+    // Called while the menu, the console or the intermission take the input
+    fn release(&mut self) {
+    }
+
+    // Scale of the mouse sensitivity, from the options menu
+    fn set_mouse_scale(&mut self, _scale: f32) {
+    }
+
+    // Command of the next tic, None once the source is over
+    fn build(&mut self, tic_time: f32) -> Option<TicCmd>;
+}
+
+// Keyboard and mouse, through the key bindings, as G_BuildTiccmd
+pub struct LocalInput {
+    controls: Controls,
+    mouse: configure::Mouse,
+    mouse_scale: f32,
+    // Degrees per second of the turn keys
+    angle_speed: f32,
+    held: Vec<Action>,
+    // Pressed since the last command
+    use_line: bool,
+    weapon: Option<u8>,
+    mouse_delta: Vector2<f32>,
+}
+
+impl LocalInput {
+    pub fn new(configure: &configure::Configure) -> Self {
+        LocalInput {
+            controls: configure.controls.clone(),
+            mouse: configure.mouse.clone(),
+            mouse_scale: 1.0,
+            angle_speed: configure.player.angle_speed,
+            held: vec![],
+            use_line: false,
+            weapon: None,
+            mouse_delta: Vector2::zeros(),
+        }
+    }
+
+    fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+}
+
+impl InputSource for LocalInput {
+    fn gather(&mut self, input: &WinitInputHelper, taken: &[KeyCode]) {
+        self.held = Action::ALL.iter().copied().filter(|action| self.controls.held_without(input, *action, taken)).collect();
+        self.use_line |= self.controls.pressed(input, Action::Use);
+        for slot in 1..=7 {
+            if self.controls.pressed(input, Action::Weapon(slot)) {
+                self.weapon = Some(slot);
+            }
+        }
+        if self.mouse.enabled {
+            let (dx, dy) = input.mouse_diff();
+            self.mouse_delta += Vector2::new(dx, dy);
+        }
+    }
+
+    fn set_mouse_scale(&mut self, scale: f32) {
+        self.mouse_scale = scale;
+    }
+
+    fn release(&mut self) {
+        self.held.clear();
+        self.use_line = false;
+        self.weapon = None;
+        self.mouse_delta = Vector2::zeros();
+    }
+
+    // The held actions last until the next input update, the tics between two updates share them
+    fn build(&mut self, tic_time: f32) -> Option<TicCmd> {
+        let (forward, side) = TicCmd::walk(self.is_held(Action::Run) as usize);
+        let (forward, side) = (forward as i32, side as i32);
+        let mut cmd = TicCmd::default();
+        let mut forward_move = 0;
+        let mut side_move = 0;
+        if self.is_held(Action::Forward) {
+            forward_move += forward;
+        }
+        if self.is_held(Action::Back) {
+            forward_move -= forward;
+        }
+        // With the strafe modifier the turn keys strafe, on top of the strafe keys
+        let strafe = self.is_held(Action::Strafe);
+        if self.is_held(Action::StrafeRight) {
+            side_move += side;
+        }
+        if self.is_held(Action::StrafeLeft) {
+            side_move -= side;
+        }
+        if strafe && self.is_held(Action::TurnRight) {
+            side_move += side;
+        }
+        if strafe && self.is_held(Action::TurnLeft) {
+            side_move -= side;
+        }
+        cmd.forward_move = forward_move.clamp(-consts::MAX_PLAYER_MOVE, consts::MAX_PLAYER_MOVE) as i8;
+        cmd.side_move = side_move.clamp(-consts::MAX_PLAYER_MOVE, consts::MAX_PLAYER_MOVE) as i8;
+        if !strafe {
+            if self.is_held(Action::TurnLeft) {
+                cmd.angle_turn += self.angle_speed * tic_time;
+            }
+            if self.is_held(Action::TurnRight) {
+                cmd.angle_turn -= self.angle_speed * tic_time;
+            }
+        }
+        // Moving the mouse to the right turns clockwise
+        let sensitivity = self.mouse.sensitivity * self.mouse_scale;
+        cmd.angle_turn -= self.mouse_delta.x * sensitivity;
+        if self.mouse.look {
+            let look = if self.mouse.invert { self.mouse_delta.y } else { -self.mouse_delta.y };
+            cmd.look = look * sensitivity;
+        }
+        cmd.fire = self.is_held(Action::Fire);
+        cmd.jump = self.is_held(Action::Jump);
+        cmd.use_line = std::mem::take(&mut self.use_line);
+        cmd.weapon = self.weapon.take();
+        self.mouse_delta = Vector2::zeros();
+        return Some(cmd);
+    }
+}
+
+// Vanilla demo lump, the commands of the first player
+pub struct Demo {
+    pub skill: u8,
+    pub episode: u8,
+    pub map: u8,
+    pub commands: Vec<TicCmd>,
+}
+
+impl Demo {
+    pub fn new(skill: u8, episode: u8, map: u8) -> Self {
+        Demo {
+            skill: skill,
+            episode: episode,
+            map: map,
+            commands: vec![],
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+        return Demo::parse(&data);
+    }
+
+    // Versions before 1.4 have a shorter header without the version byte
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let (header_size, start) = match data.first() {
+            Some(version) if *version >= 104 => (consts::DEMO_HEADER_SIZE, 1),
+            Some(_) => (consts::OLD_DEMO_HEADER_SIZE, 0),
+            None => return Err(String::from("Empty demo"))
+        };
+        if data.len() < header_size {
+            return Err(String::from("Demo too short"));
+        }
+        let players = data[header_size - 4..header_size].iter().filter(|in_game| **in_game != 0).count().max(1);
+        let mut demo = Demo::new(data[start], data[start + 1], data[start + 2]);
+        let mut offset = header_size;
+        while offset < data.len() && data[offset] != consts::DEMO_MARKER {
+            let tic = match data.get(offset..offset + 4) {
+                Some(tic) => tic,
+                None => break
+            };
+            demo.commands.push(Demo::decode(tic));
+            offset += 4 * players;
+        }
+        return Ok(demo);
+    }
+
+    fn decode(tic: &[u8]) -> TicCmd {
+        let buttons = tic[3];
+        let mut cmd = TicCmd {
+            forward_move: tic[0] as i8,
+            side_move: tic[1] as i8,
+            // High byte of the 16 bits angle
+            angle_turn: (tic[2] as i8) as f32 * consts::ANGLE_STEP,
+            ..Default::default()
+        };
+        if buttons & consts::BT_SPECIAL == 0 {
+            cmd.fire = buttons & consts::BT_ATTACK != 0;
+            cmd.use_line = buttons & consts::BT_USE != 0;
+            if buttons & consts::BT_CHANGE != 0 {
+                let weapon = (buttons & consts::BT_WEAPON_MASK) >> consts::BT_WEAPON_SHIFT;
+                cmd.weapon = consts::WEAPON_SLOTS.get(weapon as usize).copied();
+            }
+        }
+        return cmd;
+    }
+
+    fn encode(cmd: &TicCmd) -> [u8; 4] {
+        let mut buttons = 0;
+        if cmd.fire {
+            buttons |= consts::BT_ATTACK;
+        }
+        if cmd.use_line {
+            buttons |= consts::BT_USE;
+        }
+        if let Some(weapon) = cmd.weapon.and_then(|slot| consts::SLOT_WEAPONS.get((slot as usize).wrapping_sub(1)).copied()) {
+            buttons |= consts::BT_CHANGE | (weapon << consts::BT_WEAPON_SHIFT);
+        }
+        let angle = (cmd.angle_turn / consts::ANGLE_STEP).round().clamp(i8::MIN as f32, i8::MAX as f32) as i8;
+        return [cmd.forward_move as u8, cmd.side_move as u8, angle as u8, buttons];
+    }
+
+    // Version 1.9 lump of a single player game, jump and look are dropped
+    pub fn to_lmp(&self) -> Vec<u8> {
+        let mut data = vec![consts::DEMO_VERSION, self.skill, self.episode, self.map, 0, 0, 0, 0, 0, 1, 0, 0, 0];
+        for cmd in self.commands.iter() {
+            data.extend_from_slice(&Demo::encode(cmd));
+        }
+        data.push(consts::DEMO_MARKER);
+        return data;
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_lmp()).map_err(|err| err.to_string())
+    }
+}
+
+// Replays the commands of a demo
+pub struct DemoPlayback {
+    demo: Demo,
+    tic: usize,
+}
+
+impl DemoPlayback {
+    pub fn new(demo: Demo) -> Self {
+        DemoPlayback { demo, tic: 0 }
+    }
+
+    pub fn demo(&self) -> &Demo {
+        &self.demo
+    }
+}
+
+impl InputSource for DemoPlayback {
+    fn build(&mut self, _tic_time: f32) -> Option<TicCmd> {
+        let cmd = self.demo.commands.get(self.tic).copied();
+        self.tic += 1;
+        return cmd;
+    }
+}
+
+// Records the commands played and writes the demo on drop
+pub struct DemoRecorder {
+    path: String,
+    demo: Demo,
+    // Turn left out by the steps of the demo, as the carry of G_BuildTiccmd
+    angle_carry: f32,
+}
+
+impl DemoRecorder {
+    pub fn new(path: &str, demo: Demo) -> Self {
+        DemoRecorder {
+            path: String::from(path),
+            demo: demo,
+            angle_carry: 0.0,
+        }
+    }
+
+    // Stores the command with the turn rounded to the demo steps, the rest goes to the next tic.
+    // The returned command is the one to play, so the replay matches the game: demos have no jump.
+    pub fn record(&mut self, cmd: &TicCmd) -> TicCmd {
+        let desired = cmd.angle_turn + self.angle_carry;
+        let steps = (desired / consts::ANGLE_STEP).round().clamp(i8::MIN as f32, i8::MAX as f32);
+        let mut recorded = *cmd;
+        recorded.jump = false;
+        recorded.angle_turn = steps * consts::ANGLE_STEP;
+        self.angle_carry = desired - recorded.angle_turn;
+        self.demo.commands.push(recorded);
+        return recorded;
+    }
+}
+
+impl Drop for DemoRecorder {
+    fn drop(&mut self) {
+        if let Err(err) = self.demo.save(&self.path) {
+            eprintln!("Failed to write {}: {}", self.path, err);
+        }
+    }
+}
+
+// Commands queued by code, for tests and tools driving the player
+pub struct ScriptedInput {
+    commands: VecDeque<TicCmd>,
+}
+
+impl ScriptedInput {
+    pub fn new() -> Self {
+        ScriptedInput { commands: VecDeque::new() }
+    }
+
+    pub fn push(&mut self, cmd: TicCmd) {
+        self.commands.push_back(cmd);
+    }
+
+    pub fn push_repeat(&mut self, cmd: TicCmd, tics: usize) {
+        self.commands.extend(std::iter::repeat(cmd).take(tics));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn build(&mut self, _tic_time: f32) -> Option<TicCmd> {
+        self.commands.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_input(held: &[Action]) -> LocalInput {
+        let configure = configure::Configure::load_from_file(String::from("assets/doom.ini")).unwrap();
+        let mut input = LocalInput::new(&configure);
+        input.held = held.to_vec();
+        return input;
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let cmd = TicCmd {
+            forward_move: 50,
+            side_move: -40,
+            angle_turn: 2.0 * consts::ANGLE_STEP,
+            fire: true,
+            use_line: true,
+            weapon: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(Demo::decode(&Demo::encode(&cmd)), cmd);
+    }
+
+    #[test]
+    fn parse_new_header() {
+        let mut data = vec![109, 3, 1, 4, 0, 0, 0, 0, 0, 1, 1, 0, 0];
+        // Two players, the second one is skipped
+        data.extend_from_slice(&[25, 0, 0, 1, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0, 24, 0, 0, 0, 0, 0, 0]);
+        data.push(consts::DEMO_MARKER);
+        let demo = Demo::parse(&data).unwrap();
+        assert_eq!((demo.skill, demo.episode, demo.map), (3, 1, 4));
+        assert_eq!(demo.commands.len(), 2);
+        assert_eq!(demo.commands[0].forward_move, 25);
+        assert!(demo.commands[0].fire);
+        assert_eq!(demo.commands[1].side_move, 24);
+    }
+
+    #[test]
+    fn parse_old_header() {
+        let data = [2, 1, 3, 1, 0, 0, 0, 206, 0, 0, 0, consts::DEMO_MARKER];
+        let demo = Demo::parse(&data).unwrap();
+        assert_eq!((demo.skill, demo.episode, demo.map), (2, 1, 3));
+        assert_eq!(demo.commands.len(), 1);
+        assert_eq!(demo.commands[0].forward_move, -50);
+    }
+
+    #[test]
+    fn to_lmp_parses_back() {
+        let mut demo = Demo::new(2, 1, 1);
+        demo.commands.push(TicCmd { forward_move: 25, ..Default::default() });
+        demo.commands.push(TicCmd { side_move: -24, use_line: true, ..Default::default() });
+        let parsed = Demo::parse(&demo.to_lmp()).unwrap();
+        assert_eq!(parsed.commands, demo.commands);
+    }
+
+    #[test]
+    fn recorder_carries_the_turn_remainder() {
+        let path = std::env::temp_dir().join("rust-doom-recorder.lmp").to_string_lossy().to_string();
+        let mut recorder = DemoRecorder::new(&path, Demo::new(2, 1, 1));
+        let cmd = TicCmd { angle_turn: 0.5, jump: true, ..Default::default() };
+        let turned: f32 = (0..10).map(|_| recorder.record(&cmd)).map(|recorded| {
+            assert!(!recorded.jump);
+            recorded.angle_turn
+        }).sum();
+        assert!((turned - 5.0).abs() <= consts::ANGLE_STEP / 2.0);
+        drop(recorder);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn strafe_keys_and_modifier_reach_strafe50() {
+        let mut input = local_input(&[Action::Run, Action::Strafe, Action::StrafeRight, Action::TurnRight]);
+        let cmd = input.build(1.0 / 35.0).unwrap();
+        assert_eq!(cmd.side_move, 50);
+        assert_eq!(cmd.angle_turn, 0.0);
+    }
+
+    #[test]
+    fn run_moves_faster() {
+        let mut input = local_input(&[Action::Forward, Action::StrafeLeft]);
+        let cmd = input.build(1.0 / 35.0).unwrap();
+        assert_eq!((cmd.forward_move, cmd.side_move), (25, -24));
+        let mut input = local_input(&[Action::Run, Action::Forward, Action::StrafeLeft]);
+        let cmd = input.build(1.0 / 35.0).unwrap();
+        assert_eq!((cmd.forward_move, cmd.side_move), (50, -40));
+        assert!(cmd.is_running());
+    }
+
+    #[test]
+    fn held_keys_last_until_released() {
+        let mut input = local_input(&[Action::Forward]);
+        assert_eq!(input.build(1.0 / 35.0).unwrap().forward_move, 25);
+        assert_eq!(input.build(1.0 / 35.0).unwrap().forward_move, 25);
+        input.release();
+        assert_eq!(input.build(1.0 / 35.0).unwrap().forward_move, 0);
+    }
+
+    #[test]
+    fn scripted_input_plays_its_commands() {
+        let mut input = ScriptedInput::new();
+        input.push(TicCmd { fire: true, ..Default::default() });
+        input.push_repeat(TicCmd { forward_move: 50, ..Default::default() }, 2);
+        assert!(input.build(1.0 / 35.0).unwrap().fire);
+        assert_eq!(input.build(1.0 / 35.0).unwrap().forward_move, 50);
+        assert_eq!(input.build(1.0 / 35.0).unwrap().forward_move, 50);
+        assert!(input.is_empty());
+        assert_eq!(input.build(1.0 / 35.0), None);
+    }
+}
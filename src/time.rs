@@ -1,3 +1,11 @@
+// Vanilla game logic rate, tics per second
+pub const TIC_RATE: f32 = 35.0;
+
+// Convert a duration in tics into seconds
+pub fn tics(count: u32) -> f32 {
+    count as f32 / TIC_RATE
+}
+
 // Trait of time struct
 pub trait TimeTrait : Copy {
     fn now() -> Self;
@@ -0,0 +1,178 @@
+#![allow(dead_code)]
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+// Engine
+use crate::actors::Actor;
+use crate::map::{LineDef, LineDefFlags, Map};
+use crate::math::Vector2;
+
+pub enum InterceptKind<'wad> {
+    Line(&'wad LineDef),
+    Thing(Rc<RefCell<Box<dyn Actor>>>),
+}
+
+pub struct Intercept<'wad> {
+    // Position along the trace in [0, 1]
+    pub fraction: f32,
+    pub kind: InterceptKind<'wad>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Opening {
+    pub top: f32,
+    pub bottom: f32,
+    pub range: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Trace {
+    pub origin: Vector2<f32>,
+    pub delta: Vector2<f32>,
+}
+
+impl Trace {
+    pub fn new(from: &Vector2<f32>, to: &Vector2<f32>) -> Self {
+        Trace {
+            origin: *from,
+            delta: *to - *from,
+        }
+    }
+
+    pub fn end(&self) -> Vector2<f32> {
+        self.origin + self.delta
+    }
+
+    pub fn length(&self) -> f32 {
+        self.delta.magnitude()
+    }
+
+    pub fn point(&self, fraction: f32) -> Vector2<f32> {
+        self.origin + self.delta * fraction
+    }
+
+    // Fraction of the trace where it crosses the segment, if any
+    pub fn intersect_segment(&self, start: &Vector2<f32>, end: &Vector2<f32>) -> Option<f32> {
+        let segment = *end - *start;
+        let denominator = self.delta.cross(&segment);
+        if denominator == 0.0 {
+            return None;
+        }
+        let to_start = *start - self.origin;
+        let fraction = to_start.cross(&segment) / denominator;
+        let along_segment = to_start.cross(&self.delta) / denominator;
+        if fraction < 0.0 || fraction > 1.0 || along_segment < 0.0 || along_segment > 1.0 {
+            return None;
+        }
+        return Some(fraction);
+    }
+
+    // Fraction of the trace where it enters a circle, if any
+    pub fn intersect_circle(&self, center: &Vector2<f32>, radius: f32) -> Option<f32> {
+        let length_sq = self.delta.dot(&self.delta);
+        if length_sq == 0.0 {
+            return None;
+        }
+        let to_center = *center - self.origin;
+        let projection = to_center.dot(&self.delta) / length_sq;
+        let closest = self.point(projection);
+        let distance = closest.distance(center);
+        if distance > radius {
+            return None;
+        }
+        let entry = projection - (radius * radius - distance * distance).sqrt() / length_sq.sqrt();
+        if entry < 0.0 || entry > 1.0 {
+            return None;
+        }
+        return Some(entry);
+    }
+}
+
+// Vertical gap through a two sided line
+pub fn line_opening(map: &Map, line: &LineDef) -> Option<Opening> {
+    if !line.has_flag(LineDefFlags::TwoSided) {
+        return None;
+    }
+    if let (Some(front), Some(back)) = (line.front_sector(&map), line.back_sector(&map)) {
+        let top = front.ceiling_height.min(back.ceiling_height) as f32;
+        let bottom = front.floor_height.max(back.floor_height) as f32;
+        return Some(Opening { top, bottom, range: top - bottom });
+    }
+    return None;
+}
+
+// Collect lines and things crossed by the trace, sorted by distance.
+// Actors that are currently mutably borrowed (i.e. the one tracing) are skipped.
+pub fn path_traverse<'wad>(
+    map: &Map<'wad>,
+    actors: &Vec<Rc<RefCell<Box<dyn Actor>>>>,
+    trace: &Trace,
+    add_lines: bool,
+    add_things: bool,
+) -> Vec<Intercept<'wad>> {
+    let mut intercepts = vec![];
+    // Lines from the blockmap
+    if add_lines {
+        if let Some(ref blockmaps) = map.blockmaps {
+            let mut visited: HashSet<*const LineDef> = HashSet::new();
+            for list_lines in blockmaps.get_on_path(&trace.origin, &trace.end()) {
+                for line in list_lines.iter() {
+                    if !visited.insert(*line as *const LineDef) {
+                        continue;
+                    }
+                    let start = Vector2::<f32>::from(line.start_vertex(&map));
+                    let end = Vector2::<f32>::from(line.end_vertex(&map));
+                    if let Some(fraction) = trace.intersect_segment(&start, &end) {
+                        intercepts.push(Intercept {
+                            fraction,
+                            kind: InterceptKind::Line(*line),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    // Things
+    if add_things {
+        for rc_actor in actors.iter() {
+            if let Ok(actor) = rc_actor.try_borrow() {
+                if let Some(fraction) = trace.intersect_circle(actor.position(), actor.size() as f32) {
+                    intercepts.push(Intercept {
+                        fraction,
+                        kind: InterceptKind::Thing(rc_actor.clone()),
+                    });
+                }
+            }
+        }
+    }
+    // Nearest first
+    intercepts.sort_by(|a, b| a.fraction.total_cmp(&b.fraction));
+    return intercepts;
+}
+
+// True if nothing solid stands between the two points
+pub fn check_sight(map: &Map, from: &Vector2<f32>, from_z: f32, to: &Vector2<f32>, to_z: f32) -> bool {
+    let trace = Trace::new(from, to);
+    let length = trace.length();
+    if length == 0.0 {
+        return true;
+    }
+    for intercept in path_traverse(map, &vec![], &trace, true, false) {
+        if let InterceptKind::Line(line) = intercept.kind {
+            match line_opening(map, line) {
+                Some(opening) => {
+                    if opening.range <= 0.0 {
+                        return false;
+                    }
+                    // Height of the sight line at the crossing point
+                    let z = from_z + (to_z - from_z) * intercept.fraction;
+                    if z < opening.bottom || z > opening.top {
+                        return false;
+                    }
+                }
+                None => return false,
+            }
+        }
+    }
+    return true;
+}
@@ -1,4 +1,4 @@
-use crate::map::Thing;
+use crate::map::{Thing, ThingFlags};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u16)]
@@ -179,4 +179,275 @@ impl ThingType {
     pub fn id(&self) -> u16 {
         *self as u16
     }
+
+    // Health at spawn, as in vanilla mobjinfo
+    pub fn spawn_health(&self) -> i32 {
+        match self {
+            ThingType::Player1Start
+            | ThingType::Player2Start
+            | ThingType::Player3Start
+            | ThingType::Player4Start => 100,
+            ThingType::ZombieMan => 20,
+            ThingType::ShotgunGuy => 30,
+            ThingType::HeavyWeaponDude => 70,
+            ThingType::WolfensteinSS => 50,
+            ThingType::Imp => 60,
+            ThingType::Demon
+            | ThingType::Spectre => 150,
+            ThingType::LostSoul => 100,
+            ThingType::Cacodemon => 400,
+            ThingType::PainElemental => 400,
+            ThingType::HellKnight => 500,
+            ThingType::BaronOfHell => 1000,
+            ThingType::Arachnotron => 500,
+            ThingType::Revenant => 300,
+            ThingType::Mancubus => 600,
+            ThingType::ArchVile => 700,
+            ThingType::SpiderDemon => 3000,
+            ThingType::Cyberdemon => 4000,
+            ThingType::CommanderKeen => 100,
+            _ => 1000
+        }
+    }
+
+    // Chance (out of 256) to enter the pain state when hurt
+    pub fn pain_chance(&self) -> i32 {
+        match self {
+            ThingType::ZombieMan => 200,
+            ThingType::ShotgunGuy
+            | ThingType::HeavyWeaponDude
+            | ThingType::WolfensteinSS => 170,
+            ThingType::Imp => 200,
+            ThingType::Demon
+            | ThingType::Spectre => 180,
+            ThingType::LostSoul => 256,
+            ThingType::Cacodemon
+            | ThingType::PainElemental
+            | ThingType::Arachnotron => 128,
+            ThingType::HellKnight
+            | ThingType::BaronOfHell => 50,
+            ThingType::Revenant => 100,
+            ThingType::Mancubus => 80,
+            ThingType::ArchVile => 10,
+            ThingType::SpiderDemon => 40,
+            ThingType::Cyberdemon => 20,
+            ThingType::CommanderKeen => 256,
+            _ => 255
+        }
+    }
+
+    // Sounds as the mobjinfo seesound, painsound and deathsound, one of them is picked at random
+    pub fn sight_sounds(&self) -> &'static [&'static str] {
+        match self {
+            ThingType::ZombieMan
+            | ThingType::ShotgunGuy
+            | ThingType::HeavyWeaponDude => &["posit1", "posit2", "posit3"],
+            ThingType::Imp => &["bgsit1", "bgsit2"],
+            ThingType::Demon
+            | ThingType::Spectre => &["sgtsit"],
+            ThingType::Cacodemon => &["cacsit"],
+            ThingType::HellKnight => &["kntsit"],
+            ThingType::BaronOfHell => &["brssit"],
+            ThingType::Arachnotron => &["bspsit"],
+            ThingType::PainElemental => &["pesit"],
+            ThingType::Revenant => &["skesit"],
+            ThingType::Mancubus => &["mansit"],
+            ThingType::ArchVile => &["vilsit"],
+            ThingType::SpiderDemon => &["spisit"],
+            ThingType::Cyberdemon => &["cybsit"],
+            ThingType::WolfensteinSS => &["sssit"],
+            _ => &[]
+        }
+    }
+
+    pub fn pain_sound(&self) -> Option<&'static str> {
+        match self {
+            ThingType::ZombieMan
+            | ThingType::ShotgunGuy
+            | ThingType::HeavyWeaponDude
+            | ThingType::Imp
+            | ThingType::Revenant
+            | ThingType::WolfensteinSS => Some("popain"),
+            ThingType::PainElemental => Some("pepain"),
+            ThingType::Mancubus => Some("mnpain"),
+            ThingType::ArchVile => Some("vipain"),
+            ThingType::CommanderKeen => Some("keenpn"),
+            _ if self.counts_kill() || *self == ThingType::LostSoul => Some("dmpain"),
+            _ => None
+        }
+    }
+
+    pub fn death_sounds(&self) -> &'static [&'static str] {
+        match self {
+            ThingType::ZombieMan
+            | ThingType::ShotgunGuy
+            | ThingType::HeavyWeaponDude => &["podth1", "podth2", "podth3"],
+            ThingType::Imp => &["bgdth1", "bgdth2"],
+            ThingType::Demon
+            | ThingType::Spectre => &["sgtdth"],
+            ThingType::LostSoul => &["firxpl"],
+            ThingType::Cacodemon => &["cacdth"],
+            ThingType::HellKnight => &["kntdth"],
+            ThingType::BaronOfHell => &["brsdth"],
+            ThingType::Arachnotron => &["bspdth"],
+            ThingType::PainElemental => &["pedth"],
+            ThingType::Revenant => &["skedth"],
+            ThingType::Mancubus => &["mandth"],
+            ThingType::ArchVile => &["vildth"],
+            ThingType::SpiderDemon => &["spidth"],
+            ThingType::Cyberdemon => &["cybdth"],
+            ThingType::WolfensteinSS => &["ssdth"],
+            ThingType::CommanderKeen => &["keendt"],
+            _ => &[]
+        }
+    }
+
+    // As P_TouchSpecialThing
+    pub fn pickup_sound(&self) -> &'static str {
+        match self {
+            ThingType::BFG9000
+            | ThingType::Chaingun
+            | ThingType::Chainsaw
+            | ThingType::PlasmaRifle
+            | ThingType::RocketLauncher
+            | ThingType::Shotgun
+            | ThingType::SuperShotgun => "wpnup",
+            ThingType::Berserk
+            | ThingType::ComputerMap
+            | ThingType::Invulnerability
+            | ThingType::LightAmp
+            | ThingType::Megasphere
+            | ThingType::PartialInvisibility
+            | ThingType::Soulsphere
+            | ThingType::RadSuit => "getpow",
+            _ => "itemup"
+        }
+    }
+
+    // Things the player picks up by touching them
+    pub fn is_pickup(&self) -> bool {
+        matches!(self,
+            // Weapons
+            ThingType::BFG9000
+            | ThingType::Chaingun
+            | ThingType::Chainsaw
+            | ThingType::PlasmaRifle
+            | ThingType::RocketLauncher
+            | ThingType::Shotgun
+            | ThingType::SuperShotgun
+            // Ammo
+            | ThingType::Shell4
+            | ThingType::BulletBox
+            | ThingType::RocketBox
+            | ThingType::ShellBox
+            | ThingType::Clip
+            | ThingType::EnergyCell
+            | ThingType::EnergyPack
+            | ThingType::Rocket
+            // Artifacts
+            | ThingType::ArmorBonus
+            | ThingType::Berserk
+            | ThingType::ComputerMap
+            | ThingType::HealthBonus
+            | ThingType::Invulnerability
+            | ThingType::LightAmp
+            | ThingType::Megasphere
+            | ThingType::PartialInvisibility
+            | ThingType::Soulsphere
+            // Powerups
+            | ThingType::GreenArmor
+            | ThingType::Backpack
+            | ThingType::Medikit
+            | ThingType::BlueArmor
+            | ThingType::RadSuit
+            | ThingType::Stimpack
+            // Keys
+            | ThingType::BlueCard
+            | ThingType::BlueSkull
+            | ThingType::RedCard
+            | ThingType::RedSkull
+            | ThingType::YellowCard
+            | ThingType::YellowSkull
+        )
+    }
+
+    // Monsters counted by the kill percentage, the lost souls are not, as MF_COUNTKILL
+    pub fn counts_kill(&self) -> bool {
+        matches!(self,
+            ThingType::Arachnotron
+            | ThingType::ArchVile
+            | ThingType::BaronOfHell
+            | ThingType::Cacodemon
+            | ThingType::CommanderKeen
+            | ThingType::Cyberdemon
+            | ThingType::Demon
+            | ThingType::HeavyWeaponDude
+            | ThingType::HellKnight
+            | ThingType::Imp
+            | ThingType::Mancubus
+            | ThingType::PainElemental
+            | ThingType::Revenant
+            | ThingType::ShotgunGuy
+            | ThingType::Spectre
+            | ThingType::SpiderDemon
+            | ThingType::WolfensteinSS
+            | ThingType::ZombieMan
+        )
+    }
+
+    // Artifacts counted by the item percentage, as MF_COUNTITEM
+    pub fn counts_item(&self) -> bool {
+        matches!(self,
+            ThingType::ArmorBonus
+            | ThingType::Berserk
+            | ThingType::ComputerMap
+            | ThingType::HealthBonus
+            | ThingType::Invulnerability
+            | ThingType::LightAmp
+            | ThingType::Megasphere
+            | ThingType::PartialInvisibility
+            | ThingType::Soulsphere
+        )
+    }
+
+    // Item left behind by a dead monster, as in A_Fall/P_KillMobj
+    pub fn dropped_item(&self) -> Option<ThingType> {
+        match self {
+            ThingType::WolfensteinSS
+            | ThingType::ZombieMan => Some(ThingType::Clip),
+            ThingType::ShotgunGuy => Some(ThingType::Shotgun),
+            ThingType::HeavyWeaponDude => Some(ThingType::Chaingun),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Skill {
+    Baby,
+    Easy,
+    Medium,
+    Hard,
+    Nightmare
+}
+
+impl Skill {
+    pub const COUNT: usize = 5;
+    pub const ALL: [Skill; Skill::COUNT] = [Skill::Baby, Skill::Easy, Skill::Medium, Skill::Hard, Skill::Nightmare];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    // Things placed on this skill, as in P_SpawnMapThing
+    pub fn spawns(&self, thing: &Thing) -> bool {
+        if thing.has_flag(ThingFlags::NotSinglePlayer) {
+            return false;
+        }
+        match self {
+            Skill::Baby | Skill::Easy => thing.has_flag(ThingFlags::Easy),
+            Skill::Medium => thing.has_flag(ThingFlags::Medium),
+            Skill::Hard | Skill::Nightmare => thing.has_flag(ThingFlags::Hard),
+        }
+    }
 }
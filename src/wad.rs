@@ -194,4 +194,11 @@ impl  Reader {
         }
     }
 
+    // Raw content of a lump, case insensitive
+    pub fn lump<'a>(&'a self, name: &str) -> Option<&'a [u8]> {
+        let directories = self.directories()?;
+        let directory = directories[directories.index_of(&name.to_string())?];
+        return self.buffer.get(directory.start()..directory.end());
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,589 @@
+#![allow(dead_code)]
+// Engine
+use crate::actors::{Actor, Player};
+use crate::attack::{melee_attack, Hitscan};
+use crate::doom::Doom;
+use crate::inventory::{PowerType, WeaponType};
+use crate::math::Vector2;
+use crate::projectiles::{Projectile, ProjectileType};
+use crate::time::tics;
+
+mod consts {
+    // Psprite positions, in 320x200 screen units
+    pub const WEAPON_TOP: f32 = 32.0;
+    pub const WEAPON_BOTTOM: f32 = 128.0;
+    pub const RAISE_SPEED: f32 = 6.0;
+    pub const LOWER_SPEED: f32 = 6.0;
+    // Bobbing
+    pub const MAX_BOB: f32 = 16.0;
+    pub const BOB_PERIOD_TICS: f32 = 64.0;
+    // Melee
+    pub const MELEE_RANGE: f32 = 64.0;
+    pub const SAW_RANGE: f32 = 65.0;
+    pub const BERSERK_FACTOR: i32 = 10;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    None,
+    Fire,
+    ReFire,
+    Flash,
+    CheckReload
+}
+
+// A frame of the weapon (or flash) animation, as the S_* states
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    frame: u8,
+    tics: u32,
+    action: Action
+}
+
+const fn frame(frame: u8, tics: u32, action: Action) -> Frame {
+    Frame { frame: frame - b'A', tics, action }
+}
+
+// Animations
+const CHAINSAW_READY: &[Frame] = &[frame(b'C', 4, Action::None), frame(b'D', 4, Action::None)];
+
+const DEFAULT_READY: &[Frame] = &[frame(b'A', 1, Action::None)];
+
+const FIST_ATTACK: &[Frame] = &[
+    frame(b'B', 4, Action::None),
+    frame(b'C', 4, Action::Fire),
+    frame(b'D', 5, Action::None),
+    frame(b'C', 4, Action::None),
+    frame(b'B', 5, Action::ReFire),
+];
+
+const PISTOL_ATTACK: &[Frame] = &[
+    frame(b'A', 4, Action::None),
+    frame(b'B', 6, Action::Fire),
+    frame(b'C', 4, Action::None),
+    frame(b'B', 5, Action::ReFire),
+];
+
+const SHOTGUN_ATTACK: &[Frame] = &[
+    frame(b'A', 3, Action::None),
+    frame(b'A', 7, Action::Fire),
+    frame(b'B', 5, Action::None),
+    frame(b'C', 5, Action::None),
+    frame(b'D', 4, Action::None),
+    frame(b'C', 5, Action::None),
+    frame(b'B', 5, Action::None),
+    frame(b'A', 3, Action::None),
+    frame(b'A', 7, Action::ReFire),
+];
+
+const CHAINGUN_ATTACK: &[Frame] = &[
+    frame(b'A', 4, Action::Fire),
+    frame(b'B', 4, Action::Fire),
+    frame(b'B', 0, Action::ReFire),
+];
+
+const ROCKET_ATTACK: &[Frame] = &[
+    frame(b'B', 8, Action::Flash),
+    frame(b'B', 12, Action::Fire),
+    frame(b'B', 0, Action::ReFire),
+];
+
+const PLASMA_ATTACK: &[Frame] = &[
+    frame(b'A', 3, Action::Fire),
+    frame(b'B', 20, Action::ReFire),
+];
+
+const BFG_ATTACK: &[Frame] = &[
+    frame(b'A', 20, Action::None),
+    frame(b'B', 10, Action::Flash),
+    frame(b'B', 10, Action::Fire),
+    frame(b'B', 20, Action::ReFire),
+];
+
+const CHAINSAW_ATTACK: &[Frame] = &[
+    frame(b'A', 4, Action::Fire),
+    frame(b'B', 4, Action::Fire),
+    frame(b'B', 0, Action::ReFire),
+];
+
+const SUPER_SHOTGUN_ATTACK: &[Frame] = &[
+    frame(b'A', 3, Action::None),
+    frame(b'A', 7, Action::Fire),
+    frame(b'B', 7, Action::None),
+    frame(b'C', 7, Action::CheckReload),
+    frame(b'D', 7, Action::None),
+    frame(b'E', 7, Action::None),
+    frame(b'F', 7, Action::None),
+    frame(b'G', 6, Action::None),
+    frame(b'H', 6, Action::None),
+    frame(b'A', 5, Action::ReFire),
+];
+
+const PISTOL_FLASH: &[Frame] = &[frame(b'A', 7, Action::None)];
+
+const SHOTGUN_FLASH: &[Frame] = &[frame(b'A', 4, Action::None), frame(b'B', 3, Action::None)];
+
+const CHAINGUN_FLASH: &[Frame] = &[frame(b'A', 5, Action::None), frame(b'B', 5, Action::None)];
+
+const ROCKET_FLASH: &[Frame] = &[
+    frame(b'A', 3, Action::None),
+    frame(b'B', 4, Action::None),
+    frame(b'C', 4, Action::None),
+    frame(b'D', 4, Action::None),
+];
+
+const PLASMA_FLASH: &[Frame] = &[frame(b'A', 4, Action::None), frame(b'B', 4, Action::None)];
+
+const BFG_FLASH: &[Frame] = &[frame(b'A', 11, Action::None), frame(b'B', 6, Action::None)];
+
+const SUPER_SHOTGUN_FLASH: &[Frame] = &[frame(b'I', 4, Action::None), frame(b'J', 3, Action::None)];
+
+const NO_FLASH: &[Frame] = &[];
+
+impl WeaponType {
+    pub fn sprite(&self) -> &'static [u8; 4] {
+        match self {
+            WeaponType::Fist => b"PUNG",
+            WeaponType::Pistol => b"PISG",
+            WeaponType::Shotgun => b"SHTG",
+            WeaponType::Chaingun => b"CHGG",
+            WeaponType::RocketLauncher => b"MISG",
+            WeaponType::PlasmaRifle => b"PLSG",
+            WeaponType::BFG => b"BFGG",
+            WeaponType::Chainsaw => b"SAWG",
+            WeaponType::SuperShotgun => b"SHT2",
+        }
+    }
+
+    fn flash_sprite(&self) -> &'static [u8; 4] {
+        match self {
+            WeaponType::Pistol => b"PISF",
+            WeaponType::Shotgun => b"SHTF",
+            WeaponType::Chaingun => b"CHGF",
+            WeaponType::RocketLauncher => b"MISF",
+            WeaponType::PlasmaRifle => b"PLSF",
+            WeaponType::BFG => b"BFGF",
+            _ => self.sprite()
+        }
+    }
+
+    fn ready_frames(&self) -> &'static [Frame] {
+        match self {
+            WeaponType::Chainsaw => CHAINSAW_READY,
+            _ => DEFAULT_READY
+        }
+    }
+
+    fn attack_frames(&self) -> &'static [Frame] {
+        match self {
+            WeaponType::Fist => FIST_ATTACK,
+            WeaponType::Pistol => PISTOL_ATTACK,
+            WeaponType::Shotgun => SHOTGUN_ATTACK,
+            WeaponType::Chaingun => CHAINGUN_ATTACK,
+            WeaponType::RocketLauncher => ROCKET_ATTACK,
+            WeaponType::PlasmaRifle => PLASMA_ATTACK,
+            WeaponType::BFG => BFG_ATTACK,
+            WeaponType::Chainsaw => CHAINSAW_ATTACK,
+            WeaponType::SuperShotgun => SUPER_SHOTGUN_ATTACK,
+        }
+    }
+
+    fn flash_frames(&self) -> &'static [Frame] {
+        match self {
+            WeaponType::Pistol => PISTOL_FLASH,
+            WeaponType::Shotgun => SHOTGUN_FLASH,
+            WeaponType::Chaingun => CHAINGUN_FLASH,
+            WeaponType::RocketLauncher => ROCKET_FLASH,
+            WeaponType::PlasmaRifle => PLASMA_FLASH,
+            WeaponType::BFG => BFG_FLASH,
+            WeaponType::SuperShotgun => SUPER_SHOTGUN_FLASH,
+            _ => NO_FLASH
+        }
+    }
+
+    // Number key bound to the weapon slot
+    pub fn slot(&self) -> u8 {
+        match self {
+            WeaponType::Fist | WeaponType::Chainsaw => 1,
+            WeaponType::Pistol => 2,
+            WeaponType::Shotgun | WeaponType::SuperShotgun => 3,
+            WeaponType::Chaingun => 4,
+            WeaponType::RocketLauncher => 5,
+            WeaponType::PlasmaRifle => 6,
+            WeaponType::BFG => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WeaponMode {
+    Raise,
+    Ready,
+    Attack,
+    Lower
+}
+
+// What the renderer needs to draw an overlay
+#[derive(Debug, Clone, Copy)]
+pub struct Psprite {
+    pub name: [u8; 8],
+    // Psprite origin, in 320x200 screen units
+    pub x: f32,
+    pub y: f32,
+    // Flashes ignore the sector light
+    pub full_bright: bool,
+}
+
+// An animation cursor into a frame table
+#[derive(Debug, Clone, Copy)]
+struct FrameCursor {
+    index: usize,
+    tics: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerWeapon {
+    weapon: WeaponType,
+    mode: WeaponMode,
+    frame: FrameCursor,
+    flash: Option<FrameCursor>,
+    // Psprite position
+    y: f32,
+    bob: Vector2<f32>,
+    // Consecutive shots, the first one is accurate
+    refire: u32,
+    // Input
+    fire: bool,
+    slot: Option<u8>,
+    // Tics
+    think_time: f32,
+    level_tics: u32,
+}
+
+impl Default for PlayerWeapon {
+    fn default() -> Self {
+        PlayerWeapon::new(WeaponType::Pistol)
+    }
+}
+
+impl PlayerWeapon {
+    pub fn new(weapon: WeaponType) -> Self {
+        PlayerWeapon {
+            weapon: weapon,
+            mode: WeaponMode::Raise,
+            frame: FrameCursor { index: 0, tics: 0 },
+            flash: None,
+            y: consts::WEAPON_BOTTOM,
+            bob: Vector2::zeros(),
+            refire: 0,
+            fire: false,
+            slot: None,
+            think_time: 0.0,
+            level_tics: 0,
+        }
+    }
+
+    pub fn weapon(&self) -> WeaponType {
+        self.weapon
+    }
+
+    pub fn mode(&self) -> WeaponMode {
+        self.mode
+    }
+
+    pub fn refire(&self) -> u32 {
+        self.refire
+    }
+
+    // Input, latched until the next tic
+    pub fn set_fire(&mut self, fire: bool) {
+        self.fire = self.fire || fire;
+    }
+
+    pub fn select_slot(&mut self, slot: u8) {
+        self.slot = Some(slot);
+    }
+
+    // Overlays to draw, weapon first then its flash
+    pub fn psprites(&self) -> Vec<Psprite> {
+        let mut psprites = vec![];
+        let x = 1.0 + self.bob.x;
+        let y = self.y + self.bob.y;
+        let frames = match self.mode {
+            WeaponMode::Attack => self.weapon.attack_frames(),
+            _ => self.weapon.ready_frames()
+        };
+        let index = if self.mode == WeaponMode::Attack || self.mode == WeaponMode::Ready { self.frame.index } else { 0 };
+        psprites.push(Psprite {
+            name: sprite_name(self.weapon.sprite(), frames[index.min(frames.len() - 1)].frame),
+            x, y,
+            full_bright: false
+        });
+        if let Some(flash) = self.flash {
+            let frames = self.weapon.flash_frames();
+            if flash.index < frames.len() {
+                psprites.push(Psprite {
+                    name: sprite_name(self.weapon.flash_sprite(), frames[flash.index].frame),
+                    x, y,
+                    full_bright: true
+                });
+            }
+        }
+        return psprites;
+    }
+
+    // As P_MovePsprites, run once per tic
+    pub fn update<'wad>(&mut self, engine: &Doom<'wad>, player: &mut Player, last_frame_time: f32) {
+        self.think_time += last_frame_time;
+        while self.think_time >= tics(1) {
+            self.think_time -= tics(1);
+            self.level_tics += 1;
+            self.tic(engine, player);
+        }
+    }
+
+    fn tic<'wad>(&mut self, engine: &Doom<'wad>, player: &mut Player) {
+        // Weapon change
+        if let Some(slot) = self.slot.take() {
+            self.change_weapon(player, slot);
+        }
+        let alive = player.health() > 0;
+        match self.mode {
+            WeaponMode::Lower => {
+                self.y += consts::LOWER_SPEED;
+                if self.y >= consts::WEAPON_BOTTOM {
+                    self.y = consts::WEAPON_BOTTOM;
+                    // Dead players keep the weapon down
+                    if alive {
+                        self.bring_up(player);
+                    }
+                }
+            }
+            WeaponMode::Raise => {
+                self.y -= consts::RAISE_SPEED;
+                if self.y <= consts::WEAPON_TOP {
+                    self.y = consts::WEAPON_TOP;
+                    self.set_ready();
+                }
+            }
+            WeaponMode::Ready => {
+                self.weapon_ready(engine, player, alive);
+            }
+            WeaponMode::Attack => {
+                self.frame.tics = self.frame.tics.saturating_sub(1);
+                while self.mode == WeaponMode::Attack && self.frame.tics == 0 {
+                    self.frame.index += 1;
+                    let frames = self.weapon.attack_frames();
+                    if self.frame.index >= frames.len() {
+                        self.set_ready();
+                        break;
+                    }
+                    self.frame.tics = frames[self.frame.index].tics;
+                    self.run_action(engine, player, frames[self.frame.index].action);
+                }
+            }
+        }
+        // Flash
+        if let Some(mut flash) = self.flash {
+            flash.tics = flash.tics.saturating_sub(1);
+            if flash.tics == 0 {
+                flash.index += 1;
+                let frames = self.weapon.flash_frames();
+                self.flash = if flash.index < frames.len() {
+                    Some(FrameCursor { index: flash.index, tics: frames[flash.index].tics })
+                } else {
+                    None
+                };
+            } else {
+                self.flash = Some(flash);
+            }
+        }
+        self.fire = false;
+    }
+
+    // As A_WeaponReady, bob the weapon and wait for the fire button
+    fn weapon_ready<'wad>(&mut self, engine: &Doom<'wad>, player: &mut Player, alive: bool) {
+        // Ready animation
+        let frames = self.weapon.ready_frames();
+        self.frame.tics = self.frame.tics.saturating_sub(1);
+        if self.frame.tics == 0 {
+            self.frame.index = (self.frame.index + 1) % frames.len();
+            self.frame.tics = frames[self.frame.index].tics;
+        }
+        // Put the weapon away
+        if player.inventory().pending_weapon().is_some() || !alive {
+            self.mode = WeaponMode::Lower;
+            self.bob = Vector2::zeros();
+            return;
+        }
+        // Fire
+        if self.fire {
+            if self.check_ammo(player) {
+                self.start_attack(engine, player);
+                return;
+            }
+        } else {
+            self.refire = 0;
+        }
+        // Bob
+        let speed = (player.get_transform().position() - player.get_last_transform().position()).magnitude();
+        let bob = (speed * speed / 4.0).min(consts::MAX_BOB);
+        let angle = std::f32::consts::TAU * self.level_tics as f32 / consts::BOB_PERIOD_TICS;
+        self.bob = Vector2::new(bob * angle.cos(), bob * angle.sin().abs());
+    }
+
+    fn set_ready(&mut self) {
+        self.mode = WeaponMode::Ready;
+        self.frame = FrameCursor { index: 0, tics: self.weapon.ready_frames()[0].tics };
+    }
+
+    fn start_attack<'wad>(&mut self, engine: &Doom<'wad>, player: &mut Player) {
+        let frames = self.weapon.attack_frames();
+        self.mode = WeaponMode::Attack;
+        self.bob = Vector2::zeros();
+        self.frame = FrameCursor { index: 0, tics: frames[0].tics };
+        self.run_action(engine, player, frames[0].action);
+    }
+
+    fn start_flash(&mut self, offset: usize) {
+        let frames = self.weapon.flash_frames();
+        if offset < frames.len() {
+            self.flash = Some(FrameCursor { index: offset, tics: frames[offset].tics });
+        }
+    }
+
+    // Switch to the pending weapon from the bottom of the screen
+    fn bring_up(&mut self, player: &mut Player) {
+        if let Some(pending) = player.inventory().pending_weapon() {
+            self.weapon = pending;
+        }
+        player.inventory_mut().set_pending_weapon(None);
+        self.mode = WeaponMode::Raise;
+        self.flash = None;
+        self.refire = 0;
+    }
+
+    // Number keys, as in P_PlayerThink
+    fn change_weapon(&mut self, player: &mut Player, slot: u8) {
+        let inventory = player.inventory();
+        let mut weapon = match WeaponType::ALL.iter().find(|weapon| weapon.slot() == slot) {
+            Some(weapon) => *weapon,
+            None => return
+        };
+        if weapon == WeaponType::Fist
+        && inventory.has_weapon(WeaponType::Chainsaw)
+        && !(self.weapon == WeaponType::Chainsaw && inventory.has_power(PowerType::Strength)) {
+            weapon = WeaponType::Chainsaw;
+        }
+        if weapon == WeaponType::Shotgun
+        && inventory.has_weapon(WeaponType::SuperShotgun)
+        && self.weapon != WeaponType::SuperShotgun {
+            weapon = WeaponType::SuperShotgun;
+        }
+        if inventory.has_weapon(weapon) && weapon != self.weapon {
+            player.inventory_mut().set_pending_weapon(Some(weapon));
+        }
+    }
+
+    // As P_CheckAmmo, switch to the best weapon when empty
+    fn check_ammo(&mut self, player: &mut Player) -> bool {
+        if player.inventory().has_ammo_for(self.weapon) {
+            return true;
+        }
+        let best = player.inventory().best_weapon();
+        player.inventory_mut().set_pending_weapon(Some(best));
+        self.mode = WeaponMode::Lower;
+        return false;
+    }
+
+    fn run_action<'wad>(&mut self, engine: &Doom<'wad>, player: &mut Player, action: Action) {
+        match action {
+            Action::None => (),
+            Action::Flash => self.start_flash(0),
+            Action::Fire => self.fire_weapon(engine, player),
+            // As A_ReFire
+            Action::ReFire => {
+                if self.fire && player.health() > 0 && player.inventory().pending_weapon().is_none() {
+                    self.refire += 1;
+                    if self.check_ammo(player) {
+                        self.start_attack(engine, player);
+                    }
+                } else {
+                    self.refire = 0;
+                    self.check_ammo(player);
+                }
+            }
+            Action::CheckReload => {
+                self.check_ammo(player);
+            }
+        }
+    }
+
+    fn fire_weapon<'wad>(&mut self, engine: &Doom<'wad>, player: &mut Player) {
+        let weapon = self.weapon;
+        if !player.inventory().has_ammo_for(weapon) {
+            return;
+        }
+        if let Some(ammo) = weapon.ammo() {
+            player.inventory_mut().use_ammo(ammo, weapon.ammo_per_shot());
+        }
+        let refire = self.refire > 0;
+        let shooter: &dyn Actor = player;
+        // As vanilla the fist, the chainsaw and the plasma rifle make no noise
+        if !matches!(weapon, WeaponType::Fist | WeaponType::Chainsaw | WeaponType::PlasmaRifle) {
+            engine.noise_alert(shooter);
+        }
+        match weapon {
+            WeaponType::Fist => {
+                let mut damage = (engine.random.borrow_mut().next() % 10 + 1) * 2;
+                if player.inventory().has_power(PowerType::Strength) {
+                    damage *= consts::BERSERK_FACTOR;
+                }
+                melee_attack(engine, shooter, damage, consts::MELEE_RANGE);
+            }
+            WeaponType::Chainsaw => {
+                let damage = 2 * (engine.random.borrow_mut().next() % 10 + 1);
+                engine.start_sound("sawful", Some(shooter));
+                melee_attack(engine, shooter, damage, consts::SAW_RANGE);
+            }
+            WeaponType::Pistol => {
+                self.start_flash(0);
+                engine.start_sound("pistol", Some(shooter));
+                Hitscan::Pistol.fire(engine, shooter, refire);
+            }
+            WeaponType::Shotgun => {
+                self.start_flash(0);
+                engine.start_sound("shotgn", Some(shooter));
+                Hitscan::Shotgun.fire(engine, shooter, refire);
+            }
+            WeaponType::SuperShotgun => {
+                self.start_flash(0);
+                engine.start_sound("dshtgn", Some(shooter));
+                Hitscan::SuperShotgun.fire(engine, shooter, refire);
+            }
+            WeaponType::Chaingun => {
+                // The flash follows the firing frame
+                self.start_flash(self.frame.index.min(1));
+                engine.start_sound("pistol", Some(shooter));
+                Hitscan::Chaingun.fire(engine, shooter, refire);
+            }
+            WeaponType::RocketLauncher => {
+                engine.start_sound("rlaunc", Some(shooter));
+                Projectile::spawn_player_missile(engine, ProjectileType::Rocket, shooter);
+            }
+            WeaponType::PlasmaRifle => {
+                self.start_flash((engine.random.borrow_mut().next() & 1) as usize);
+                engine.start_sound("plasma", Some(shooter));
+                Projectile::spawn_player_missile(engine, ProjectileType::Plasma, shooter);
+            }
+            WeaponType::BFG => {
+                engine.start_sound("bfg", Some(shooter));
+                Projectile::spawn_player_missile(engine, ProjectileType::BFG, shooter);
+            }
+        }
+    }
+}
+
+fn sprite_name(sprite: &[u8; 4], frame: u8) -> [u8; 8] {
+    [sprite[0], sprite[1], sprite[2], sprite[3], b'A' + frame, b'0', 0, 0]
+}
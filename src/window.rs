@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 use std::sync::Arc;
 // Using, d3d
+use crate::data_textures::{nearest_index, Palette};
 use crate::math::Vector2;
 use crate::shape::Size;
 use crate::time::{Time, TimeTrait};
@@ -12,12 +13,37 @@ use winit::{
     error::EventLoopError,
     event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
+    window::{CursorGrabMode, Window, WindowBuilder},
 };
 
+// PLAYPAL indices of the frame, converted to RGBA on swap
+pub struct IndexedFrame {
+    indices: Vec<u8>,
+    palette: [[u8; 3]; 256],
+    // First palette, the RGBA draws take its nearest color
+    base: [[u8; 3]; 256],
+    // Nearest index of the 15 bits colors already drawn
+    nearest: Vec<Option<u8>>,
+}
+
+impl IndexedFrame {
+    fn nearest(&mut self, color: &[u8]) -> u8 {
+        let key = (color[0] as usize >> 3) << 10 | (color[1] as usize >> 3) << 5 | color[2] as usize >> 3;
+        match self.nearest[key] {
+            Some(index) => index,
+            None => {
+                let index = nearest_index(&self.base, color);
+                self.nearest[key] = Some(index);
+                index
+            }
+        }
+    }
+}
+
 pub struct DoomSurface {
     pub size: PhysicalSize<u32>,
     pub pixels: Pixels,
+    indexed: Option<IndexedFrame>,
 }
 
 #[readonly::make]
@@ -60,23 +86,86 @@ impl DoomSurface {
             .enable_vsync(vsync)
             .build()
         {
-            Some(DoomSurface { size, pixels })
+            Some(DoomSurface { size, pixels, indexed: None })
         } else {
             None
         }
     }
 
     pub fn clear(&mut self, color: [u8; 4]) {
+        if let Some(indexed) = &mut self.indexed {
+            let index = indexed.nearest(&color);
+            indexed.indices.fill(index);
+            return;
+        }
         let frame = self.pixels.frame_mut();
         let size = (frame.len() / color.len()) as usize;
         frame.copy_from_slice(&color.repeat(size));
     }
 
     pub fn swap(&mut self) -> Result<(), Error> {
+        if let Some(indexed) = &self.indexed {
+            for (pixel, index) in self.pixels.frame_mut().chunks_exact_mut(4).zip(indexed.indices.iter()) {
+                pixel[0..3].copy_from_slice(&indexed.palette[*index as usize]);
+                pixel[3] = 0xFF;
+            }
+        }
         self.pixels.render()
     }
 
+    // Enables the 8-bit pipeline, the RGBA draws take the nearest index
+    pub fn enable_indexed(&mut self, palette: &Palette) {
+        let size = (self.size.width * self.size.height) as usize;
+        self.indexed = Some(IndexedFrame {
+            indices: vec![0; size],
+            palette: *palette.slices(),
+            base: *palette.slices(),
+            nearest: vec![None; 1 << 15],
+        });
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.indexed.is_some()
+    }
+
+    // Palette used by the next conversion
+    pub fn set_palette(&mut self, palette: &Palette) {
+        if let Some(indexed) = &mut self.indexed {
+            indexed.palette = *palette.slices();
+        }
+    }
+
+    pub fn draw_index_lt(&mut self, position: &Vector2<usize>, index: u8) {
+        if let Some(indexed) = &mut self.indexed {
+            indexed.indices[position.y * self.size.width as usize + position.x] = index;
+        }
+    }
+
+    // Pixels as stored, a PLAYPAL index or the RGBA channels each
+    pub fn channels(&self) -> usize {
+        if self.indexed.is_some() { 1 } else { 4 }
+    }
+
+    pub fn frame(&self) -> &[u8] {
+        match &self.indexed {
+            Some(indexed) => &indexed.indices,
+            None => self.pixels.frame()
+        }
+    }
+
+    pub fn frame_mut(&mut self) -> &mut [u8] {
+        match &mut self.indexed {
+            Some(indexed) => &mut indexed.indices,
+            None => self.pixels.frame_mut()
+        }
+    }
+
     pub fn draw_lt(&mut self, position: &Vector2<usize>, color: &[u8]) {
+        if let Some(indexed) = &mut self.indexed {
+            let index = indexed.nearest(color);
+            indexed.indices[position.y * self.size.width as usize + position.x] = index;
+            return;
+        }
         let size = self.pixels.texture().size();
         
         // Bounds check
@@ -132,6 +221,11 @@ impl DoomSurface {
         if position.x >= size.width as usize || position.y >= size.height as usize {
             return;
         }
+        if let Some(indexed) = &mut self.indexed {
+            let index = indexed.nearest(color);
+            indexed.indices[(size.height as usize - position.y - 1) * size.width as usize + position.x] = index;
+            return;
+        }
         let frame = self.pixels.frame_mut();
         let row_size = (size.width as usize) * channels; // 4 colors per byte
         let offset: usize =
@@ -287,6 +381,15 @@ pub fn doom_window<T: 'static>(
     }
 }
 
+// Hides the cursor and keeps it in the window, the mouse moves come as relative device events
+pub fn grab_cursor(window: &Window) -> bool {
+    let grabbed = window.set_cursor_grab(CursorGrabMode::Locked)
+        .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+        .is_ok();
+    window.set_cursor_visible(!grabbed);
+    return grabbed;
+}
+
 pub fn doom_loop<C, U, R, H, T>(
     event_loop: EventLoop<T>,
     window: Arc<Window>,
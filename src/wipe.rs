@@ -0,0 +1,89 @@
+#![allow(dead_code)]
+// Engine
+use crate::random::Random;
+use crate::time::tics;
+use crate::window::DoomSurface;
+
+mod consts {
+    // As f_wipe.c, the melt works on 2 pixels wide columns of a 320x200 screen
+    pub const COLUMNS: usize = 160;
+    pub const HEIGHT: i32 = 200;
+    pub const MAX_START_DELAY: i32 = 16;
+    pub const MAX_STEP: i32 = 8;
+}
+
+// Column melt from the previous frame to the new one, as wipe_initMelt and wipe_doMelt
+pub struct Wipe {
+    // Previous frame, stored as the surface does
+    start: Vec<u8>,
+    channels: usize,
+    width: usize,
+    height: usize,
+    // Melted rows of each column, negative ones are still waiting
+    columns: Vec<i32>,
+    time: f32,
+}
+
+impl Wipe {
+    // Captures the frame currently on the surface
+    pub fn new(surface: &DoomSurface) -> Self {
+        let mut random = Random::new();
+        let mut columns = vec![0; consts::COLUMNS];
+        columns[0] = -(random.next() % consts::MAX_START_DELAY);
+        for i in 1..consts::COLUMNS {
+            let step = random.next() % 3 - 1;
+            columns[i] = (columns[i - 1] + step).clamp(1 - consts::MAX_START_DELAY, 0);
+        }
+        Wipe {
+            start: surface.frame().to_vec(),
+            channels: surface.channels(),
+            width: surface.size.width as usize,
+            height: surface.size.height as usize,
+            columns: columns,
+            time: 0.0,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.columns.iter().all(|y| *y >= consts::HEIGHT)
+    }
+
+    fn tic(&mut self) {
+        for y in self.columns.iter_mut() {
+            if *y < 0 {
+                *y += 1;
+            } else if *y < consts::HEIGHT {
+                let step = if *y < consts::MAX_START_DELAY { *y + 1 } else { consts::MAX_STEP };
+                *y = (*y + step).min(consts::HEIGHT);
+            }
+        }
+    }
+
+    // The melt runs at the tic rate
+    pub fn update(&mut self, last_frame_time: f32) {
+        self.time += last_frame_time;
+        while self.time >= tics(1) && !self.is_done() {
+            self.time -= tics(1);
+            self.tic();
+        }
+    }
+
+    // Draws the previous frame over the new one, each column moved down by its melt
+    pub fn draw(&self, surface: &mut DoomSurface) {
+        let channels = self.channels;
+        let frame = surface.frame_mut();
+        if frame.len() != self.start.len() {
+            return;
+        }
+        let stride = self.width * channels;
+        for x in 0..self.width {
+            let column = x * consts::COLUMNS / self.width;
+            let melt = self.columns[column].max(0) as usize * self.height / consts::HEIGHT as usize;
+            for y in melt..self.height {
+                let to = y * stride + x * channels;
+                let from = (y - melt) * stride + x * channels;
+                frame[to..to + channels].copy_from_slice(&self.start[from..from + channels]);
+            }
+        }
+    }
+}